@@ -0,0 +1,192 @@
+use std::{collections::HashSet, env, str::FromStr, sync::LazyLock, time::Duration};
+
+use anyhow::{Result, anyhow};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    address_lookup_table::{
+        AddressLookupTableAccount,
+        instruction::{create_lookup_table, extend_lookup_table},
+        state::{AddressLookupTable, LOOKUP_TABLE_MAX_ADDRESSES},
+    },
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+// addresses per extend instruction, keeps each extend tx under the size limit
+pub const EXTEND_CHUNK_SIZE: usize = 30;
+const ACTIVATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Our own lookup table, merged with jupiter's when compiling arb txs
+pub static OWN_ALT: LazyLock<Option<Pubkey>> = LazyLock::new(|| {
+    env::var("OWN_ALT")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| Pubkey::from_str(s.trim()).expect("OWN_ALT must be a valid pubkey"))
+});
+
+/// Create a lookup table owned by the payer, returns its address
+pub fn create(client: &RpcClient, payer: &Keypair) -> Result<Pubkey> {
+    // the derivation slot must be in the SlotHashes sysvar, finalized is always there
+    let recent_slot = client.get_slot_with_commitment(CommitmentConfig::finalized())?;
+    let (ix, table) = create_lookup_table(payer.pubkey(), payer.pubkey(), recent_slot);
+    let signature = send(client, payer, ix)?;
+    info!("created lookup table {}, tx: {}", table, signature);
+    Ok(table)
+}
+
+/// Append the addresses missing from the table in chunks, then wait until
+/// the new entries can be used
+pub fn extend(
+    client: &RpcClient,
+    payer: &Keypair,
+    table: &Pubkey,
+    addresses: &[Pubkey],
+) -> Result<Vec<Signature>> {
+    let state = fetch(client, table)?;
+    let existing: HashSet<&Pubkey> = state.addresses.iter().collect();
+    let mut seen = HashSet::new();
+    let new_addresses: Vec<Pubkey> = addresses
+        .iter()
+        .filter(|address| !existing.contains(address) && seen.insert(**address))
+        .copied()
+        .collect();
+    if new_addresses.is_empty() {
+        info!("lookup table {} already holds every address", table);
+        return Ok(vec![]);
+    }
+    if state.addresses.len() + new_addresses.len() > LOOKUP_TABLE_MAX_ADDRESSES {
+        return Err(anyhow!(
+            "lookup table {} holds {} addresses, adding {} exceeds the limit of {}",
+            table,
+            state.addresses.len(),
+            new_addresses.len(),
+            LOOKUP_TABLE_MAX_ADDRESSES
+        ));
+    }
+
+    let mut signatures = vec![];
+    for chunk in new_addresses.chunks(EXTEND_CHUNK_SIZE) {
+        let ix = extend_lookup_table(*table, payer.pubkey(), Some(payer.pubkey()), chunk.to_vec());
+        let signature = send(client, payer, ix)?;
+        info!(
+            "extended lookup table {} with {} addresses, tx: {}",
+            table,
+            chunk.len(),
+            signature
+        );
+        signatures.push(signature);
+    }
+    wait_until_active(client, table)?;
+    Ok(signatures)
+}
+
+/// Block until every address of the table is usable. Entries added in a
+/// slot only become active from the next one, a tx compiled against them
+/// earlier fails.
+pub fn wait_until_active(client: &RpcClient, table: &Pubkey) -> Result<()> {
+    let last_extended_slot = fetch(client, table)?.meta.last_extended_slot;
+    let start = Instant::now();
+    loop {
+        let slot = client.get_slot()?;
+        if slot > last_extended_slot {
+            debug!(
+                "lookup table {} active at slot {} (last extended {})",
+                table, slot, last_extended_slot
+            );
+            return Ok(());
+        }
+        if start.elapsed() > ACTIVATION_TIMEOUT {
+            return Err(anyhow!(
+                "lookup table {} still not active after {:?}",
+                table,
+                ACTIVATION_TIMEOUT
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(400));
+    }
+}
+
+pub fn fetch(client: &RpcClient, table: &Pubkey) -> Result<AddressLookupTable<'static>> {
+    let account = client.get_account(table)?;
+    let state = AddressLookupTable::deserialize(&account.data)
+        .map_err(|e| anyhow!("{} is not a lookup table: {}", table, e))?;
+    Ok(AddressLookupTable {
+        meta: state.meta,
+        addresses: state.addresses.into_owned().into(),
+    })
+}
+
+/// The part of a table usable at `current_slot`, None once deactivated.
+/// Addresses appended in the current slot are left out.
+pub fn active_account(
+    key: Pubkey,
+    table: &AddressLookupTable,
+    current_slot: u64,
+) -> Option<AddressLookupTableAccount> {
+    if table.meta.deactivation_slot != u64::MAX {
+        return None;
+    }
+    let active_len = if current_slot > table.meta.last_extended_slot {
+        table.addresses.len()
+    } else {
+        table.meta.last_extended_slot_start_index as usize
+    };
+    if active_len == 0 {
+        return None;
+    }
+    Some(AddressLookupTableAccount {
+        key,
+        addresses: table.addresses[..active_len].to_vec(),
+    })
+}
+
+fn send(client: &RpcClient, payer: &Keypair, ix: Instruction) -> Result<Signature> {
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&payer.pubkey()),
+        &[payer],
+        client.get_latest_blockhash()?,
+    );
+    Ok(client.send_and_confirm_transaction(&tx)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use solana_sdk::{address_lookup_table::state::LookupTableMeta, pubkey::Pubkey};
+
+    use super::{AddressLookupTable, active_account};
+
+    #[test]
+    fn test_active_account() {
+        let addresses: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let mut table = AddressLookupTable {
+            meta: LookupTableMeta {
+                last_extended_slot: 100,
+                last_extended_slot_start_index: 3,
+                ..Default::default()
+            },
+            addresses: Cow::Owned(addresses.clone()),
+        };
+        let key = Pubkey::new_unique();
+
+        // the last extension isn't usable within its own slot
+        let account = active_account(key, &table, 100).unwrap();
+        assert_eq!(account.addresses, addresses[..3]);
+        let account = active_account(key, &table, 101).unwrap();
+        assert_eq!(account.addresses, addresses);
+
+        table.meta.last_extended_slot_start_index = 0;
+        assert!(active_account(key, &table, 100).is_none());
+
+        table.meta.deactivation_slot = 90;
+        assert!(active_account(key, &table, 101).is_none());
+    }
+}
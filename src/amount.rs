@@ -0,0 +1,41 @@
+use spl_token::{amount_to_ui_amount, amount_to_ui_amount_string_trimmed};
+
+/// Signed counterpart of `amount_to_ui_amount`, e.g. for a negative profit
+pub fn lamports_to_ui_signed(v: i64, decimals: u8) -> f64 {
+    let ui_amount = amount_to_ui_amount(v.unsigned_abs(), decimals);
+    if v < 0 { -ui_amount } else { ui_amount }
+}
+
+/// Exact sol amount of signed lamports, trailing zeros trimmed
+pub fn format_sol_signed(v: i64) -> String {
+    let ui_amount = amount_to_ui_amount_string_trimmed(v.unsigned_abs(), 9);
+    if v < 0 {
+        format!("-{}", ui_amount)
+    } else {
+        ui_amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_sol_signed, lamports_to_ui_signed};
+
+    #[test]
+    fn test_lamports_to_ui_signed() {
+        assert_eq!(lamports_to_ui_signed(0, 9), 0.0);
+        assert_eq!(lamports_to_ui_signed(-1_500_000_000, 9), -1.5);
+        assert_eq!(lamports_to_ui_signed(1_500_000, 6), 1.5);
+        // the absolute value of i64::MIN doesn't fit an i64
+        assert!((lamports_to_ui_signed(i64::MIN, 9) + 9_223_372_036.854_776).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_format_sol_signed() {
+        assert_eq!(format_sol_signed(0), "0");
+        assert_eq!(format_sol_signed(-1), "-0.000000001");
+        assert_eq!(format_sol_signed(1_000_000_000), "1");
+        assert_eq!(format_sol_signed(-1_234_500_000), "-1.2345");
+        assert_eq!(format_sol_signed(i64::MIN), "-9223372036.854775808");
+        assert_eq!(format_sol_signed(i64::MAX), "9223372036.854775807");
+    }
+}
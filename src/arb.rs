@@ -1,150 +1,848 @@
-use std::{collections::HashMap, env};
+use std::{
+    collections::HashMap,
+    env,
+    future::Future,
+    sync::{
+        LazyLock, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
 
-use anyhow::{Ok, Result, anyhow};
+use anyhow::anyhow;
+use clap::ValueEnum;
 use jupiter_swap_api_client::{
     JupiterSwapApiClient,
-    quote::{QuoteRequest, QuoteResponse},
+    quote::{QuoteRequest, QuoteResponse, SwapMode},
     swap::{SwapInstructionsResponse, SwapRequest},
-    transaction_config::TransactionConfig,
+    transaction_config::{DynamicSlippageSettings, TransactionConfig},
+};
+use reqwest::StatusCode;
+use rust_decimal::{
+    Decimal,
+    prelude::{ToPrimitive, Zero},
+};
+use solana_sdk::{
+    compute_budget::{self, ComputeBudgetInstruction},
+    instruction::Instruction,
+    pubkey::Pubkey,
+    transaction::VersionedTransaction,
 };
-use rust_decimal::{Decimal, prelude::Zero};
-use solana_sdk::{instruction::Instruction, pubkey::Pubkey, transaction::VersionedTransaction};
+use spl_token_2022::extension::transfer_fee::TransferFee;
+use tokio::time::Instant;
 use tracing::{debug, trace, warn};
 
-use crate::dex::Dex;
+use crate::{
+    backtest::{QUOTE_RECORD_PATH, record_quotes},
+    decay::DecayPolicy,
+    dex::{Dex, DexSelection},
+    error::{ArbError, ValidationError},
+    jito::TipPolicy,
+    latency::LatencyBreakdown,
+    metrics,
+    observer::Opportunity,
+    ratelimit,
+};
+
+/// Knobs applied to every jupiter quote request
+#[derive(Debug, Clone, Default)]
+pub struct QuoteOptions {
+    pub slippage_bps: u16,
+    pub only_direct_routes: Option<bool>,
+    pub restrict_intermediate_tokens: Option<bool>,
+    pub platform_fee_bps: Option<u8>,
+    pub as_legacy_transaction: Option<bool>,
+    // ExactOut makes `amount` the output amount, jupiter computes the input
+    pub swap_mode: Option<SwapMode>,
+    // bounds every jupiter quote, None waits as long as reqwest does
+    pub timeout: Option<Duration>,
+    // swap and swap_instructions build a whole tx and take longer
+    pub swap_timeout: Option<Duration>,
+    // percent, either leg above this is rejected by caculate_profit
+    pub max_price_impact_pct: Option<f64>,
+    // let jupiter pick the swap slippage up to this many bps, relaxes the
+    // guaranteed-profit threshold of the merged quote
+    pub dynamic_slippage_max_bps: Option<u16>,
+    // fixed slippage on the merged quote's sell leg, only as much as the
+    // worst case still covers amount_in + tip + min_profit
+    pub max_slippage_bps: Option<u16>,
+    // send quotes as a POST with a json body, long routes can overflow a
+    // GET url
+    pub post: bool,
+    // conservative scaling of quoted outputs in caculate_profit
+    pub decay: DecayPolicy,
+    // an identical request within this long is answered from the last
+    // response, None always asks jupiter
+    pub cache_ttl: Option<Duration>,
+}
+
+impl QuoteOptions {
+    /// These options without the quote cache, for quotes an execution acts
+    /// on right away
+    pub fn uncached(&self) -> Self {
+        Self {
+            cache_ttl: None,
+            ..self.clone()
+        }
+    }
+
+    pub fn build_request(
+        &self,
+        input_mint: &Pubkey,
+        output_mint: &Pubkey,
+        amount: u64,
+        dexes: &DexSelection,
+        quote_args: Option<HashMap<String, String>>,
+    ) -> QuoteRequest {
+        QuoteRequest {
+            amount,
+            input_mint: *input_mint,
+            output_mint: *output_mint,
+            dexes: Some(dexes.to_string()),
+            slippage_bps: self.slippage_bps,
+            only_direct_routes: self.only_direct_routes,
+            restrict_intermediate_tokens: self.restrict_intermediate_tokens,
+            platform_fee_bps: self.platform_fee_bps,
+            as_legacy_transaction: self.as_legacy_transaction,
+            swap_mode: self.swap_mode.clone(),
+            quote_args,
+            ..QuoteRequest::default()
+        }
+    }
+}
+
+/// A round trip to quote: `amount_in` of the base mint into `mint` and back
+#[derive(Debug, Clone, Copy)]
+pub struct RoundTrip<'a> {
+    pub base_mint: &'a Pubkey,
+    pub mint: &'a Pubkey,
+    pub amount_in: u64,
+    pub buy_dexes: &'a DexSelection,
+    pub sell_dexes: &'a DexSelection,
+    // the mint's token-2022 transfer fee, if any
+    pub transfer_fee: Option<&'a TransferFee>,
+}
 
 pub async fn caculate_profit(
     jupiter_swap_api_client: &JupiterSwapApiClient,
     jupiter_extra_args: Option<HashMap<String, String>>,
-    amount_in: &u64,
-    token_in: &Pubkey,
-    token_out: &Pubkey,
-    dexes: Dex,
-    partner_fee: f64,
-) -> Result<(i64, QuoteResponse, QuoteResponse)> {
-    let slippage_bps = 0u16;
-    let native_mint = spl_token::native_mint::id();
-    if token_in != &native_mint {
-        return Err(anyhow!("Only support swap from native mint"));
-    }
-    // decay factor
-    let mut buy_decay_factor: f64 = env::var("BUY_DECAY_FACTOR")
-        .unwrap_or("1.0".to_string())
-        .parse()
-        .unwrap_or(1.0);
-
-    let mut sell_decay_factor: f64 = env::var("SELL_DECAY_FACTOR")
-        .unwrap_or("1.0".to_string())
-        .parse()
-        .unwrap_or(1.0);
-
-    if buy_decay_factor <= 0.0 || buy_decay_factor > 1.0 {
-        warn!("Invalid buy_decay_factor: {}, using 1.0", buy_decay_factor);
-        buy_decay_factor = 1.0;
-    }
-
-    if sell_decay_factor <= 0.0 || sell_decay_factor > 1.0 {
-        warn!(
-            "Invalid sell_decay_factor: {}, using 1.0",
-            sell_decay_factor
-        );
-        sell_decay_factor = 1.0;
-    }
-
-    let quote_request = QuoteRequest {
-        amount: *amount_in,
-        input_mint: *token_in,
-        output_mint: *token_out,
-        dexes: Some(dexes.to_string()),
-        slippage_bps,
-        only_direct_routes: Some(true),
-        quote_args: jupiter_extra_args.clone(),
-        ..QuoteRequest::default()
+    round_trip: &RoundTrip<'_>,
+    quote_options: &QuoteOptions,
+    latency: &mut LatencyBreakdown,
+) -> Result<(i64, QuoteResponse, QuoteResponse), ArbError> {
+    let RoundTrip {
+        base_mint: token_in,
+        mint: token_out,
+        amount_in,
+        buy_dexes,
+        sell_dexes,
+        transfer_fee,
+    } = *round_trip;
+    // the platform fee is only charged on the sell leg, in wsol
+    let buy_options = QuoteOptions {
+        platform_fee_bps: None,
+        ..quote_options.clone()
     };
-    let mut quote_buy_response = jupiter_swap_api_client.quote(&quote_request).await?;
+    let quote_request = buy_options.build_request(
+        token_in,
+        token_out,
+        amount_in,
+        buy_dexes,
+        jupiter_extra_args.clone(),
+    );
+    let start_quote = Instant::now();
+    let quote_buy_response = quote(jupiter_swap_api_client, &quote_request, quote_options).await?;
+    latency.buy_quote = Some(start_quote.elapsed());
+    check_price_impact(
+        "buy",
+        quote_buy_response.price_impact_pct,
+        quote_options.max_price_impact_pct,
+    )?;
     trace!("quote_buy_response: {:#?}", quote_buy_response);
-    // buy decay factor
-    let decayed_buy_out_amount = (quote_buy_response.out_amount as f64 * buy_decay_factor) as u64;
-    let decayed_buy_other_amount_threshold =
-        (quote_buy_response.other_amount_threshold as f64 * buy_decay_factor) as u64;
-    debug!(
-        "buy out amount: {}(decayed: {}), other amount threshold: {}(decayed: {})",
+
+    // the sell leg is quoted on exactly what the buy leg routes to us, decay
+    // factors only make the profit estimate below more conservative
+    let quote_request = quote_options.build_request(
+        token_out,
+        token_in,
         quote_buy_response.out_amount,
-        decayed_buy_out_amount,
-        quote_buy_response.other_amount_threshold,
-        decayed_buy_other_amount_threshold
+        sell_dexes,
+        jupiter_extra_args,
     );
-    quote_buy_response.out_amount = decayed_buy_out_amount;
-    quote_buy_response.other_amount_threshold = decayed_buy_other_amount_threshold;
-
-    let quote_request = QuoteRequest {
-        amount: quote_buy_response.out_amount,
-        input_mint: *token_out,
-        output_mint: *token_in,
-        dexes: Some(dexes.to_string()),
-        slippage_bps,
-        only_direct_routes: Some(true),
-        quote_args: jupiter_extra_args,
-        ..QuoteRequest::default()
-    };
 
-    let mut quote_sell_response = jupiter_swap_api_client.quote(&quote_request).await?;
+    let start_quote = Instant::now();
+    let quote_sell_response = quote(jupiter_swap_api_client, &quote_request, quote_options).await?;
+    latency.sell_quote = Some(start_quote.elapsed());
+    check_sell_input(
+        quote_buy_response.out_amount,
+        quote_sell_response.in_amount,
+        &quote_sell_response.swap_mode,
+    )?;
+    check_price_impact(
+        "sell",
+        quote_sell_response.price_impact_pct,
+        quote_options.max_price_impact_pct,
+    )?;
+    if QUOTE_RECORD_PATH.is_some() {
+        let recorded = record_quotes(
+            token_out,
+            amount_in,
+            &quote_buy_response,
+            &quote_sell_response,
+        );
+        if let Err(e) = recorded {
+            warn!("Failed to record quotes: {}", e);
+        }
+    }
     trace!("quote_sell_response: {:#?}", quote_sell_response);
-    // sell decay factor
-    let decayed_sell_out_amount =
-        (quote_sell_response.out_amount as f64 * sell_decay_factor) as u64;
-    let decayed_sell_other_amount_threshold =
-        (quote_sell_response.other_amount_threshold as f64 * sell_decay_factor) as u64;
-    debug!(
-        "sell out amount: {}(decayed: {}), other amount threshold: {}(decayed: {})",
-        quote_sell_response.out_amount,
-        decayed_sell_out_amount,
-        quote_sell_response.other_amount_threshold,
-        decayed_sell_other_amount_threshold
-    );
-    quote_sell_response.out_amount = decayed_sell_out_amount;
-    quote_sell_response.other_amount_threshold = decayed_sell_other_amount_threshold;
 
     let mut fee_amount = 0u64;
     quote_buy_response.route_plan.iter().for_each(|route| {
-        if route.swap_info.fee_mint == native_mint {
+        if route.swap_info.fee_mint == *token_in {
             fee_amount += route.swap_info.fee_amount;
         }
     });
-    debug!("swap fee amount (only caculate wsol): {}", fee_amount);
-    let mut profit = quote_sell_response.out_amount as i64 - *amount_in as i64;
+    debug!(
+        "swap fee amount (only caculate {}): {}",
+        token_in, fee_amount
+    );
+    let buy_decay_factor = quote_options.decay.buy_factor(&quote_buy_response);
+    let sell_decay_factor = quote_options.decay.sell_factor(&quote_sell_response);
+    let mut sell_out_amount = decayed_sell_out(
+        quote_sell_response.out_amount,
+        buy_decay_factor,
+        sell_decay_factor,
+    );
+    debug!(
+        "sell out amount: {}(decayed: {}), buy decay: {}, sell decay: {}",
+        quote_sell_response.out_amount, sell_out_amount, buy_decay_factor, sell_decay_factor
+    );
+    // token-2022 transfer fees shrink what the sell leg actually receives,
+    // scale its output down accordingly
+    if let Some(transfer_fee) = transfer_fee {
+        let delivered = apply_transfer_fee(transfer_fee, quote_buy_response.out_amount);
+        sell_out_amount = (sell_out_amount as u128 * delivered as u128
+            / quote_buy_response.out_amount.max(1) as u128) as u64;
+        debug!(
+            "transfer fee: token delivered {}(of {}), sell out amount {}(quoted: {})",
+            delivered,
+            quote_buy_response.out_amount,
+            sell_out_amount,
+            quote_sell_response.out_amount
+        );
+    }
+    // out_amount is already net of the platform fee jupiter reports
+    if let Some(platform_fee) = &quote_sell_response.platform_fee {
+        debug!(
+            "platform fee: {} ({} bps)",
+            platform_fee.amount, platform_fee.fee_bps
+        );
+    }
+    let mut profit = sell_out_amount as i64 - amount_in as i64;
     profit = profit - fee_amount as i64;
-    // caculate partner fee
-    profit = profit - (*amount_in as f64 * partner_fee) as i64;
 
     Ok((profit, quote_buy_response, quote_sell_response))
 }
-// merge buy and sell quotes
+/// Invariant: the sell leg sells exactly the buy leg's quoted output. The
+/// quotes are routed as they are, so a sell quoted on any other amount
+/// either leaves tokens behind or fails for lack of them.
+fn check_sell_input(
+    buy_out_amount: u64,
+    sell_in_amount: u64,
+    sell_swap_mode: &SwapMode,
+) -> Result<(), ArbError> {
+    // exact out quotes fix the output, the input is whatever it takes
+    if *sell_swap_mode == SwapMode::ExactOut {
+        return Ok(());
+    }
+    if sell_in_amount != buy_out_amount {
+        return Err(ArbError::InvalidPath(format!(
+            "sell leg takes {} but the buy leg delivers {}",
+            sell_in_amount, buy_out_amount
+        )));
+    }
+    Ok(())
+}
+
+/// Conservative sell output for profit math only, never routed. A buy decay
+/// means fewer tokens to sell, which scales the sell output linearly, the
+/// same model the backtest replays.
+fn decayed_sell_out(sell_out_amount: u64, buy_decay_factor: f64, sell_decay_factor: f64) -> u64 {
+    (sell_out_amount as f64 * buy_decay_factor * sell_decay_factor) as u64
+}
+
+// a leg with a huge price impact goes through a thin pool and rarely lands
+fn check_price_impact(
+    leg: &'static str,
+    price_impact_pct: Decimal,
+    max_price_impact_pct: Option<f64>,
+) -> Result<(), ArbError> {
+    let Some(max_price_impact_pct) = max_price_impact_pct else {
+        return Ok(());
+    };
+    // jupiter reports a fraction, 0.01 = 1%
+    let impact_pct = price_impact_pct.to_f64().unwrap_or(0.0).abs() * 100.0;
+    if impact_pct > max_price_impact_pct {
+        return Err(ArbError::HighImpact { leg, impact_pct });
+    }
+    Ok(())
+}
+
+/// Value of `amount` of `mint` in lamports, quoted on jupiter
+pub async fn to_lamports(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    jupiter_extra_args: Option<HashMap<String, String>>,
+    mint: &Pubkey,
+    amount: u64,
+    timeout: Option<Duration>,
+) -> Result<u64, ArbError> {
+    let native_mint = spl_token::native_mint::id();
+    if *mint == native_mint || amount == 0 {
+        return Ok(amount);
+    }
+    let quote_options = QuoteOptions {
+        timeout,
+        ..Default::default()
+    };
+    let quote_request = quote_options.build_request(
+        mint,
+        &native_mint,
+        amount,
+        &Dex::ALL.into(),
+        jupiter_extra_args,
+    );
+    let quote_response = quote(jupiter_swap_api_client, &quote_request, &quote_options).await?;
+    Ok(quote_response.out_amount)
+}
+
+/// Amount of `mint` worth `lamports`, quoted on jupiter
+pub async fn from_lamports(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    jupiter_extra_args: Option<HashMap<String, String>>,
+    mint: &Pubkey,
+    lamports: u64,
+    timeout: Option<Duration>,
+) -> Result<u64, ArbError> {
+    let native_mint = spl_token::native_mint::id();
+    if *mint == native_mint || lamports == 0 {
+        return Ok(lamports);
+    }
+    let quote_options = QuoteOptions {
+        timeout,
+        ..Default::default()
+    };
+    let quote_request = quote_options.build_request(
+        &native_mint,
+        mint,
+        lamports,
+        &Dex::ALL.into(),
+        jupiter_extra_args,
+    );
+    let quote_response = quote(jupiter_swap_api_client, &quote_request, &quote_options).await?;
+    Ok(quote_response.out_amount)
+}
+
+/// The tip in `mint` and in lamports as jito is paid it, clamped into
+/// `policy`. A clamped tip is converted back so the threshold and profit
+/// checks cover what is actually paid.
+pub async fn clamped_tip(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    jupiter_extra_args: Option<HashMap<String, String>>,
+    mint: &Pubkey,
+    tip_amount: u64,
+    policy: &TipPolicy,
+    timeout: Option<Duration>,
+) -> Result<(u64, u64), ArbError> {
+    let tip_lamports = to_lamports(
+        jupiter_swap_api_client,
+        jupiter_extra_args.clone(),
+        mint,
+        tip_amount,
+        timeout,
+    )
+    .await?;
+    let clamped = policy.clamp(tip_lamports);
+    if clamped == tip_lamports {
+        return Ok((tip_amount, tip_lamports));
+    }
+    let tip_amount = from_lamports(
+        jupiter_swap_api_client,
+        jupiter_extra_args,
+        mint,
+        clamped,
+        timeout,
+    )
+    .await?;
+    Ok((tip_amount, clamped))
+}
+
+/// Take a rate limit token for jupiter `call`, then run it within `timeout`
+pub async fn jupiter_call<T, E>(
+    call: &'static str,
+    timeout: Option<Duration>,
+    request: impl Future<Output = Result<T, E>>,
+) -> Result<T, ArbError>
+where
+    ArbError: From<E>,
+{
+    ratelimit::acquire(call).await?;
+    with_timeout(call, timeout, request).await
+}
+
+/// Run jupiter `call` within `timeout`, the rate limit is up to the caller
+pub async fn with_timeout<T, E>(
+    call: &'static str,
+    timeout: Option<Duration>,
+    request: impl Future<Output = Result<T, E>>,
+) -> Result<T, ArbError>
+where
+    ArbError: From<E>,
+{
+    let Some(timeout) = timeout else {
+        return Ok(request.await?);
+    };
+    match tokio::time::timeout(timeout, request).await {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(ArbError::JupiterTimeout {
+            call,
+            timeout_ms: timeout.as_millis() as u64,
+        }),
+    }
+}
+
+pub static JUP_QUOTE_API: LazyLock<String> = LazyLock::new(|| {
+    env::var("JUP_QUOTE_API").unwrap_or("https://quote-api.jup.ag/v6".to_string())
+});
+
+// set once the quote api turned a POST down, GET is used from then on
+static POST_UNSUPPORTED: AtomicBool = AtomicBool::new(false);
+
+// POST quotes share one connection pool
+static QUOTE_HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+
+// responses by the request that got them, see QuoteOptions::cache_ttl
+static QUOTE_CACHE: LazyLock<Mutex<QuoteCache>> =
+    LazyLock::new(|| Mutex::new(QuoteCache::default()));
+
+#[derive(Default)]
+struct QuoteCache {
+    entries: HashMap<String, (Instant, QuoteResponse)>,
+}
+
+impl QuoteCache {
+    fn get(&self, key: &str, ttl: Duration) -> Option<QuoteResponse> {
+        let (at, quote_response) = self.entries.get(key)?;
+        (at.elapsed() < ttl).then(|| quote_response.clone())
+    }
+
+    // expired entries go on every insert, the cache never outgrows one ttl
+    // of distinct requests
+    fn insert(&mut self, key: String, quote_response: QuoteResponse, ttl: Duration) {
+        self.entries.retain(|_, (at, _)| at.elapsed() < ttl);
+        self.entries.insert(key, (Instant::now(), quote_response));
+    }
+}
+
+/// Quote through jupiter, as a POST when `quote_options.post` is set.
+/// An api that doesn't take POST quotes gets GET instead. With a
+/// `cache_ttl`, identical requests within it share one response.
+pub async fn quote(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    quote_request: &QuoteRequest,
+    quote_options: &QuoteOptions,
+) -> Result<QuoteResponse, ArbError> {
+    let Some(ttl) = quote_options.cache_ttl else {
+        return fetch_quote(jupiter_swap_api_client, quote_request, quote_options).await;
+    };
+    // the request as sent, mints, amount, dexes and slippage included
+    let key = serde_json::to_string(quote_request).map_err(|e| ArbError::Other(e.into()))?;
+    if let Some(quote_response) = QUOTE_CACHE.lock().unwrap().get(&key, ttl) {
+        metrics::inc_counter("arb_quote_cache_total", &[("result", "hit")]);
+        return Ok(quote_response);
+    }
+    metrics::inc_counter("arb_quote_cache_total", &[("result", "miss")]);
+    let quote_response = fetch_quote(jupiter_swap_api_client, quote_request, quote_options).await?;
+    QUOTE_CACHE
+        .lock()
+        .unwrap()
+        .insert(key, quote_response.clone(), ttl);
+    Ok(quote_response)
+}
+
+async fn fetch_quote(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    quote_request: &QuoteRequest,
+    quote_options: &QuoteOptions,
+) -> Result<QuoteResponse, ArbError> {
+    // one token per quote, a POST that falls back to GET isn't charged twice
+    ratelimit::acquire("quote").await?;
+    if quote_options.post && !POST_UNSUPPORTED.load(Ordering::Relaxed) {
+        let base_path = &jupiter_swap_api_client.base_path;
+        match with_timeout(
+            "quote",
+            quote_options.timeout,
+            post_quote(base_path, quote_request),
+        )
+        .await?
+        {
+            Some(quote_response) => return Ok(quote_response),
+            None => {
+                warn!("{}/quote doesn't take POST, quoting with GET", base_path);
+                POST_UNSUPPORTED.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+    with_timeout(
+        "quote",
+        quote_options.timeout,
+        jupiter_swap_api_client.quote(quote_request),
+    )
+    .await
+}
+
+// None when the endpoint doesn't know the method
+async fn post_quote(
+    base_path: &str,
+    quote_request: &QuoteRequest,
+) -> Result<Option<QuoteResponse>, ArbError> {
+    let transport = |e: reqwest::Error| ArbError::QuoteTransport(e.to_string());
+    let mut body = serde_json::to_value(quote_request).map_err(|e| ArbError::Other(e.into()))?;
+    // extra args such as the api key stay in the query, like on GET
+    if let Some(body) = body.as_object_mut() {
+        body.remove("quoteArgs");
+    }
+    let response = QUOTE_HTTP_CLIENT
+        .post(format!("{}/quote", base_path))
+        .query(&quote_request.quote_args)
+        .json(&body)
+        .send()
+        .await
+        .map_err(transport)?;
+    let status = response.status();
+    if matches!(
+        status,
+        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED
+    ) {
+        return Ok(None);
+    }
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(ArbError::from_quote_response(status.as_u16(), body));
+    }
+    Ok(Some(response.json().await.map_err(transport)?))
+}
+
+// tokens that reach the sell pool: the fee is charged once when the buy pool
+// pays us and again when we pay the sell pool
+pub fn apply_transfer_fee(transfer_fee: &TransferFee, amount: u64) -> u64 {
+    let received = transfer_fee.calculate_post_fee_amount(amount).unwrap_or(0);
+    transfer_fee
+        .calculate_post_fee_amount(received)
+        .unwrap_or(0)
+}
+
+/// The merged quote requires `amount_in + tip_amount` back, a sell leg
+/// quoted below that would only revert on chain
+pub fn check_sell_threshold(
+    sell_out_amount: u64,
+    amount_in: u64,
+    tip_amount: u64,
+) -> Result<(), ArbError> {
+    let required = amount_in + tip_amount;
+    if sell_out_amount < required {
+        return Err(ArbError::ThresholdUnmet {
+            expected_out: sell_out_amount,
+            required,
+        });
+    }
+    Ok(())
+}
+
+/// Last check before an opportunity is built into transactions, the raw
+/// sell quote has to return the principal, `tip`, the network fee and
+/// `min_profit`. `tip` is the clamped tip the bundle pays, it and
+/// `min_profit` are in the base mint, the fee only counts when that is SOL.
+pub fn validate_opportunity(
+    opportunity: &Opportunity,
+    tip: u64,
+    min_profit: u64,
+) -> Result<(), ValidationError> {
+    let expected_out = opportunity.quote_sell_response.out_amount;
+    let network_fee = network_fee(&opportunity.quote_buy_response.input_mint);
+    let required =
+        opportunity.amount_in as u128 + tip as u128 + network_fee as u128 + min_profit as u128;
+    if (expected_out as u128) < required {
+        return Err(ValidationError {
+            expected_out,
+            required: required.min(u64::MAX as u128) as u64,
+            amount_in: opportunity.amount_in,
+            tip,
+            network_fee,
+            min_profit,
+        });
+    }
+    Ok(())
+}
+
+/// The network fee of a round trip in `base_mint`, the base signature fee is
+/// paid in SOL so it only counts against a SOL base.
+pub fn network_fee(base_mint: &Pubkey) -> u64 {
+    match *base_mint == spl_token::native_mint::id() {
+        true => BASE_FEE_LAMPORTS,
+        false => 0,
+    }
+}
+
+// merge buy and sell quotes, `tip_amount` is in the input mint
 pub fn merge_quotes(
     quote_buy_response: QuoteResponse,
     quote_sell_response: QuoteResponse,
     amount_in: u64,
-    tip_lamports: u64,
-) -> QuoteResponse {
-    let mut merged_quote = quote_buy_response;
+    tip_amount: u64,
+) -> Result<QuoteResponse, ArbError> {
+    merge_legs(
+        vec![quote_buy_response, quote_sell_response],
+        amount_in,
+        tip_amount,
+    )
+}
+
+/// Merge an ordered list of quotes, e.g. SOL -> A -> B -> SOL, into a
+/// single quote whose route plan runs every leg in turn
+pub fn merge_legs(
+    legs: Vec<QuoteResponse>,
+    amount_in: u64,
+    tip_amount: u64,
+) -> Result<QuoteResponse, ArbError> {
+    let path: Vec<(Pubkey, Pubkey)> = legs
+        .iter()
+        .map(|leg| (leg.input_mint, leg.output_mint))
+        .collect();
+    check_path(&path)?;
+    Ok(merge_route(legs, amount_in, tip_amount))
+}
 
-    // set output mint
-    merged_quote.output_mint = quote_sell_response.output_mint;
+// every leg starts with the mint the previous one ended with and the last
+// one returns to the first input mint
+fn check_path(path: &[(Pubkey, Pubkey)]) -> Result<(), ArbError> {
+    if path.len() < 2 {
+        return Err(ArbError::InvalidPath(format!(
+            "need at least 2 legs, got {}",
+            path.len()
+        )));
+    }
+    for (i, window) in path.windows(2).enumerate() {
+        let (_, output_mint) = window[0];
+        let (input_mint, _) = window[1];
+        if output_mint != input_mint {
+            return Err(ArbError::InvalidPath(format!(
+                "leg {} outputs {} but leg {} takes {}",
+                i,
+                output_mint,
+                i + 1,
+                input_mint
+            )));
+        }
+    }
+    let (start, _) = path[0];
+    let (_, end) = path[path.len() - 1];
+    if start != end {
+        return Err(ArbError::InvalidPath(format!(
+            "path starts with {} but ends with {}",
+            start, end
+        )));
+    }
+    Ok(())
+}
 
-    // set output amount
-    merged_quote.out_amount = amount_in + tip_lamports;
-    merged_quote.other_amount_threshold = amount_in + tip_lamports;
+// every field is set from the legs as a whole, none is left over from the
+// first leg. The path must have been checked.
+fn merge_route(legs: Vec<QuoteResponse>, amount_in: u64, tip_amount: u64) -> QuoteResponse {
+    let context_slot = legs.iter().map(|leg| leg.context_slot).max().unwrap_or(0);
+    let time_taken = legs.iter().map(|leg| leg.time_taken).sum();
+    let mut legs = legs.into_iter();
+    let mut merged_quote = legs.next().expect("at least one leg");
+    for leg in legs {
+        merged_quote.output_mint = leg.output_mint;
+        merged_quote.route_plan.extend(leg.route_plan);
+    }
 
-    // set price impact
+    merged_quote.in_amount = amount_in;
+    merged_quote.swap_mode = SwapMode::ExactIn;
+    // at least the input and the tip must come back, no slippage below that
+    merged_quote.out_amount = amount_in + tip_amount;
+    merged_quote.other_amount_threshold = amount_in + tip_amount;
+    merged_quote.slippage_bps = 0;
     merged_quote.price_impact_pct = Decimal::zero();
-    // merged_quote.price_impact_pct = Decimal::from_f64(1.0).unwrap();
+    // the referral fee is set on the merged quote by whoever charges it
+    merged_quote.platform_fee = None;
+    merged_quote.context_slot = context_slot;
+    merged_quote.time_taken = time_taken;
+    merged_quote
+}
 
-    // set route plan
-    let mut merged_route_plan = merged_quote.route_plan;
-    merged_route_plan.extend(quote_sell_response.route_plan);
-    merged_quote.route_plan = merged_route_plan;
+/// Every step of the route as `label(amm_key, in_mint->out_mint, fee)`,
+/// pubkeys shortened for logs
+pub fn route_summary(quote: &QuoteResponse) -> String {
+    render_route(quote, short_key)
+}
 
-    merged_quote
+/// `route_summary` with full pubkeys, for the trade history
+pub fn route_summary_full(quote: &QuoteResponse) -> String {
+    render_route(quote, Pubkey::to_string)
+}
+
+fn render_route(quote: &QuoteResponse, key: impl Fn(&Pubkey) -> String) -> String {
+    quote
+        .route_plan
+        .iter()
+        .map(|step| {
+            let info = &step.swap_info;
+            // a split route only sends part of the input through a step
+            let share = match step.percent {
+                100 => String::new(),
+                percent => format!("{}% ", percent),
+            };
+            format!(
+                "{}{}({}, {}->{}, fee {})",
+                share,
+                info.label,
+                key(&info.amm_key),
+                key(&info.input_mint),
+                key(&info.output_mint),
+                info.fee_amount
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn short_key(key: &Pubkey) -> String {
+    let key = key.to_string();
+    format!("{}..{}", &key[..4], &key[key.len() - 4..])
+}
+
+/// Quote `path[0] -> path[1] -> ... -> path[0]`, each leg selling what the
+/// previous one bought. Returns the profit in `path[0]` units and the legs
+/// in order, ready for `merge_legs`. The platform fee is only charged on
+/// the last leg; decay factors and transfer fees are not applied.
+pub async fn quote_legs(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    jupiter_extra_args: Option<HashMap<String, String>>,
+    amount_in: u64,
+    path: &[Pubkey],
+    dexes: &DexSelection,
+    quote_options: &QuoteOptions,
+) -> Result<(i64, Vec<QuoteResponse>), ArbError> {
+    if path.len() < 2 {
+        return Err(ArbError::InvalidPath(format!(
+            "need at least 2 mints, got {}",
+            path.len()
+        )));
+    }
+    let mut legs = Vec::with_capacity(path.len());
+    let mut amount = amount_in;
+    for i in 0..path.len() {
+        let last = i == path.len() - 1;
+        let options = QuoteOptions {
+            platform_fee_bps: quote_options.platform_fee_bps.filter(|_| last),
+            ..quote_options.clone()
+        };
+        let quote_request = options.build_request(
+            &path[i],
+            &path[(i + 1) % path.len()],
+            amount,
+            dexes,
+            jupiter_extra_args.clone(),
+        );
+        let quote_response = quote(jupiter_swap_api_client, &quote_request, quote_options).await?;
+        let leg = if i == 0 {
+            "buy"
+        } else if last {
+            "sell"
+        } else {
+            "hop"
+        };
+        check_price_impact(
+            leg,
+            quote_response.price_impact_pct,
+            quote_options.max_price_impact_pct,
+        )?;
+        debug!(
+            "leg {}: {} -> {}, in: {}, out: {}",
+            i,
+            quote_response.input_mint,
+            quote_response.output_mint,
+            amount,
+            quote_response.out_amount
+        );
+        amount = quote_response.out_amount;
+        legs.push(quote_response);
+    }
+    let profit = amount as i64 - amount_in as i64;
+    Ok((profit, legs))
+}
+
+/// Quote both legs of a round trip once, so a mint without a route on the
+/// selected dexes fails at startup instead of being scanned forever
+pub async fn probe_route(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    jupiter_extra_args: Option<HashMap<String, String>>,
+    round_trip: &RoundTrip<'_>,
+    quote_options: &QuoteOptions,
+) -> Result<(), ArbError> {
+    let RoundTrip {
+        base_mint,
+        mint,
+        amount_in,
+        buy_dexes,
+        sell_dexes,
+        ..
+    } = *round_trip;
+    let not_tradeable = |dexes: &DexSelection| {
+        let dexes = dexes.to_string();
+        move |e: ArbError| match e {
+            ArbError::NoRoute(body) => ArbError::NoRoute(format!(
+                "mint {} not tradeable on {}: {}",
+                mint, dexes, body
+            )),
+            e => e,
+        }
+    };
+    let quote_request = quote_options.build_request(
+        base_mint,
+        mint,
+        amount_in,
+        buy_dexes,
+        jupiter_extra_args.clone(),
+    );
+    let quote_buy_response = quote(jupiter_swap_api_client, &quote_request, quote_options)
+        .await
+        .map_err(not_tradeable(buy_dexes))?;
+    let quote_request = quote_options.build_request(
+        mint,
+        base_mint,
+        quote_buy_response.out_amount,
+        sell_dexes,
+        jupiter_extra_args,
+    );
+    quote(jupiter_swap_api_client, &quote_request, quote_options)
+        .await
+        .map_err(not_tradeable(sell_dexes))?;
+    Ok(())
+}
+
+/// The swap request as sent to jupiter, `config` goes through untouched
+pub fn swap_request(
+    user_public_key: &Pubkey,
+    quote_response: &QuoteResponse,
+    config: TransactionConfig,
+) -> SwapRequest {
+    SwapRequest {
+        user_public_key: *user_public_key,
+        quote_response: quote_response.clone(),
+        config,
+    }
 }
 
 pub async fn swap(
@@ -152,23 +850,20 @@ pub async fn swap(
     jupiter_extra_args: Option<HashMap<String, String>>,
     user_public_key: &Pubkey,
     quote_response: &QuoteResponse,
-) -> Result<VersionedTransaction> {
-    let swap_response = jupiter_swap_api_client
-        .swap(
-            &SwapRequest {
-                user_public_key: user_public_key.clone(),
-                quote_response: quote_response.clone(),
-                config: TransactionConfig {
-                    use_shared_accounts: Some(false),
-                    ..Default::default()
-                },
-            },
-            jupiter_extra_args,
-        )
-        .await?;
+    config: TransactionConfig,
+    timeout: Option<Duration>,
+) -> Result<VersionedTransaction, ArbError> {
+    let request = swap_request(user_public_key, quote_response, config);
+    let swap_response = jupiter_call(
+        "swap",
+        timeout,
+        jupiter_swap_api_client.swap(&request, jupiter_extra_args),
+    )
+    .await?;
 
     let versioned_transaction: VersionedTransaction =
-        bincode::deserialize(&swap_response.swap_transaction).unwrap();
+        bincode::deserialize(&swap_response.swap_transaction)
+            .map_err(|e| anyhow!("failed to deserialize swap transaction: {}", e))?;
     Ok(versioned_transaction)
 }
 
@@ -177,49 +872,715 @@ pub async fn swap_instructions(
     jupiter_extra_args: Option<HashMap<String, String>>,
     user_public_key: &Pubkey,
     quote_response: &QuoteResponse,
-) -> Result<SwapInstructionsResponse> {
-    let swap_instructions = jupiter_swap_api_client
-        .swap_instructions(
-            &SwapRequest {
-                user_public_key: user_public_key.clone(),
-                quote_response: quote_response.clone(),
-                config: TransactionConfig {
-                    dynamic_compute_unit_limit: true,
-                    use_shared_accounts: Some(false),
-                    ..Default::default()
-                },
-            },
-            jupiter_extra_args,
-        )
-        .await?;
+    config: TransactionConfig,
+    timeout: Option<Duration>,
+) -> Result<SwapInstructionsResponse, ArbError> {
+    let request = swap_request(user_public_key, quote_response, config);
+    let swap_instructions = jupiter_call(
+        "swap_instructions",
+        timeout,
+        jupiter_swap_api_client.swap_instructions(&request, jupiter_extra_args),
+    )
+    .await?;
 
     Ok(swap_instructions)
 }
 
+pub fn dynamic_slippage(max_bps: Option<u16>) -> Option<DynamicSlippageSettings> {
+    max_bps.map(|max_bps| DynamicSlippageSettings {
+        min_bps: None,
+        max_bps: Some(max_bps),
+    })
+}
+
+/// Profit left if the sell leg slips by the full `max_bps`
+pub fn worst_case_profit(profit: i64, sell_out_amount: u64, max_bps: u16) -> i64 {
+    profit - (sell_out_amount as u128 * max_bps as u128 / 10_000) as i64
+}
+
+/// Largest slippage in bps on `sell_out_amount` whose worst case still
+/// returns `required`, capped at `max_bps`
+pub fn sell_slippage_bps(sell_out_amount: u64, required: u64, max_bps: u16) -> u16 {
+    if sell_out_amount <= required {
+        return 0;
+    }
+    let headroom = (sell_out_amount - required) as u128 * 10_000 / sell_out_amount as u128;
+    headroom.min(max_bps as u128) as u16
+}
+
+/// Let the merged quote slip by `slippage_bps` below `sell_out_amount`
+pub fn apply_slippage(quote_response: &mut QuoteResponse, sell_out_amount: u64, slippage_bps: u16) {
+    quote_response.slippage_bps = slippage_bps;
+    quote_response.out_amount = sell_out_amount;
+    quote_response.other_amount_threshold =
+        (sell_out_amount as u128 * (10_000 - slippage_bps as u128) / 10_000) as u64;
+}
+
+// lamports per signature, an arb tx is signed by the payer only
+pub const BASE_FEE_LAMPORTS: u64 = 5000;
+
+/// Largest tip that keeps a round trip net-positive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakEven {
+    // lamports, before fees and tip
+    pub gross_profit: i64,
+    // base fee plus priority fee, lamports
+    pub fees: u64,
+    // 0 when the fees already eat the profit
+    pub max_tip: u64,
+}
+
+impl BreakEven {
+    pub fn new(gross_profit: i64, compute_unit_price: u64, compute_unit_limit: u32) -> Self {
+        // micro-lamports per compute unit, rounded up like the runtime does
+        let priority_fee =
+            (compute_unit_price as u128 * compute_unit_limit as u128).div_ceil(1_000_000) as u64;
+        let fees = BASE_FEE_LAMPORTS + priority_fee;
+        Self {
+            gross_profit,
+            fees,
+            max_tip: (gross_profit - fees as i64).max(0) as u64,
+        }
+    }
+}
+
+/// Quote a round trip and work out the tip it can afford, the base mint
+/// has to be SOL for the fees to add up
+pub async fn break_even(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    jupiter_extra_args: Option<HashMap<String, String>>,
+    round_trip: &RoundTrip<'_>,
+    quote_options: &QuoteOptions,
+    compute_unit_price: u64,
+    compute_unit_limit: u32,
+    latency: &mut LatencyBreakdown,
+) -> Result<(BreakEven, QuoteResponse, QuoteResponse), ArbError> {
+    let (profit, quote_buy_response, quote_sell_response) = caculate_profit(
+        jupiter_swap_api_client,
+        jupiter_extra_args,
+        round_trip,
+        quote_options,
+        latency,
+    )
+    .await?;
+    Ok((
+        BreakEven::new(profit, compute_unit_price, compute_unit_limit),
+        quote_buy_response,
+        quote_sell_response,
+    ))
+}
+
+/// Split mode: the sell leg goes out on its own, so it carries the profit
+/// guard (threshold and slippage) the merged quote would have had. A buy
+/// that fills short leaves too little to sell and the bundle reverts.
+pub fn guard_sell_leg(quote_sell_response: &mut QuoteResponse, merged_quote: &QuoteResponse) {
+    quote_sell_response.other_amount_threshold = merged_quote.other_amount_threshold;
+    quote_sell_response.slippage_bps = merged_quote.slippage_bps;
+    quote_sell_response.platform_fee = merged_quote.platform_fee.clone();
+}
+
+/// Where the jito tip goes in the arb transaction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum TipPlacement {
+    /// Before the swap, pays even if the swap reverts outside a bundle
+    First,
+    /// Right after the swap, before cleanup
+    #[default]
+    AfterSwap,
+    /// After every other instruction
+    Last,
+}
+
+/// Order of the arb transaction and which optional jupiter instructions
+/// make it in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InstructionLayout {
+    pub tip: TipPlacement,
+    pub token_ledger: bool,
+    pub other_instructions: bool,
+}
+
+/// Assemble the arb transaction. A `compute_unit_price` replaces any price
+/// set by jupiter, the jito path passes None and pays the tip only.
 pub fn build_instructions(
     swap_instructions_response: SwapInstructionsResponse,
-    tip_instruction: Instruction,
+    tip_instruction: Option<Instruction>,
+    compute_unit_price: Option<u64>,
+    layout: InstructionLayout,
+) -> Vec<Instruction> {
+    let compute_budget = match compute_unit_price {
+        Some(micro_lamports) => swap_instructions_response
+            .compute_budget_instructions
+            .into_iter()
+            .filter(|ix| !is_set_compute_unit_price(ix))
+            .chain([ComputeBudgetInstruction::set_compute_unit_price(
+                micro_lamports,
+            )])
+            .collect(),
+        None => swap_instructions_response.compute_budget_instructions,
+    };
+    let parts = InstructionParts {
+        compute_budget,
+        token_ledger: swap_instructions_response.token_ledger_instruction,
+        setup: swap_instructions_response.setup_instructions,
+        swap: swap_instructions_response.swap_instruction,
+        cleanup: swap_instructions_response.cleanup_instruction,
+        other: swap_instructions_response.other_instructions,
+    };
+    layout_instructions(parts, tip_instruction, layout)
+}
+
+// the pieces of a swap-instructions response, in jupiter's order
+struct InstructionParts {
+    compute_budget: Vec<Instruction>,
+    token_ledger: Option<Instruction>,
+    setup: Vec<Instruction>,
+    swap: Instruction,
+    cleanup: Option<Instruction>,
+    other: Vec<Instruction>,
+}
+
+fn layout_instructions(
+    parts: InstructionParts,
+    tip_instruction: Option<Instruction>,
+    layout: InstructionLayout,
 ) -> Vec<Instruction> {
-    let mut ixs = Vec::new();
-    // compute budget instructions
-    ixs.extend(swap_instructions_response.compute_budget_instructions);
-    // token ledger instruction
-    // if let Some(token_ledger) = swap_instructions_response.token_ledger_instruction {
-    //     ixs.push(token_ledger);
-    // }
-    // setup
-    ixs.extend(swap_instructions_response.setup_instructions);
-
-    // swap
-    ixs.push(swap_instructions_response.swap_instruction);
-    // jito tips
-    ixs.push(tip_instruction);
-    // cleanup
-    if let Some(cleanup) = swap_instructions_response.cleanup_instruction {
-        ixs.push(cleanup);
-    }
-    // other instructions
-    // ixs.extend(swap_instructions_response.other_instructions);
+    let (mut tip_first, mut tip_after_swap, mut tip_last) = (None, None, None);
+    match layout.tip {
+        TipPlacement::First => tip_first = tip_instruction,
+        TipPlacement::AfterSwap => tip_after_swap = tip_instruction,
+        TipPlacement::Last => tip_last = tip_instruction,
+    }
 
+    let mut ixs = parts.compute_budget;
+    ixs.extend(tip_first);
+    if layout.token_ledger {
+        ixs.extend(parts.token_ledger);
+    }
+    ixs.extend(parts.setup);
+    ixs.push(parts.swap);
+    ixs.extend(tip_after_swap);
+    ixs.extend(parts.cleanup);
+    if layout.other_instructions {
+        ixs.extend(parts.other);
+    }
+    ixs.extend(tip_last);
     ixs
 }
+
+// ComputeBudgetInstruction::SetComputeUnitPrice
+const SET_COMPUTE_UNIT_PRICE_TAG: u8 = 3;
+
+fn is_set_compute_unit_price(ix: &Instruction) -> bool {
+    ix.program_id == compute_budget::id() && ix.data.first() == Some(&SET_COMPUTE_UNIT_PRICE_TAG)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use jupiter_swap_api_client::{
+        JupiterSwapApiClient,
+        quote::{QuoteResponse, SwapMode},
+        transaction_config::{ComputeUnitPriceMicroLamports, TransactionConfig},
+    };
+    use rust_decimal::Decimal;
+    use solana_sdk::{
+        compute_budget::ComputeBudgetInstruction, instruction::Instruction, pubkey::Pubkey,
+    };
+
+    use spl_token_2022::extension::transfer_fee::TransferFee;
+
+    use super::{
+        BreakEven, InstructionLayout, InstructionParts, QuoteCache, QuoteOptions, TipPlacement,
+        apply_transfer_fee, check_path, check_price_impact, check_sell_input, check_sell_threshold,
+        clamped_tip, decayed_sell_out, is_set_compute_unit_price, layout_instructions,
+        merge_quotes, route_summary, route_summary_full, sell_slippage_bps, swap,
+        swap_instructions, swap_request, validate_opportunity, worst_case_profit,
+    };
+    use crate::{
+        dex::Dex,
+        error::ArbError,
+        jito::TipPolicy,
+        observer::Opportunity,
+        testing::{MockJupiter, quote_fixture},
+    };
+
+    #[test]
+    fn test_build_quote_request() {
+        let options = QuoteOptions {
+            slippage_bps: 50,
+            only_direct_routes: Some(false),
+            restrict_intermediate_tokens: Some(true),
+            platform_fee_bps: Some(20),
+            as_legacy_transaction: Some(false),
+            swap_mode: Some(SwapMode::ExactOut),
+            timeout: None,
+            swap_timeout: None,
+            max_price_impact_pct: None,
+            dynamic_slippage_max_bps: None,
+            max_slippage_bps: None,
+            ..Default::default()
+        };
+        let input_mint = Pubkey::new_unique();
+        let output_mint = Pubkey::new_unique();
+        let mut quote_args = HashMap::new();
+        quote_args.insert("api_key".to_string(), "key".to_string());
+
+        let request = options.build_request(
+            &input_mint,
+            &output_mint,
+            1_000_000,
+            &(Dex::RAYDIUM | Dex::WHIRLPOOL).into(),
+            Some(quote_args.clone()),
+        );
+        assert_eq!(request.input_mint, input_mint);
+        assert_eq!(request.output_mint, output_mint);
+        assert_eq!(request.amount, 1_000_000);
+        assert_eq!(request.dexes, Some("Raydium,Whirlpool".to_string()));
+        assert_eq!(request.slippage_bps, 50);
+        assert_eq!(request.only_direct_routes, Some(false));
+        assert_eq!(request.restrict_intermediate_tokens, Some(true));
+        assert_eq!(request.platform_fee_bps, Some(20));
+        assert_eq!(request.as_legacy_transaction, Some(false));
+        assert_eq!(request.swap_mode, Some(SwapMode::ExactOut));
+        assert_eq!(request.quote_args, Some(quote_args));
+    }
+
+    #[test]
+    fn test_build_quote_request_defaults() {
+        let request = QuoteOptions::default().build_request(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            1,
+            &Dex::ALL.into(),
+            None,
+        );
+        assert_eq!(request.slippage_bps, 0);
+        assert_eq!(request.only_direct_routes, None);
+        assert_eq!(request.restrict_intermediate_tokens, None);
+        assert_eq!(request.platform_fee_bps, None);
+        assert_eq!(request.swap_mode, None);
+        assert_eq!(request.quote_args, None);
+    }
+
+    #[test]
+    fn test_check_price_impact() {
+        // 2.5% impact
+        let impact = Decimal::new(25, 3);
+        assert!(check_price_impact("buy", impact, None).is_ok());
+        assert!(check_price_impact("buy", impact, Some(5.0)).is_ok());
+        assert!(matches!(
+            check_price_impact("buy", impact, Some(1.0)),
+            Err(ArbError::HighImpact { leg: "buy", .. })
+        ));
+    }
+
+    #[test]
+    fn test_check_path() {
+        let (sol, a, b) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        assert!(check_path(&[(sol, a), (a, sol)]).is_ok());
+        assert!(check_path(&[(sol, a), (a, b), (b, sol)]).is_ok());
+        // too short
+        assert!(check_path(&[(sol, sol)]).is_err());
+        // gap between legs
+        assert!(matches!(
+            check_path(&[(sol, a), (b, sol)]),
+            Err(ArbError::InvalidPath(_))
+        ));
+        // doesn't return to the start
+        assert!(check_path(&[(sol, a), (a, b)]).is_err());
+    }
+
+    #[test]
+    fn test_worst_case_profit() {
+        // 50 bps of a 1 SOL sell leg eats 0.005 SOL of a 0.01 SOL profit
+        assert_eq!(worst_case_profit(10_000_000, 1_000_000_000, 50), 5_000_000);
+        assert_eq!(worst_case_profit(10_000_000, 1_000_000_000, 0), 10_000_000);
+    }
+
+    #[test]
+    fn test_sell_slippage_bps() {
+        // 1.01 SOL out, 1.005 SOL required: 0.005 / 1.01 = 49 bps of headroom
+        assert_eq!(sell_slippage_bps(1_010_000_000, 1_005_000_000, 100), 49);
+        assert_eq!(sell_slippage_bps(1_010_000_000, 1_005_000_000, 20), 20);
+        // no headroom
+        assert_eq!(sell_slippage_bps(1_000_000_000, 1_005_000_000, 100), 0);
+        // the threshold never drops below what is required
+        let out = 1_010_000_000u64;
+        let bps = sell_slippage_bps(out, 1_005_000_000, 100);
+        assert!(out - (out as u128 * bps as u128).div_ceil(10_000) as u64 >= 1_005_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_clamped_tip() {
+        // a sol tip never reaches jupiter
+        let jupiter = JupiterSwapApiClient::new("http://127.0.0.1:0".to_string());
+        let sol = spl_token::native_mint::id();
+        let policy = TipPolicy::new(0.001, 10_000).unwrap();
+        // the floor raises the tip, the checks have to cover the raised one
+        assert_eq!(
+            clamped_tip(&jupiter, None, &sol, 500, &policy, None)
+                .await
+                .unwrap(),
+            (10_000, 10_000)
+        );
+        assert_eq!(
+            clamped_tip(&jupiter, None, &sol, 50_000, &policy, None)
+                .await
+                .unwrap(),
+            (50_000, 50_000)
+        );
+        assert_eq!(
+            clamped_tip(&jupiter, None, &sol, 5_000_000, &policy, None)
+                .await
+                .unwrap(),
+            (1_000_000, 1_000_000)
+        );
+    }
+
+    #[test]
+    fn test_apply_transfer_fee() {
+        let transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: 100.into(),
+        };
+        // 1000 -> 990 -> 980 (fees are rounded up)
+        assert_eq!(apply_transfer_fee(&transfer_fee, 1000), 980);
+        assert_eq!(apply_transfer_fee(&transfer_fee, 0), 0);
+
+        let capped = TransferFee {
+            maximum_fee: 5.into(),
+            ..transfer_fee
+        };
+        assert_eq!(apply_transfer_fee(&capped, 1000), 990);
+    }
+
+    #[test]
+    fn test_is_set_compute_unit_price() {
+        assert!(is_set_compute_unit_price(
+            &ComputeBudgetInstruction::set_compute_unit_price(1_000)
+        ));
+        assert!(!is_set_compute_unit_price(
+            &ComputeBudgetInstruction::set_compute_unit_limit(200_000)
+        ));
+    }
+
+    #[test]
+    fn test_layout_instructions() {
+        let ix =
+            |name: &str| Instruction::new_with_bytes(Pubkey::new_unique(), name.as_bytes(), vec![]);
+        let parts = || InstructionParts {
+            compute_budget: vec![ix("budget")],
+            token_ledger: Some(ix("ledger")),
+            setup: vec![ix("setup")],
+            swap: ix("swap"),
+            cleanup: Some(ix("cleanup")),
+            other: vec![ix("other")],
+        };
+        let order = |layout: InstructionLayout| -> Vec<String> {
+            layout_instructions(parts(), Some(ix("tip")), layout)
+                .into_iter()
+                .map(|ix| String::from_utf8(ix.data).unwrap())
+                .collect()
+        };
+
+        assert_eq!(
+            order(InstructionLayout::default()),
+            ["budget", "setup", "swap", "tip", "cleanup"]
+        );
+        assert_eq!(
+            order(InstructionLayout {
+                tip: TipPlacement::First,
+                ..Default::default()
+            }),
+            ["budget", "tip", "setup", "swap", "cleanup"]
+        );
+        assert_eq!(
+            order(InstructionLayout {
+                tip: TipPlacement::Last,
+                token_ledger: true,
+                other_instructions: true,
+            }),
+            [
+                "budget", "ledger", "setup", "swap", "cleanup", "other", "tip"
+            ]
+        );
+        let without_tip = layout_instructions(parts(), None, InstructionLayout::default());
+        assert_eq!(without_tip.len(), 4);
+    }
+
+    #[test]
+    fn test_break_even() {
+        let break_even = BreakEven::new(1_000_000, 0, 300_000);
+        assert_eq!(break_even.fees, 5_000);
+        assert_eq!(break_even.max_tip, 995_000);
+
+        // 10_000 micro-lamports * 300k CU = 3_000 lamports
+        let break_even = BreakEven::new(1_000_000, 10_000, 300_000);
+        assert_eq!(break_even.fees, 8_000);
+        assert_eq!(break_even.max_tip, 992_000);
+
+        // rounded up
+        assert_eq!(BreakEven::new(0, 1, 1).fees, 5_001);
+        assert_eq!(BreakEven::new(-10_000, 0, 300_000).max_tip, 0);
+    }
+
+    #[test]
+    fn test_check_sell_input() {
+        assert!(check_sell_input(1_000, 1_000, &SwapMode::ExactIn).is_ok());
+        // a sell quoted on a decayed amount would leave tokens behind
+        assert!(matches!(
+            check_sell_input(1_000, 900, &SwapMode::ExactIn),
+            Err(ArbError::InvalidPath(_))
+        ));
+        assert!(check_sell_input(1_000, 900, &SwapMode::ExactOut).is_ok());
+    }
+
+    #[test]
+    fn test_quote_cache() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ttl = Duration::from_secs(3600);
+        let mut cache = QuoteCache::default();
+        assert!(cache.get("request", ttl).is_none());
+        cache.insert(
+            "request".to_string(),
+            quote_fixture(&a, &b, 100, 90, 1),
+            ttl,
+        );
+        assert_eq!(cache.get("request", ttl).unwrap().out_amount, 90);
+        assert!(cache.get("other", ttl).is_none());
+        // past the ttl the entry is a miss, and dropped by the next insert
+        assert!(cache.get("request", Duration::ZERO).is_none());
+        cache.insert(
+            "other".to_string(),
+            quote_fixture(&b, &a, 90, 100, 2),
+            Duration::ZERO,
+        );
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_merge_quotes() {
+        let sol = spl_token::native_mint::id();
+        let token = Pubkey::new_unique();
+        let buy = quote_fixture(&sol, &token, 1_000_000, 500, 100);
+        let sell = quote_fixture(&token, &sol, 500, 1_010_000, 102);
+
+        let merged = merge_quotes(buy.clone(), sell.clone(), 1_000_000, 5_000).unwrap();
+        assert_eq!(merged.input_mint, sol);
+        assert_eq!(merged.output_mint, sol);
+        assert_eq!(merged.in_amount, 1_000_000);
+        assert_eq!(merged.out_amount, 1_005_000);
+        assert_eq!(merged.other_amount_threshold, 1_005_000);
+        assert_eq!(merged.slippage_bps, 0);
+        assert_eq!(merged.swap_mode, SwapMode::ExactIn);
+        assert!(merged.platform_fee.is_none());
+        assert_eq!(merged.price_impact_pct, Decimal::ZERO);
+        // the later of the two quotes
+        assert_eq!(merged.context_slot, 102);
+        assert_eq!(merged.route_plan.len(), 2);
+
+        // a sell of some other token doesn't chain
+        let other = quote_fixture(&Pubkey::new_unique(), &sol, 500, 1_010_000, 102);
+        assert!(matches!(
+            merge_quotes(buy.clone(), other, 1_000_000, 5_000),
+            Err(ArbError::InvalidPath(_))
+        ));
+        // nor does a round trip that doesn't come back to SOL
+        let away = quote_fixture(&token, &Pubkey::new_unique(), 500, 1_010_000, 102);
+        assert!(matches!(
+            merge_quotes(buy, away, 1_000_000, 5_000),
+            Err(ArbError::InvalidPath(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_sell_threshold() {
+        assert!(check_sell_threshold(1_010, 1_000, 10).is_ok());
+        assert!(matches!(
+            check_sell_threshold(1_009, 1_000, 10),
+            Err(ArbError::ThresholdUnmet {
+                expected_out: 1_009,
+                required: 1_010
+            })
+        ));
+    }
+
+    #[test]
+    fn test_validate_opportunity() {
+        let sol = spl_token::native_mint::id();
+        let token = Pubkey::new_unique();
+        let opportunity = |base: &Pubkey, sell_out: u64| Opportunity {
+            execution_id: uuid::Uuid::new_v4(),
+            mint: token,
+            amount_in: 1_000_000,
+            profit: sell_out as i64 - 1_000_000,
+            tip_amount: 0,
+            tip_lamports: 0,
+            rent: 0,
+            quote_buy_response: quote_fixture(base, &token, 1_000_000, 500, 1),
+            quote_sell_response: quote_fixture(&token, base, 500, sell_out, 1),
+        };
+
+        // principal 1_000_000 + tip 20_000 + fee 5_000 + min profit 10_000
+        assert!(validate_opportunity(&opportunity(&sol, 1_035_000), 20_000, 10_000).is_ok());
+        let err = validate_opportunity(&opportunity(&sol, 1_034_999), 20_000, 10_000).unwrap_err();
+        assert_eq!(err.expected_out, 1_034_999);
+        assert_eq!(err.required, 1_035_000);
+        assert_eq!(err.network_fee, 5_000);
+
+        // the network fee is paid in SOL, not out of another base mint
+        let usdc = Pubkey::new_unique();
+        assert!(validate_opportunity(&opportunity(&usdc, 1_030_000), 20_000, 10_000).is_ok());
+        assert!(validate_opportunity(&opportunity(&usdc, 1_029_999), 20_000, 10_000).is_err());
+    }
+
+    #[test]
+    fn test_decayed_sell_out() {
+        assert_eq!(decayed_sell_out(1_000_000, 1.0, 1.0), 1_000_000);
+        assert_eq!(decayed_sell_out(1_000_000, 0.9, 1.0), 900_000);
+        assert_eq!(decayed_sell_out(1_000_000, 0.9, 0.5), 450_000);
+    }
+
+    #[test]
+    fn test_swap_request_keeps_config() {
+        let quote_response: QuoteResponse = serde_json::from_value(serde_json::json!({
+            "inputMint": "So11111111111111111111111111111111111111112",
+            "inAmount": "1000",
+            "outputMint": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+            "outAmount": "2000",
+            "otherAmountThreshold": "1990",
+            "swapMode": "ExactIn",
+            "slippageBps": 50,
+            "priceImpactPct": "0",
+            "routePlan": [],
+            "contextSlot": 1,
+            "timeTaken": 0.0
+        }))
+        .unwrap();
+        let user = Pubkey::new_unique();
+        let fee_account = Pubkey::new_unique();
+        let config = TransactionConfig {
+            wrap_and_unwrap_sol: false,
+            dynamic_compute_unit_limit: true,
+            use_shared_accounts: Some(false),
+            fee_account: Some(fee_account),
+            compute_unit_price_micro_lamports: Some(ComputeUnitPriceMicroLamports::MicroLamports(
+                50_000,
+            )),
+            ..Default::default()
+        };
+
+        let request = swap_request(&user, &quote_response, config);
+        assert_eq!(request.user_public_key, user);
+        assert_eq!(request.quote_response.out_amount, 2000);
+        assert!(!request.config.wrap_and_unwrap_sol);
+        assert!(request.config.dynamic_compute_unit_limit);
+        assert_eq!(request.config.use_shared_accounts, Some(false));
+        assert_eq!(request.config.fee_account, Some(fee_account));
+        assert!(matches!(
+            request.config.compute_unit_price_micro_lamports,
+            Some(ComputeUnitPriceMicroLamports::MicroLamports(50_000))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_swap_sends_config() {
+        // the canned answer is no transaction, only what the api got matters
+        let mock = MockJupiter::start(|_| "{}".to_string()).await;
+        let jupiter = JupiterSwapApiClient::new(mock.url.clone());
+        let quote_response = quote_fixture(
+            &spl_token::native_mint::id(),
+            &Pubkey::new_unique(),
+            1_000,
+            2_000,
+            1,
+        );
+        let user = Pubkey::new_unique();
+        let fee_account = Pubkey::new_unique();
+        let config = || TransactionConfig {
+            wrap_and_unwrap_sol: false,
+            dynamic_compute_unit_limit: true,
+            use_shared_accounts: Some(false),
+            fee_account: Some(fee_account),
+            compute_unit_price_micro_lamports: Some(ComputeUnitPriceMicroLamports::MicroLamports(
+                50_000,
+            )),
+            ..Default::default()
+        };
+        let expected =
+            serde_json::to_value(swap_request(&user, &quote_response, config())).unwrap();
+
+        let _ = swap(&jupiter, None, &user, &quote_response, config(), None).await;
+        let _ = swap_instructions(&jupiter, None, &user, &quote_response, config(), None).await;
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].path.ends_with("/swap"));
+        assert!(requests[1].path.ends_with("/swap-instructions"));
+        for request in requests {
+            assert_eq!(request.method, "POST");
+            let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+            assert_eq!(body, expected);
+            assert!(request.body.contains(&fee_account.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_route_summary() {
+        let sol = "So11111111111111111111111111111111111111112";
+        let usdc = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+        let raydium = "58oQChx4yWmvKdwLLZzBi4ChoCc2fqCUWBkwMihLYQo2";
+        let whirlpool = "Czfq3xZZDmsdGdUyrNLtRhGc47cXcZtLG4crryfu44zE";
+        let step = |amm_key: &str, label: &str, fee: &str, percent: u8| {
+            serde_json::json!({
+                "swapInfo": {
+                    "ammKey": amm_key,
+                    "label": label,
+                    "inputMint": sol,
+                    "outputMint": usdc,
+                    "inAmount": "1000",
+                    "outAmount": "150",
+                    "feeAmount": fee,
+                    "feeMint": sol
+                },
+                "percent": percent
+            })
+        };
+        let quote: QuoteResponse = serde_json::from_value(serde_json::json!({
+            "inputMint": sol,
+            "inAmount": "1000",
+            "outputMint": usdc,
+            "outAmount": "150",
+            "otherAmountThreshold": "150",
+            "swapMode": "ExactIn",
+            "slippageBps": 0,
+            "priceImpactPct": "0",
+            "routePlan": [
+                step(raydium, "Raydium", "25", 60),
+                step(whirlpool, "Whirlpool", "4", 40)
+            ],
+            "contextSlot": 1,
+            "timeTaken": 0.0
+        }))
+        .unwrap();
+
+        assert_eq!(
+            route_summary(&quote),
+            "60% Raydium(58oQ..YQo2, So11..1112->EPjF..Dt1v, fee 25) | \
+             40% Whirlpool(Czfq..44zE, So11..1112->EPjF..Dt1v, fee 4)"
+        );
+        assert_eq!(
+            route_summary_full(&quote),
+            format!(
+                "60% Raydium({raydium}, {sol}->{usdc}, fee 25) | \
+                 40% Whirlpool({whirlpool}, {sol}->{usdc}, fee 4)"
+            )
+        );
+    }
+}
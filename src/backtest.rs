@@ -0,0 +1,175 @@
+use std::{env, fs, path::Path, sync::LazyLock};
+
+use anyhow::{Result, anyhow};
+use jupiter_swap_api_client::quote::QuoteResponse;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    arb::merge_quotes,
+    history,
+    outcome::{now_millis, pubkey_str},
+};
+
+// when set, every quote pair caculate_profit produces is appended here
+pub static QUOTE_RECORD_PATH: LazyLock<Option<String>> =
+    LazyLock::new(|| env::var("QUOTE_RECORD_PATH").ok().filter(|s| !s.is_empty()));
+
+/// Raw buy/sell quotes of one scan, before decay factors
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteRecord {
+    // unix timestamp in milliseconds
+    pub timestamp: u64,
+    #[serde(with = "pubkey_str")]
+    pub mint: Pubkey,
+    pub amount_in: u64,
+    pub buy: QuoteResponse,
+    pub sell: QuoteResponse,
+}
+
+pub fn record_quotes(
+    mint: &Pubkey,
+    amount_in: u64,
+    buy: &QuoteResponse,
+    sell: &QuoteResponse,
+) -> Result<()> {
+    let Some(path) = QUOTE_RECORD_PATH.as_ref() else {
+        return Ok(());
+    };
+    history::append_jsonl(
+        Path::new(path),
+        &QuoteRecord {
+            timestamp: now_millis(),
+            mint: *mint,
+            amount_in,
+            buy: buy.clone(),
+            sell: sell.clone(),
+        },
+    )
+}
+
+pub fn read_records(path: &Path) -> Result<Vec<QuoteRecord>> {
+    fs::read_to_string(path)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| anyhow!("line {}: {}", i + 1, e)))
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktestParams {
+    // lamports
+    pub min_profit: u64,
+    // share of the profit paid as jito tip
+    pub tip_bps: u16,
+    pub buy_decay_factor: f64,
+    pub sell_decay_factor: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BacktestReport {
+    pub records: usize,
+    pub opportunities: usize,
+    // lamports, after tips
+    pub total_profit: i64,
+    pub total_tip: u64,
+    pub best_profit: Option<i64>,
+}
+
+/// Profit of a recorded round trip under `params`. The sell leg was quoted
+/// for `sell_in` tokens, a different buy decay scales its output linearly.
+pub fn replay_profit(
+    amount_in: u64,
+    buy_out: u64,
+    sell_in: u64,
+    sell_out: u64,
+    fee_amount: u64,
+    params: &BacktestParams,
+) -> i64 {
+    let buy_out = (buy_out as f64 * params.buy_decay_factor) as u64;
+    let sell_out = (sell_out as u128 * buy_out as u128 / sell_in.max(1) as u128) as u64;
+    let sell_out = (sell_out as f64 * params.sell_decay_factor) as u64;
+    sell_out as i64 - amount_in as i64 - fee_amount as i64
+}
+
+/// Replay recorded quotes through the profit math without any network calls.
+/// Token-2022 transfer fees are not part of the record and are ignored.
+pub fn replay(records: &[QuoteRecord], params: &BacktestParams) -> BacktestReport {
+    let native_mint = spl_token::native_mint::id();
+    let mut report = BacktestReport {
+        records: records.len(),
+        ..Default::default()
+    };
+    for record in records {
+        let fee_amount = record
+            .buy
+            .route_plan
+            .iter()
+            .filter(|route| route.swap_info.fee_mint == native_mint)
+            .map(|route| route.swap_info.fee_amount)
+            .sum();
+        let profit = replay_profit(
+            record.amount_in,
+            record.buy.out_amount,
+            record.sell.in_amount,
+            record.sell.out_amount,
+            fee_amount,
+            params,
+        );
+        if profit < params.min_profit as i64 {
+            continue;
+        }
+        let tip = (profit as u128 * params.tip_bps as u128 / 10_000) as u64;
+        // the merged quote is what would have been sent, its threshold
+        // must be met by the replayed sell output
+        // legs that don't chain could never have been sent
+        let Ok(merged) = merge_quotes(
+            record.buy.clone(),
+            record.sell.clone(),
+            record.amount_in,
+            tip,
+        ) else {
+            continue;
+        };
+        let sell_out = record.amount_in + profit as u64 + fee_amount;
+        if merged.other_amount_threshold > sell_out {
+            continue;
+        }
+        let net = profit - tip as i64;
+        report.opportunities += 1;
+        report.total_profit += net;
+        report.total_tip += tip;
+        report.best_profit = Some(report.best_profit.map_or(net, |best| best.max(net)));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BacktestParams, replay_profit};
+
+    #[test]
+    fn test_replay_profit() {
+        let params = BacktestParams {
+            min_profit: 0,
+            tip_bps: 5000,
+            buy_decay_factor: 1.0,
+            sell_decay_factor: 1.0,
+        };
+        // 1 SOL -> 500 tokens -> 1.01 SOL, 1000 lamports of fees
+        assert_eq!(
+            replay_profit(1_000_000_000, 500, 500, 1_010_000_000, 1000, &params),
+            9_999_000
+        );
+        // 10% less tokens scales the sell leg down
+        let decayed = BacktestParams {
+            buy_decay_factor: 0.9,
+            ..params
+        };
+        assert_eq!(
+            replay_profit(1_000_000_000, 500, 500, 1_010_000_000, 0, &decayed),
+            -91_000_000
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use spl_token::amount_to_ui_amount;
+use tokio::{sync::RwLock, time::Instant};
+use tracing::{debug, info, warn};
+
+use crate::{get_rpc_client, metrics, notify, token::get_ata};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BalanceSource {
+    /// Native SOL balance of the wallet
+    Sol,
+    /// Balance of the wallet's WSOL associated token account
+    Wsol,
+}
+
+/// Trade size as a percentage of the spendable balance
+#[derive(Debug, Clone)]
+pub struct PositionSizing {
+    // (0, 100]
+    pub pct: f64,
+    // lamports kept aside for fees and tips
+    pub reserve: u64,
+    // sizes are rounded down to a multiple of this, in lamports
+    pub granularity: u64,
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+impl PositionSizing {
+    /// Size in lamports for the given balance, callers should skip
+    /// the iteration when it falls below `min`
+    pub fn size(&self, balance: u64) -> u64 {
+        let spendable = balance.saturating_sub(self.reserve);
+        let mut amount = (spendable as f64 * self.pct / 100.0) as u64;
+        if let Some(max) = self.max {
+            amount = amount.min(max);
+        }
+        let granularity = self.granularity.max(1);
+        amount / granularity * granularity
+    }
+}
+
+/// Caches the wallet balance for a short time so the scan loop
+/// doesn't hit the RPC on every tick
+pub struct BalanceTracker {
+    owner: Pubkey,
+    source: BalanceSource,
+    ttl: Duration,
+    cache: RwLock<Option<(u64, Instant)>>,
+}
+
+impl BalanceTracker {
+    pub fn new(owner: Pubkey, source: BalanceSource, ttl: Duration) -> Self {
+        Self {
+            owner,
+            source,
+            ttl,
+            cache: RwLock::new(None),
+        }
+    }
+
+    // unit lamports
+    pub async fn get(&self, client: &RpcClient) -> Result<u64> {
+        if let Some((balance, fetched_at)) = *self.cache.read().await {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(balance);
+            }
+        }
+
+        let balance = match self.source {
+            BalanceSource::Sol => client.get_balance(&self.owner)?,
+            BalanceSource::Wsol => {
+                let ata = get_ata(&self.owner, &spl_token::native_mint::id());
+                client
+                    .get_token_account_balance(&ata)?
+                    .amount
+                    .parse::<u64>()?
+            }
+        };
+        debug!("balance({:?}): {} lamports", self.source, balance);
+        *self.cache.write().await = Some((balance, Instant::now()));
+        Ok(balance)
+    }
+
+    pub async fn invalidate(&self) {
+        *self.cache.write().await = None;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LowBalanceConfig {
+    // lamports, a payer's native balance below this alerts
+    pub threshold: u64,
+    pub interval: Duration,
+    pub webhook: Option<String>,
+}
+
+/// What the low balance alert pushes
+#[derive(Debug, Serialize)]
+struct LowBalanceAlert {
+    wallet: String,
+    balance: u64,
+    threshold: u64,
+}
+
+/// Poll the native balance of the payers, exporting it as a gauge, and alert
+/// once when one drops below the threshold. The alert re-arms when the
+/// balance is back above it, a wallet running dry isn't reported every poll.
+pub async fn run_low_balance_alert(wallets: Vec<Pubkey>, config: LowBalanceConfig) {
+    let client = reqwest::Client::new();
+    let mut low = vec![false; wallets.len()];
+    loop {
+        for (wallet, low) in wallets.iter().zip(low.iter_mut()) {
+            let balance = match get_rpc_client()
+                .and_then(|rpc| rpc.get_balance(wallet).map_err(Into::into))
+            {
+                Ok(balance) => balance,
+                Err(e) => {
+                    warn!("Failed to fetch balance of {}: {}", wallet, e);
+                    continue;
+                }
+            };
+            let label = wallet.to_string();
+            let labels = [("wallet", label.as_str())];
+            metrics::set_gauge("arb_wallet_balance_lamports", &labels, balance as f64);
+            let crossing = crossing(low, balance, config.threshold);
+            metrics::set_gauge("arb_wallet_low_balance", &labels, *low as u8 as f64);
+            match crossing {
+                Some(Crossing::Below) => alert(&client, &config, wallet, balance).await,
+                Some(Crossing::Recovered) => info!(
+                    "💰 {} balance recovered to {} SOL",
+                    wallet,
+                    amount_to_ui_amount(balance, 9)
+                ),
+                None => {}
+            }
+        }
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Crossing {
+    Below,
+    Recovered,
+}
+
+// how `balance` moved against the threshold, `low` remembers the side it
+// was on last time
+fn crossing(low: &mut bool, balance: u64, threshold: u64) -> Option<Crossing> {
+    let was_low = std::mem::replace(low, balance < threshold);
+    match (was_low, *low) {
+        (false, true) => Some(Crossing::Below),
+        (true, false) => Some(Crossing::Recovered),
+        _ => None,
+    }
+}
+
+async fn alert(client: &reqwest::Client, config: &LowBalanceConfig, wallet: &Pubkey, balance: u64) {
+    let text = format!(
+        "solana-arb payer {} is low on SOL: {} below {}",
+        wallet,
+        amount_to_ui_amount(balance, 9),
+        amount_to_ui_amount(config.threshold, 9)
+    );
+    warn!("🪫 {}", text);
+    let Some(webhook) = &config.webhook else {
+        return;
+    };
+    let details = LowBalanceAlert {
+        wallet: wallet.to_string(),
+        balance,
+        threshold: config.threshold,
+    };
+    if let Err(e) = notify::post(client, webhook, text, "low_balance", &details).await {
+        warn!("Failed to push low balance alert: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Crossing, PositionSizing, crossing};
+
+    fn sizing() -> PositionSizing {
+        PositionSizing {
+            pct: 25.0,
+            reserve: 100_000_000,
+            granularity: 1_000_000,
+            min: 10_000_000,
+            max: None,
+        }
+    }
+
+    #[test]
+    fn test_size_from_spendable_balance() {
+        // (2.1 SOL - 0.1 reserve) * 25% = 0.5 SOL
+        assert_eq!(sizing().size(2_100_000_000), 500_000_000);
+        // rounded down to the granularity
+        assert_eq!(sizing().size(2_103_999_999), 500_000_000);
+        // balance below the reserve
+        assert_eq!(sizing().size(50_000_000), 0);
+    }
+
+    #[test]
+    fn test_size_clamped_by_max() {
+        let sizing = PositionSizing {
+            max: Some(200_000_000),
+            ..sizing()
+        };
+        assert_eq!(sizing.size(10_100_000_000), 200_000_000);
+    }
+
+    #[test]
+    fn test_low_balance_crossing() {
+        let mut low = false;
+        assert_eq!(crossing(&mut low, 2_000, 1_000), None);
+        assert_eq!(crossing(&mut low, 999, 1_000), Some(Crossing::Below));
+        // alerted once for the whole dry spell
+        assert_eq!(crossing(&mut low, 500, 1_000), None);
+        assert_eq!(crossing(&mut low, 1_000, 1_000), Some(Crossing::Recovered));
+        assert!(!low);
+        assert_eq!(crossing(&mut low, 0, 1_000), Some(Crossing::Below));
+    }
+}
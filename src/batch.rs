@@ -0,0 +1,252 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::anyhow;
+use solana_sdk::{signature::Keypair, system_transaction, transaction::VersionedTransaction};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+use tracing::{debug, info};
+
+use crate::{
+    error::TxError,
+    get_rpc_client, jito,
+    latency::LatencyBreakdown,
+    tx::{self, ConfirmationConfig, SendResult},
+};
+
+// jito's limit on transactions per bundle
+pub const MAX_BUNDLE_TXS: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    // how long the first execution of a batch waits for others to join
+    pub window: Duration,
+    pub wait_for_confirmation: bool,
+    pub confirmation: ConfirmationConfig,
+}
+
+// one execution's signed transactions, waiting for the bundle they go out in
+struct BatchItem {
+    txs: Vec<VersionedTransaction>,
+    tip_lamports: u64,
+    payer: Arc<Keypair>,
+    reply: oneshot::Sender<Result<SendResult, TxError>>,
+}
+
+/// Combines the executions ready within a window into one jito bundle, so
+/// two profitable mints don't compete with each other for the same block.
+/// The bundle carries a single tip, the sum of theirs, as its last
+/// transaction.
+#[derive(Clone)]
+pub struct Batcher {
+    sender: mpsc::Sender<BatchItem>,
+}
+
+impl Batcher {
+    pub fn spawn(config: BatchConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(64);
+        tokio::spawn(run_batches(config, receiver));
+        Self { sender }
+    }
+
+    /// Queue signed transactions for the next bundle, `payer` signs the tip
+    /// when they lead the batch. Resolves with the outcome of the bundle
+    /// they went out in, the signatures being their own.
+    pub async fn send(
+        &self,
+        txs: Vec<VersionedTransaction>,
+        tip_lamports: u64,
+        payer: Arc<Keypair>,
+    ) -> Result<SendResult, TxError> {
+        if txs.is_empty() || !fits(0, txs.len()) {
+            return Err(TxError::Other(anyhow!(
+                "{} transactions can't be batched with a tip",
+                txs.len()
+            )));
+        }
+        let (reply, receiver) = oneshot::channel();
+        let item = BatchItem {
+            txs,
+            tip_lamports,
+            payer,
+            reply,
+        };
+        self.sender
+            .send(item)
+            .await
+            .map_err(|_| TxError::Other(anyhow!("bundle batcher stopped")))?;
+        receiver
+            .await
+            .map_err(|_| TxError::Other(anyhow!("bundle batch dropped")))?
+    }
+}
+
+// whether `txs` more fit next to `batched` and the tip
+fn fits(batched: usize, txs: usize) -> bool {
+    batched + txs < MAX_BUNDLE_TXS
+}
+
+async fn run_batches(config: BatchConfig, mut receiver: mpsc::Receiver<BatchItem>) {
+    let mut next = None;
+    loop {
+        let first = match next.take() {
+            Some(item) => item,
+            None => match receiver.recv().await {
+                Some(item) => item,
+                None => return,
+            },
+        };
+        let deadline = Instant::now() + config.window;
+        let mut batched = first.txs.len();
+        let mut batch = vec![first];
+        while fits(batched, 1) {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(item)) if fits(batched, item.txs.len()) => {
+                    batched += item.txs.len();
+                    batch.push(item);
+                }
+                // leads the next batch
+                Ok(Some(item)) => {
+                    next = Some(item);
+                    break;
+                }
+                Ok(None) | Err(_) => break,
+            }
+        }
+        // confirmation takes a while, the next batch collects meanwhile
+        tokio::spawn(send_batch(config.clone(), batch));
+    }
+}
+
+async fn send_batch(config: BatchConfig, batch: Vec<BatchItem>) {
+    let result = send(&config, &batch).await;
+    for item in batch {
+        let result = match &result {
+            Ok(sent) => Ok(attribute(sent, &item.txs)),
+            Err(e) => Err(share(e)),
+        };
+        let _ = item.reply.send(result);
+    }
+}
+
+async fn send(config: &BatchConfig, batch: &[BatchItem]) -> Result<SendResult, TxError> {
+    let client = get_rpc_client()?;
+    let tip_account = jito::get_tip_account().await?;
+    let tip_lamports = jito::TIP_POLICY.clamp(batch.iter().map(|item| item.tip_lamports).sum());
+    let leader = &batch[0];
+    let blockhash = *leader.txs[0].message.recent_blockhash();
+    let mut bundle: Vec<VersionedTransaction> = batch
+        .iter()
+        .flat_map(|item| item.txs.iter().cloned())
+        .collect();
+    bundle.push(VersionedTransaction::from(system_transaction::transfer(
+        &leader.payer,
+        &tip_account,
+        tip_lamports,
+        blockhash,
+    )));
+    match batch.len() {
+        1 => debug!("batch window closed with a single execution"),
+        n => info!(
+            "📚 batching {} executions in one bundle of {} txs, tip {} lamports",
+            n,
+            bundle.len(),
+            tip_lamports
+        ),
+    }
+    let mut latency = LatencyBreakdown::default();
+    let mut bundle_id = None;
+    let mut sent = tx::send_bundle(
+        &client,
+        bundle,
+        config.wait_for_confirmation,
+        &config.confirmation,
+        |id| bundle_id = Some(id.to_string()),
+        &mut latency,
+    )
+    .await?;
+    sent.bundle_id = bundle_id;
+    Ok(sent)
+}
+
+// the bundle outcome as seen by the execution that sent `txs`
+fn attribute(sent: &SendResult, txs: &[VersionedTransaction]) -> SendResult {
+    let signatures = match sent.signatures.is_empty() {
+        true => vec![],
+        false => txs.iter().map(|tx| tx.signatures[0].to_string()).collect(),
+    };
+    SendResult {
+        signatures,
+        ..sent.clone()
+    }
+}
+
+// every execution of a failed bundle gets the error, kept under the same
+// kind where it carries only text
+fn share(err: &TxError) -> TxError {
+    match err {
+        TxError::BundleRejected { reason } => TxError::BundleRejected {
+            reason: reason.clone(),
+        },
+        TxError::ConfirmationTimeout { bundle_id } => TxError::ConfirmationTimeout {
+            bundle_id: bundle_id.clone(),
+        },
+        TxError::TransactionFailed(reason) => TxError::TransactionFailed(reason.clone()),
+        err => TxError::Other(anyhow!("{}", err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{
+        hash::Hash, pubkey::Pubkey, signature::Keypair, system_transaction,
+        transaction::VersionedTransaction,
+    };
+
+    use super::{MAX_BUNDLE_TXS, attribute, fits};
+    use crate::tx::SendResult;
+
+    #[test]
+    fn test_fits() {
+        // four txs and the tip fill a bundle
+        assert!(fits(0, MAX_BUNDLE_TXS - 1));
+        assert!(!fits(0, MAX_BUNDLE_TXS));
+        assert!(fits(2, 2));
+        assert!(!fits(3, 2));
+    }
+
+    #[test]
+    fn test_attribute() {
+        let payer = Keypair::new();
+        let tx = |lamports| {
+            VersionedTransaction::from(system_transaction::transfer(
+                &payer,
+                &Pubkey::new_unique(),
+                lamports,
+                Hash::default(),
+            ))
+        };
+        let own = [tx(1)];
+        let sent = SendResult {
+            signatures: vec!["a".to_string(), "b".to_string(), "tip".to_string()],
+            bundle_id: Some("bundle".to_string()),
+            submit_slot: Some(10),
+            landed_slot: Some(12),
+        };
+        let attributed = attribute(&sent, &own);
+        assert_eq!(
+            attributed.signatures,
+            vec![own[0].signatures[0].to_string()]
+        );
+        assert_eq!(attributed.bundle_id.as_deref(), Some("bundle"));
+        assert_eq!(attributed.landed_slot, Some(12));
+
+        // not confirmed yet, there is nothing to attribute
+        let submitted = SendResult {
+            signatures: vec![],
+            ..sent
+        };
+        assert!(attribute(&submitted, &own).signatures.is_empty());
+    }
+}
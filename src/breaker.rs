@@ -0,0 +1,299 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::{
+    error::{ArbError, TxError},
+    outcome::now_millis,
+};
+
+/// The provider a failure is blamed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureCategory {
+    Rpc,
+    Jupiter,
+    Jito,
+    Other,
+}
+
+impl FailureCategory {
+    pub fn of(err: &ArbError) -> Self {
+        match err {
+            ArbError::QuoteHttp { .. }
+            | ArbError::QuoteTransport(_)
+            | ArbError::JupiterTimeout { .. } => FailureCategory::Jupiter,
+            ArbError::Tx(
+                TxError::BundleRejected { .. }
+                | TxError::ConfirmationTimeout { .. }
+                | TxError::InvalidBundleStatus(_),
+            ) => FailureCategory::Jito,
+            ArbError::Tx(
+                TxError::Rpc(_) | TxError::BroadcastFailed(_) | TxError::SignatureTimeout { .. },
+            ) => FailureCategory::Rpc,
+            _ => FailureCategory::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BreakerConfig {
+    // consecutive failures of one category that trip the breaker, 0 disables it
+    pub threshold: u32,
+    // first pause, doubled on every trip until a scan succeeds
+    pub cooldown: Duration,
+    pub max_cooldown: Duration,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 5,
+            cooldown: Duration::from_secs(10),
+            max_cooldown: Duration::from_secs(300),
+        }
+    }
+}
+
+/// The part of the breaker that survives a restart
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BreakerState {
+    pub trips: u32,
+    // unix timestamp in milliseconds
+    pub open_until: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+    failures: HashMap<FailureCategory, u32>,
+    trips: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            failures: HashMap::new(),
+            trips: 0,
+            open_until: None,
+        }
+    }
+
+    pub fn record_success(&mut self) {
+        if self.trips > 0 {
+            info!("circuit breaker: scan succeeded, closed");
+        }
+        self.failures.clear();
+        self.trips = 0;
+    }
+
+    /// Returns the cooldown when this failure trips the breaker
+    pub fn record_failure(&mut self, category: FailureCategory, now: Instant) -> Option<Duration> {
+        if self.config.threshold == 0 {
+            return None;
+        }
+        let failures = self.failures.entry(category).or_default();
+        *failures += 1;
+        if *failures < self.config.threshold {
+            return None;
+        }
+        let failures = *failures;
+        self.failures.clear();
+        let cooldown = self
+            .config
+            .cooldown
+            .saturating_mul(2u32.saturating_pow(self.trips))
+            .min(self.config.max_cooldown);
+        self.trips += 1;
+        self.open_until = Some(now + cooldown);
+        warn!(
+            "circuit breaker tripped: {} consecutive {:?} failures, pausing scans for {:?} (trip {})",
+            failures, category, cooldown, self.trips
+        );
+        Some(cooldown)
+    }
+
+    /// Time left before scanning may resume
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.open_until
+            .map(|until| until.saturating_duration_since(now))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// `now_ms` is `now` as a unix timestamp in milliseconds
+    pub fn state(&self, now: Instant, now_ms: u64) -> BreakerState {
+        BreakerState {
+            trips: self.trips,
+            open_until: self
+                .remaining(now)
+                .map(|remaining| now_ms + remaining.as_millis() as u64),
+        }
+    }
+
+    pub fn restore(&mut self, state: &BreakerState, now: Instant, now_ms: u64) {
+        self.trips = state.trips;
+        self.open_until = state
+            .open_until
+            .filter(|until| *until > now_ms)
+            .map(|until| now + Duration::from_millis(until - now_ms));
+    }
+}
+
+static BREAKER: LazyLock<Mutex<CircuitBreaker>> =
+    LazyLock::new(|| Mutex::new(CircuitBreaker::new(BreakerConfig::default())));
+
+pub fn configure(config: BreakerConfig) {
+    *BREAKER.lock().unwrap() = CircuitBreaker::new(config);
+}
+
+/// Counts a failed scan, benign errors say nothing about provider health
+pub fn record_error(err: &ArbError) {
+    if err.is_benign() {
+        return;
+    }
+    BREAKER
+        .lock()
+        .unwrap()
+        .record_failure(FailureCategory::of(err), Instant::now());
+}
+
+pub fn record_success() {
+    BREAKER.lock().unwrap().record_success();
+}
+
+pub fn remaining() -> Option<Duration> {
+    BREAKER.lock().unwrap().remaining(Instant::now())
+}
+
+pub fn state() -> BreakerState {
+    BREAKER.lock().unwrap().state(Instant::now(), now_millis())
+}
+
+/// Call after `configure`, which starts a closed breaker
+pub fn restore(state: &BreakerState) {
+    BREAKER
+        .lock()
+        .unwrap()
+        .restore(state, Instant::now(), now_millis());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::{BreakerConfig, BreakerState, CircuitBreaker, FailureCategory};
+    use crate::error::{ArbError, TxError};
+
+    #[test]
+    fn test_failure_category() {
+        let err = ArbError::JupiterTimeout {
+            call: "quote",
+            timeout_ms: 1000,
+        };
+        assert_eq!(FailureCategory::of(&err), FailureCategory::Jupiter);
+        let err = ArbError::Tx(TxError::BundleRejected {
+            reason: "rate limited".to_string(),
+        });
+        assert_eq!(FailureCategory::of(&err), FailureCategory::Jito);
+        let err = ArbError::Tx(TxError::BroadcastFailed("down".to_string()));
+        assert_eq!(FailureCategory::of(&err), FailureCategory::Rpc);
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let mut breaker = CircuitBreaker::new(BreakerConfig {
+            threshold: 3,
+            cooldown: Duration::from_secs(10),
+            max_cooldown: Duration::from_secs(30),
+        });
+        let now = Instant::now();
+
+        // failures of different categories don't add up
+        assert!(breaker.record_failure(FailureCategory::Rpc, now).is_none());
+        assert!(breaker.record_failure(FailureCategory::Rpc, now).is_none());
+        assert!(breaker.record_failure(FailureCategory::Jito, now).is_none());
+        assert_eq!(
+            breaker.record_failure(FailureCategory::Rpc, now),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(breaker.remaining(now), Some(Duration::from_secs(10)));
+        assert!(breaker.remaining(now + Duration::from_secs(10)).is_none());
+
+        // doubles on every trip, up to the max
+        for _ in 0..2 {
+            breaker.record_failure(FailureCategory::Jupiter, now);
+        }
+        assert_eq!(
+            breaker.record_failure(FailureCategory::Jupiter, now),
+            Some(Duration::from_secs(20))
+        );
+        for _ in 0..2 {
+            breaker.record_failure(FailureCategory::Jupiter, now);
+        }
+        assert_eq!(
+            breaker.record_failure(FailureCategory::Jupiter, now),
+            Some(Duration::from_secs(30))
+        );
+
+        // a successful scan starts over
+        breaker.record_success();
+        for _ in 0..2 {
+            breaker.record_failure(FailureCategory::Jupiter, now);
+        }
+        assert_eq!(
+            breaker.record_failure(FailureCategory::Jupiter, now),
+            Some(Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn test_circuit_breaker_disabled() {
+        let mut breaker = CircuitBreaker::new(BreakerConfig {
+            threshold: 0,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert!(breaker.record_failure(FailureCategory::Rpc, now).is_none());
+        }
+        assert!(breaker.remaining(now).is_none());
+    }
+
+    #[test]
+    fn test_breaker_state() {
+        let mut breaker = CircuitBreaker::new(BreakerConfig {
+            threshold: 1,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        breaker.record_failure(FailureCategory::Jito, now);
+        let state = breaker.state(now, 1_000_000);
+        assert_eq!(
+            state,
+            BreakerState {
+                trips: 1,
+                open_until: Some(1_010_000),
+            }
+        );
+
+        // restored 4s later, the remaining 6s of the cooldown still apply
+        let mut restored = CircuitBreaker::new(BreakerConfig::default());
+        restored.restore(&state, now, 1_004_000);
+        assert_eq!(restored.remaining(now), Some(Duration::from_secs(6)));
+        assert_eq!(restored.state(now, 1_004_000), state);
+
+        // an expired cooldown is dropped, the trip count is kept
+        restored.restore(&state, now, 1_020_000);
+        assert!(restored.remaining(now).is_none());
+        assert_eq!(restored.state(now, 1_020_000).trips, 1);
+    }
+}
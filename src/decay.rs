@@ -0,0 +1,158 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use jupiter_swap_api_client::quote::QuoteResponse;
+
+use crate::dex::Dex;
+
+/// Share of a quoted output trusted to actually fill, in (0, 1]
+pub fn parse_factor(s: &str) -> Result<f64, String> {
+    let factor: f64 = s
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid decay factor {:?}: {}", s, e))?;
+    if factor <= 0.0 || factor > 1.0 {
+        return Err(format!("decay factor must be in (0, 1], got {}", factor));
+    }
+    Ok(factor)
+}
+
+/// A decay factor for legs routed mostly through one venue, e.g.
+/// "Meteora DLMM=0.97"
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecayOverride {
+    pub label: String,
+    pub factor: f64,
+}
+
+impl FromStr for DecayOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (label, factor) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("expected <dex>=<factor>, got {:?}", s))?;
+        let label = label.trim();
+        // a typo would otherwise never match a route
+        let dex = Dex::from_str(label)?;
+        if dex.iter().count() != 1 {
+            return Err(format!("expected a single dex, got {:?}", label));
+        }
+        Ok(Self {
+            label: label.to_string(),
+            factor: parse_factor(factor)?,
+        })
+    }
+}
+
+impl fmt::Display for DecayOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.label, self.factor)
+    }
+}
+
+/// Decay factors of caculate_profit, validated once at startup. They only
+/// make the profit estimate more conservative, quotes are routed as they are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecayPolicy {
+    pub buy: f64,
+    pub sell: f64,
+    // by jupiter label, replaces buy/sell for a leg mostly routed there
+    pub overrides: HashMap<String, f64>,
+}
+
+impl Default for DecayPolicy {
+    fn default() -> Self {
+        Self {
+            buy: 1.0,
+            sell: 1.0,
+            overrides: HashMap::new(),
+        }
+    }
+}
+
+impl DecayPolicy {
+    pub fn new(buy: f64, sell: f64, overrides: &[DecayOverride]) -> Self {
+        Self {
+            buy,
+            sell,
+            overrides: overrides
+                .iter()
+                .map(|o| (o.label.clone(), o.factor))
+                .collect(),
+        }
+    }
+
+    pub fn buy_factor(&self, leg: &QuoteResponse) -> f64 {
+        self.factor(self.buy, leg)
+    }
+
+    pub fn sell_factor(&self, leg: &QuoteResponse) -> f64 {
+        self.factor(self.sell, leg)
+    }
+
+    fn factor(&self, default: f64, leg: &QuoteResponse) -> f64 {
+        if self.overrides.is_empty() {
+            return default;
+        }
+        let labels = leg
+            .route_plan
+            .iter()
+            .map(|step| (step.swap_info.label.as_str(), step.percent));
+        dominant_label(labels)
+            .and_then(|label| self.overrides.get(label))
+            .copied()
+            .unwrap_or(default)
+    }
+}
+
+// the venue carrying the largest share of the route, summed over its steps;
+// the first one wins a tie
+fn dominant_label<'a>(steps: impl Iterator<Item = (&'a str, u8)>) -> Option<&'a str> {
+    let mut shares: Vec<(&str, u32)> = vec![];
+    for (label, percent) in steps {
+        match shares.iter_mut().find(|(l, _)| *l == label) {
+            Some((_, share)) => *share += percent as u32,
+            None => shares.push((label, percent as u32)),
+        }
+    }
+    shares
+        .iter()
+        .rev()
+        .max_by_key(|(_, share)| *share)
+        .map(|(label, _)| *label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecayOverride, dominant_label, parse_factor};
+
+    #[test]
+    fn test_parse_factor() {
+        assert_eq!(parse_factor("0.97"), Ok(0.97));
+        assert_eq!(parse_factor("1"), Ok(1.0));
+        assert!(parse_factor("0").is_err());
+        assert!(parse_factor("1.01").is_err());
+        assert!(parse_factor("high").is_err());
+    }
+
+    #[test]
+    fn test_decay_override() {
+        let o: DecayOverride = "Meteora DLMM=0.97".parse().unwrap();
+        assert_eq!(o.label, "Meteora DLMM");
+        assert_eq!(o.factor, 0.97);
+        assert_eq!(o.to_string(), "Meteora DLMM=0.97");
+        assert!("Meteora DLMM".parse::<DecayOverride>().is_err());
+        assert!("Raydum=0.9".parse::<DecayOverride>().is_err());
+        assert!("Raydium,Whirlpool=0.9".parse::<DecayOverride>().is_err());
+        assert!("Raydium=1.5".parse::<DecayOverride>().is_err());
+    }
+
+    #[test]
+    fn test_dominant_label() {
+        let steps = [("Raydium", 30), ("Whirlpool", 40), ("Raydium", 30)];
+        assert_eq!(dominant_label(steps.into_iter()), Some("Raydium"));
+        let tie = [("Whirlpool", 50), ("Raydium", 50)];
+        assert_eq!(dominant_label(tie.into_iter()), Some("Whirlpool"));
+        assert_eq!(dominant_label(std::iter::empty()), None);
+    }
+}
@@ -1,4 +1,19 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::Result;
 use bitflags::bitflags;
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, SeqAccess, Visitor},
+};
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{pubkey, pubkey::Pubkey};
+use tracing::{debug, warn};
 
 bitflags! {
     #[derive(Debug, Clone, Copy)]
@@ -12,6 +27,15 @@ bitflags! {
     }
 }
 
+// jupiter's label of every single venue, in declaration order
+const LABELS: [(Dex, &str); 5] = [
+    (Dex::RAYDIUM, "Raydium"),
+    (Dex::METEORA_DLMM, "Meteora DLMM"),
+    (Dex::METEORA, "Meteora"),
+    (Dex::WHIRLPOOL, "Whirlpool"),
+    (Dex::PHOENIX, "Phoenix"),
+];
+
 impl Dex {
     pub fn exclude(&self, other: &Dex) -> Self {
         Self::from_bits_truncate(self.bits() & !other.bits())
@@ -21,39 +45,364 @@ impl Dex {
     pub fn from_vec(v: Vec<&str>) -> Self {
         let mut dex = Dex::empty();
         for d in v {
-            match d {
-                "Raydium" => dex |= Dex::RAYDIUM,
-                "Meteora DLMM" => dex |= Dex::METEORA_DLMM,
-                "Meteora" => dex |= Dex::METEORA,
-                "Whirlpool" => dex |= Dex::WHIRLPOOL,
-                "Phoenix" => dex |= Dex::PHOENIX,
-                _ => {}
+            if let Some((venue, _)) = LABELS.iter().find(|(_, label)| *label == d) {
+                dex |= *venue;
             }
         }
         dex
     }
 }
 
-impl ToString for Dex {
-    fn to_string(&self) -> String {
-        let mut dexes = Vec::new();
+/// Comma-separated venue labels as jupiter names them, e.g. "Raydium,Whirlpool"
+impl FromStr for Dex {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut dex = Dex::empty();
+        for label in s
+            .split(',')
+            .map(str::trim)
+            .filter(|label| !label.is_empty())
+        {
+            let venue = Dex::from_vec(vec![label]);
+            if venue.is_empty() {
+                return Err(format!(
+                    "unknown dex {}, expected one of: {}",
+                    label,
+                    Dex::ALL | Dex::METEORA
+                ));
+            }
+            dex |= venue;
+        }
+        if dex.is_empty() {
+            return Err("no dex given".to_string());
+        }
+        Ok(dex)
+    }
+}
+
+/// Comma-separated jupiter labels, the inverse of `from_str`. `iter()`
+/// from bitflags yields the single venues in the same order.
+impl fmt::Display for Dex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels: Vec<&str> = LABELS
+            .iter()
+            .filter(|(venue, _)| self.contains(*venue))
+            .map(|(_, label)| *label)
+            .collect();
+        f.write_str(&labels.join(","))
+    }
+}
+
+/// Serialized as the comma-separated labels, read back from either that
+/// or an array of labels
+impl Serialize for Dex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Dex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DexVisitor)
+    }
+}
+
+struct DexVisitor;
+
+impl<'de> Visitor<'de> for DexVisitor {
+    type Value = Dex;
 
-        if self.contains(Dex::RAYDIUM) {
-            dexes.push("Raydium");
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("comma-separated dex labels or an array of them")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Dex, E> {
+        // the empty set serializes to an empty string
+        if v.trim().is_empty() {
+            return Ok(Dex::empty());
         }
-        if self.contains(Dex::METEORA_DLMM) {
-            dexes.push("Meteora DLMM");
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Dex, A::Error> {
+        let mut dex = Dex::empty();
+        while let Some(label) = seq.next_element::<String>()? {
+            dex |= label.parse::<Dex>().map_err(de::Error::custom)?;
         }
-        if self.contains(Dex::METEORA) {
-            dexes.push("Meteora");
+        Ok(dex)
+    }
+}
+
+/// Venues to quote: the known `Dex` ones plus jupiter labels this crate
+/// doesn't know yet, passed through untouched
+#[derive(Debug, Clone, Default)]
+pub struct DexSelection {
+    pub dexes: Dex,
+    pub custom: Vec<String>,
+}
+
+impl DexSelection {
+    /// Comma-separated labels as `Dex::from_str`, unknown ones go to
+    /// `custom` when `allow_unknown` and are an error otherwise
+    pub fn parse(s: &str, allow_unknown: bool) -> Result<Self, String> {
+        let mut selection = DexSelection::default();
+        for label in s
+            .split(',')
+            .map(str::trim)
+            .filter(|label| !label.is_empty())
+        {
+            match label.parse::<Dex>() {
+                Ok(venue) => selection.dexes |= venue,
+                Err(e) if !allow_unknown => return Err(e),
+                Err(_) => {
+                    if !selection.custom.iter().any(|custom| custom == label) {
+                        selection.custom.push(label.to_string());
+                    }
+                }
+            }
         }
-        if self.contains(Dex::WHIRLPOOL) {
-            dexes.push("Whirlpool");
+        if selection.is_empty() {
+            return Err("no dex given".to_string());
         }
-        if self.contains(Dex::PHOENIX) {
-            dexes.push("Phoenix");
+        Ok(selection)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dexes.is_empty() && self.custom.is_empty()
+    }
+
+    /// Only the known venues also in `venues`, custom labels can't be looked
+    /// up on-chain and are kept
+    pub fn intersect(&self, venues: Dex) -> Self {
+        Self {
+            dexes: self.dexes & venues,
+            custom: self.custom.clone(),
         }
+    }
+}
+
+impl From<Dex> for DexSelection {
+    fn from(dexes: Dex) -> Self {
+        Self {
+            dexes,
+            custom: vec![],
+        }
+    }
+}
+
+/// Lenient, unknown labels are kept as custom. Use `parse` to reject them.
+impl FromStr for DexSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, true)
+    }
+}
+
+/// The known labels then the custom ones, comma-separated as jupiter takes
+/// them
+impl fmt::Display for DexSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut labels = vec![self.dexes.to_string()];
+        labels.extend(self.custom.iter().cloned());
+        labels.retain(|label| !label.is_empty());
+        f.write_str(&labels.join(","))
+    }
+}
+
+// where a venue's pool account stores its two mints
+struct PoolLayout {
+    dex: Dex,
+    program_id: Pubkey,
+    data_size: Option<u64>,
+    mint_offsets: (usize, usize),
+}
+
+const POOL_LAYOUTS: &[PoolLayout] = &[
+    // amm v4: base_mint, quote_mint
+    PoolLayout {
+        dex: Dex::RAYDIUM,
+        program_id: pubkey!("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8"),
+        data_size: Some(752),
+        mint_offsets: (400, 432),
+    },
+    // lb_pair: token_x_mint, token_y_mint
+    PoolLayout {
+        dex: Dex::METEORA_DLMM,
+        program_id: pubkey!("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo"),
+        data_size: Some(904),
+        mint_offsets: (88, 120),
+    },
+    // dynamic amm pool: token_a_mint, token_b_mint
+    PoolLayout {
+        dex: Dex::METEORA,
+        program_id: pubkey!("Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB"),
+        data_size: None,
+        mint_offsets: (40, 72),
+    },
+    // whirlpool: token_mint_a, token_mint_b
+    PoolLayout {
+        dex: Dex::WHIRLPOOL,
+        program_id: pubkey!("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc"),
+        data_size: Some(653),
+        mint_offsets: (101, 181),
+    },
+    // market header: base_params.mint_key, quote_params.mint_key
+    PoolLayout {
+        dex: Dex::PHOENIX,
+        program_id: pubkey!("PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY"),
+        data_size: None,
+        mint_offsets: (48, 128),
+    },
+];
+
+/// Venues among `candidates` that have a pool pairing `mint` with
+/// `base_mint`. A venue whose lookup fails is kept, the rpc may not allow
+/// getProgramAccounts on it.
+pub fn discover_dexes(
+    client: &RpcClient,
+    mint: &Pubkey,
+    base_mint: &Pubkey,
+    candidates: Dex,
+) -> Dex {
+    let mut found = Dex::empty();
+    for layout in POOL_LAYOUTS.iter().filter(|l| candidates.contains(l.dex)) {
+        let (a, b) = layout.mint_offsets;
+        // the mint can sit in either slot
+        for offsets in [(a, b), (b, a)] {
+            match has_pool(client, layout, offsets, mint, base_mint) {
+                Ok(false) => continue,
+                Ok(true) => {}
+                Err(e) => warn!("Failed to look up {} pools for {}: {}", layout.dex, mint, e),
+            }
+            found |= layout.dex;
+            break;
+        }
+    }
+    debug!("{} has {} pools on: {}", mint, base_mint, found);
+    found
+}
+
+fn has_pool(
+    client: &RpcClient,
+    layout: &PoolLayout,
+    (mint_offset, base_offset): (usize, usize),
+    mint: &Pubkey,
+    base_mint: &Pubkey,
+) -> Result<bool> {
+    let mut filters = vec![
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(mint_offset, mint.as_ref())),
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(base_offset, base_mint.as_ref())),
+    ];
+    if let Some(data_size) = layout.data_size {
+        filters.push(RpcFilterType::DataSize(data_size));
+    }
+    let config = RpcProgramAccountsConfig {
+        filters: Some(filters),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            // only the pubkeys matter
+            data_slice: Some(UiDataSliceConfig {
+                offset: 0,
+                length: 0,
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let accounts = client.get_program_accounts_with_config(&layout.program_id, config)?;
+    Ok(!accounts.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dex, DexSelection};
+
+    #[test]
+    fn test_dex_from_str() {
+        let dex: Dex = "Raydium, Meteora DLMM".parse().unwrap();
+        assert_eq!(dex.bits(), (Dex::RAYDIUM | Dex::METEORA_DLMM).bits());
+        assert!("Raydium,Uniswap".parse::<Dex>().is_err());
+        assert!("".parse::<Dex>().is_err());
+    }
+
+    #[test]
+    fn test_dex_display() {
+        assert_eq!(
+            Dex::ALL.to_string(),
+            "Raydium,Meteora DLMM,Whirlpool,Phoenix"
+        );
+        assert_eq!(Dex::METEORA.to_string(), "Meteora");
+        assert_eq!(Dex::empty().to_string(), "");
+        let dex = Dex::RAYDIUM | Dex::PHOENIX;
+        assert_eq!(dex.to_string().parse::<Dex>().unwrap().bits(), dex.bits());
+    }
+
+    #[test]
+    fn test_dex_iter() {
+        let venues: Vec<u8> = Dex::ALL.iter().map(|venue| venue.bits()).collect();
+        assert_eq!(
+            venues,
+            vec![
+                Dex::RAYDIUM.bits(),
+                Dex::METEORA_DLMM.bits(),
+                Dex::WHIRLPOOL.bits(),
+                Dex::PHOENIX.bits()
+            ]
+        );
+        assert!(Dex::ALL.iter().all(|venue| venue.bits().count_ones() == 1));
+        assert_eq!(Dex::empty().iter().count(), 0);
+    }
+
+    #[test]
+    fn test_dex_serde() {
+        for dex in [
+            Dex::empty(),
+            Dex::ALL,
+            Dex::ALL | Dex::METEORA,
+            Dex::WHIRLPOOL,
+        ] {
+            let json = serde_json::to_string(&dex).unwrap();
+            let parsed: Dex = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed.bits(), dex.bits());
+        }
+        assert_eq!(
+            serde_json::to_string(&(Dex::RAYDIUM | Dex::WHIRLPOOL)).unwrap(),
+            r#""Raydium,Whirlpool""#
+        );
+
+        let dex: Dex = serde_json::from_str(r#"["Raydium", "Meteora DLMM"]"#).unwrap();
+        assert_eq!(dex.bits(), (Dex::RAYDIUM | Dex::METEORA_DLMM).bits());
+        let dex: Dex = serde_json::from_str("[]").unwrap();
+        assert!(dex.is_empty());
+        assert!(serde_json::from_str::<Dex>(r#"["Uniswap"]"#).is_err());
+        assert!(serde_json::from_str::<Dex>("3").is_err());
+    }
+
+    #[test]
+    fn test_dex_selection() {
+        let selection =
+            DexSelection::parse("Raydium, SomeNewAmm,Whirlpool,SomeNewAmm", true).unwrap();
+        assert_eq!(
+            selection.dexes.bits(),
+            (Dex::RAYDIUM | Dex::WHIRLPOOL).bits()
+        );
+        assert_eq!(selection.custom, vec!["SomeNewAmm".to_string()]);
+        assert_eq!(selection.to_string(), "Raydium,Whirlpool,SomeNewAmm");
+        assert!(DexSelection::parse("Raydium,SomeNewAmm", false).is_err());
+        assert!(DexSelection::parse(" ,", true).is_err());
 
-        dexes.join(",")
+        // only custom labels, and a known set converted as before
+        let custom: DexSelection = "SomeNewAmm".parse().unwrap();
+        assert!(custom.dexes.is_empty());
+        assert_eq!(custom.to_string(), "SomeNewAmm");
+        assert_eq!(custom.intersect(Dex::ALL).custom, custom.custom);
+        assert_eq!(
+            DexSelection::from(Dex::ALL).to_string(),
+            Dex::ALL.to_string()
+        );
+        let discovered = selection.intersect(Dex::WHIRLPOOL | Dex::PHOENIX);
+        assert_eq!(discovered.to_string(), "Whirlpool,SomeNewAmm");
     }
 }
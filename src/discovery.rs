@@ -0,0 +1,173 @@
+use std::{env, str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use reqwest::Proxy;
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{debug, info, warn};
+
+use crate::watchlist::Watchlist;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiscoverySource {
+    /// Birdeye trending tokens, requires BIRDEYE_API_KEY
+    Birdeye,
+    /// Jupiter token list tagged as trending
+    JupTokens,
+}
+
+impl DiscoverySource {
+    fn default_url(&self) -> &'static str {
+        match self {
+            DiscoverySource::Birdeye => {
+                "https://public-api.birdeye.so/defi/token_trending?sort_by=rank&sort_type=asc&offset=0&limit=20"
+            }
+            DiscoverySource::JupTokens => "https://tokens.jup.ag/tokens?tags=birdeye-trending",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    pub source: DiscoverySource,
+    pub interval: Duration,
+    pub top: usize,
+    // USD
+    pub min_volume_24h: f64,
+    pub denylist: Vec<Pubkey>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub mint: Pubkey,
+    pub volume_24h: f64,
+}
+
+pub async fn fetch_candidates(source: DiscoverySource) -> Result<Vec<Candidate>> {
+    let mut client_builder = reqwest::Client::builder().timeout(Duration::from_secs(10));
+    if let Ok(http_proxy) = env::var("HTTP_PROXY") {
+        let proxy = Proxy::all(http_proxy)?;
+        client_builder = client_builder.proxy(proxy);
+    }
+    let client = client_builder.build()?;
+
+    let url = env::var("DISCOVER_URL").unwrap_or(source.default_url().to_string());
+    let mut request = client.get(&url);
+    if source == DiscoverySource::Birdeye {
+        let api_key = env::var("BIRDEYE_API_KEY").context("BIRDEYE_API_KEY is not set")?;
+        request = request
+            .header("X-API-KEY", api_key)
+            .header("x-chain", "solana");
+    }
+    let body = request
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Value>()
+        .await?;
+    parse_candidates(source, &body)
+}
+
+pub fn parse_candidates(source: DiscoverySource, body: &Value) -> Result<Vec<Candidate>> {
+    let (tokens, address_key, volume_key) = match source {
+        DiscoverySource::Birdeye => (
+            body.pointer("/data/tokens")
+                .and_then(Value::as_array)
+                .context("expected 'data.tokens' to be an array")?,
+            "address",
+            "volume24hUSD",
+        ),
+        DiscoverySource::JupTokens => (
+            body.as_array().context("expected an array of tokens")?,
+            "address",
+            "daily_volume",
+        ),
+    };
+
+    let candidates = tokens
+        .iter()
+        .filter_map(|token| {
+            let mint = Pubkey::from_str(token.get(address_key)?.as_str()?).ok()?;
+            let volume_24h = token.get(volume_key).and_then(Value::as_f64).unwrap_or(0.0);
+            Some(Candidate { mint, volume_24h })
+        })
+        .collect();
+    Ok(candidates)
+}
+
+/// Apply volume/denylist filters and keep the top N by volume
+pub fn select_candidates(mut candidates: Vec<Candidate>, config: &DiscoveryConfig) -> Vec<Pubkey> {
+    let native_mint = spl_token::native_mint::id();
+    candidates.retain(|c| {
+        c.mint != native_mint
+            && c.volume_24h >= config.min_volume_24h
+            && !config.denylist.contains(&c.mint)
+    });
+    candidates.sort_by(|a, b| b.volume_24h.total_cmp(&a.volume_24h));
+    candidates
+        .into_iter()
+        .take(config.top)
+        .map(|c| c.mint)
+        .collect()
+}
+
+/// Periodically refresh the discovered mints. Failures keep the previous
+/// set so trading on existing mints is never interrupted.
+pub async fn run_discovery(watchlist: Arc<Watchlist>, config: DiscoveryConfig) {
+    loop {
+        match fetch_candidates(config.source).await {
+            Ok(candidates) => {
+                debug!(
+                    "discovery: {} candidates from {:?}",
+                    candidates.len(),
+                    config.source
+                );
+                let mints = select_candidates(candidates, &config);
+                info!("🔎 Discovered {} mints: {:?}", mints.len(), mints);
+                watchlist.set_discovered(mints).await;
+            }
+            Err(e) => warn!("discovery: failed to fetch {:?}: {}", config.source, e),
+        }
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::{DiscoveryConfig, DiscoverySource, parse_candidates, select_candidates};
+
+    #[test]
+    fn test_parse_and_select() {
+        let (a, b, c) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let body = json!({
+            "data": {"tokens": [
+                {"address": a.to_string(), "volume24hUSD": 5000.0},
+                {"address": b.to_string(), "volume24hUSD": 90000.0},
+                {"address": c.to_string(), "volume24hUSD": 50000.0},
+                {"address": "not-a-pubkey", "volume24hUSD": 1e9},
+            ]},
+            "success": true
+        });
+        let candidates = parse_candidates(DiscoverySource::Birdeye, &body).unwrap();
+        assert_eq!(candidates.len(), 3);
+
+        let config = DiscoveryConfig {
+            source: DiscoverySource::Birdeye,
+            interval: Duration::from_secs(300),
+            top: 2,
+            min_volume_24h: 10000.0,
+            denylist: vec![c],
+        };
+        assert_eq!(select_candidates(candidates, &config), vec![b]);
+    }
+}
@@ -0,0 +1,1024 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::anyhow;
+use jupiter_swap_api_client::{
+    JupiterSwapApiClient,
+    quote::QuoteResponse,
+    transaction_config::{ComputeUnitPriceMicroLamports, TransactionConfig},
+};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_token_2022::extension::transfer_fee::TransferFee;
+use tokio::time::Instant;
+use tracing::{Instrument, debug, error, info, info_span, warn};
+use uuid::Uuid;
+
+use crate::{
+    arb::{self, InstructionLayout, QuoteOptions},
+    balance::BalanceSource,
+    batch::{BatchConfig, Batcher},
+    breaker,
+    dex::DexSelection,
+    error::{ArbError, TxError},
+    get_rpc_client, history, inflight, jito,
+    latency::LatencyBreakdown,
+    metrics,
+    observer::{ArbObserver, LogObserver, Opportunity},
+    outcome::{ArbOutcome, ArbStatus},
+    payer::PayerPool,
+    price,
+    recheck::{Rechecks, TimedOut},
+    referral::Referral,
+    session, token, trigger,
+    tx::{self, PriorityFeeConfig, SendMode, SendOptions, SendResult},
+};
+
+// compute units a round trip is priced at before it's built, as break-even
+// assumes
+const ESTIMATED_COMPUTE_UNITS: u64 = 300_000;
+
+// resends on a fresh blockhash after the first one expired, past that the
+// opportunity has most likely decayed
+const BLOCKHASH_RETRIES: u32 = 2;
+
+/// How the swap transaction is built
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapBuild {
+    /// `/swap-instructions`, assembled locally with the tip inline
+    Instructions,
+    /// `/swap`, jupiter's transaction with the tip sent as its own
+    Transaction,
+}
+
+/// Everything that stays the same across scans. Per-mint knobs are passed
+/// to `scan` as `ScanParams`.
+#[derive(Clone)]
+pub struct EngineConfig {
+    pub jupiter_api_url: String,
+    pub jupiter_extra_args: Option<HashMap<String, String>>,
+    // the round trip starts and ends in it
+    pub base_mint: Pubkey,
+    pub payers: Vec<Arc<Keypair>>,
+    pub balance_source: BalanceSource,
+    pub balance_ttl: Duration,
+    pub swap_build: SwapBuild,
+    pub quote_options: QuoteOptions,
+    pub referral: Option<Referral>,
+    // wins over ScanParams::min_profit when a sol price is available
+    pub min_profit_usd: Option<f64>,
+    pub report_usd: bool,
+    pub send: SendOptions,
+    pub priority_fee: PriorityFeeConfig,
+    // simulate before sending, a failed simulation aborts the send
+    pub simulate_then_send: bool,
+    // compute unit margin in percent, simulates before sending and sizes
+    // the limit to the units consumed when set
+    pub presimulate: Option<u16>,
+    // SwapBuild::Instructions only
+    pub bundle_split: bool,
+    pub layout: InstructionLayout,
+    pub allow_concurrent_per_mint: bool,
+    // SendMode::Jito only, executions ready within the window share a bundle
+    pub batch_window: Option<Duration>,
+    // picks the tip share by profit, ScanParams::tip_percentage below its
+    // lowest tier or when unset
+    pub tip_schedule: Option<jito::TipSchedule>,
+}
+
+/// Per-mint settings of a scan, amounts in the base mint's smallest unit
+#[derive(Debug, Clone)]
+pub struct ScanParams {
+    pub amount_in: u64,
+    pub min_profit: u64,
+    // share of the profit tipped, 0.0-1.0
+    pub tip_percentage: f64,
+    pub buy_dexes: DexSelection,
+    pub sell_dexes: DexSelection,
+    pub transfer_fee: Option<TransferFee>,
+}
+
+/// What a scan priced, `opportunity` is set once it clears the thresholds
+#[derive(Debug, Clone)]
+pub struct Scan {
+    pub profit: i64,
+    pub profit_usd: Option<f64>,
+    pub opportunity: Option<Opportunity>,
+}
+
+/// The arbitrage loop body as a library: owns the jupiter client and the
+/// payers, rpc clients come from the RPC_ENDPOINTS pool and tip accounts
+/// from the jito cache.
+pub struct ArbEngine {
+    config: EngineConfig,
+    jupiter: JupiterSwapApiClient,
+    payers: Arc<PayerPool>,
+    batcher: Option<Batcher>,
+    observer: Arc<dyn ArbObserver>,
+    rechecks: Rechecks,
+}
+
+impl ArbEngine {
+    /// Loads the jito tip accounts, events are logged until `with_observer`
+    pub async fn new(config: EngineConfig) -> anyhow::Result<Self> {
+        jito::init_tip_accounts().await?;
+        let payers = Arc::new(PayerPool::new(
+            config.payers.clone(),
+            config.balance_source,
+            config.balance_ttl,
+        ));
+        let batcher = config.batch_window.map(|window| {
+            Batcher::spawn(BatchConfig {
+                window,
+                wait_for_confirmation: config.send.wait_for_confirmation,
+                confirmation: config.send.confirmation,
+            })
+        });
+        Ok(Self {
+            jupiter: JupiterSwapApiClient::new(config.jupiter_api_url.clone()),
+            config,
+            payers,
+            batcher,
+            observer: Arc::new(LogObserver),
+            rechecks: Rechecks::default(),
+        })
+    }
+
+    pub fn with_observer(mut self, observer: Arc<dyn ArbObserver>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    pub fn payers(&self) -> &Arc<PayerPool> {
+        &self.payers
+    }
+
+    /// Quote the round trip of `mint` and price it for `owner`, whose
+    /// missing token account counts against the profit
+    pub async fn scan(
+        &self,
+        execution_id: Uuid,
+        mint: Pubkey,
+        params: &ScanParams,
+        owner: &Pubkey,
+        latency: &mut LatencyBreakdown,
+    ) -> Result<Scan, ArbError> {
+        let config = &self.config;
+        let rpc_client = get_rpc_client()?;
+        let (profit, quote_buy_response, quote_sell_response) = quote_round_trip(
+            &self.jupiter,
+            config.jupiter_extra_args.clone(),
+            &config.base_mint,
+            &mint,
+            params,
+            &config.quote_options,
+            latency,
+        )
+        .await?;
+        let rent = self
+            .ata_rent(&rpc_client, owner, &mint, params.amount_in)
+            .await?;
+        // the first trade creates the mint's token account, its rent is a cost
+        let profit = if rent > 0 {
+            info!(
+                "No token account for {} yet, counting {} of rent against profit {}",
+                mint, rent, profit
+            );
+            profit - rent as i64
+        } else {
+            profit
+        };
+        trigger::record_route(&mint, &[&quote_buy_response, &quote_sell_response]);
+        let profit_usd = match config.report_usd || config.min_profit_usd.is_some() {
+            true => {
+                profit_usd(
+                    &self.jupiter,
+                    config.jupiter_extra_args.clone(),
+                    &config.base_mint,
+                    profit,
+                    config.quote_options.timeout,
+                )
+                .await
+            }
+            false => None,
+        };
+
+        // with dynamic slippage only the worst case is guaranteed
+        let guaranteed_profit = match config.quote_options.dynamic_slippage_max_bps {
+            Some(max_bps) => {
+                let worst = arb::worst_case_profit(profit, quote_sell_response.out_amount, max_bps);
+                debug!(
+                    "Profit: {} lamports, worst case with {} bps slippage: {}",
+                    profit, max_bps, worst
+                );
+                worst
+            }
+            None => profit,
+        };
+        // the rpc path pays a priority fee instead of a tip, it has to come
+        // out of the profit. Only a sol base mint is priced in lamports.
+        let priority_fee = match config.send.send_mode == SendMode::Rpc
+            && config.base_mint == spl_token::native_mint::id()
+        {
+            true => {
+                let compute_unit_price = compute_unit_price(
+                    &rpc_client,
+                    config.send.send_mode,
+                    &[&quote_buy_response, &quote_sell_response],
+                    &config.priority_fee,
+                )?;
+                tx::priority_fee(
+                    ESTIMATED_COMPUTE_UNITS,
+                    compute_unit_price.unwrap_or_default(),
+                )
+            }
+            false => 0,
+        };
+        if priority_fee > 0 {
+            debug!(
+                "Profit: {} lamports, {} after the estimated priority fee",
+                guaranteed_profit,
+                guaranteed_profit - priority_fee as i64
+            );
+        }
+        let guaranteed_profit = guaranteed_profit - priority_fee as i64;
+        // with no profit there is nothing to take a tip share of
+        if profit <= 0
+            || !is_profitable(
+                guaranteed_profit,
+                profit_usd,
+                params.min_profit,
+                config.min_profit_usd,
+            )
+        {
+            return Ok(Scan {
+                profit,
+                profit_usd,
+                opportunity: None,
+            });
+        }
+        // in the base mint, jito is tipped its value in lamports
+        let (tip_amount, tip_lamports) = match config.send.send_mode {
+            SendMode::Rpc => (0, 0),
+            SendMode::Jito | SendMode::Both => {
+                let tip_percentage = config
+                    .tip_schedule
+                    .as_ref()
+                    .and_then(|schedule| schedule.tip_percentage(profit as u64))
+                    .unwrap_or(params.tip_percentage);
+                let tip_amount = ((profit as u64) as f64 * tip_percentage.min(1.0)) as u64;
+                arb::clamped_tip(
+                    &self.jupiter,
+                    config.jupiter_extra_args.clone(),
+                    &config.base_mint,
+                    tip_amount,
+                    &jito::TIP_POLICY,
+                    config.quote_options.timeout,
+                )
+                .await?
+            }
+        };
+        // the tip floor and the fee can take more than the profit leaves
+        let network_fee = arb::network_fee(&config.base_mint);
+        if !covers_costs(
+            guaranteed_profit,
+            tip_amount,
+            network_fee,
+            params.min_profit,
+        ) {
+            debug!(
+                "Profit: {}, tip: {}, network fee: {}, below the minimum {} after costs",
+                guaranteed_profit, tip_amount, network_fee, params.min_profit
+            );
+            return Ok(Scan {
+                profit,
+                profit_usd,
+                opportunity: None,
+            });
+        }
+        Ok(Scan {
+            profit,
+            profit_usd,
+            opportunity: Some(Opportunity {
+                execution_id,
+                mint,
+                amount_in: params.amount_in,
+                profit,
+                tip_amount,
+                tip_lamports,
+                rent,
+                quote_buy_response,
+                quote_sell_response,
+            }),
+        })
+    }
+
+    /// Build, check and send the opportunity's transactions with `payer`.
+    /// `min_profit` bounds the slippage allowed on the sell leg.
+    pub async fn execute(
+        &self,
+        opportunity: &Opportunity,
+        min_profit: u64,
+        payer: &Keypair,
+        latency: &mut LatencyBreakdown,
+    ) -> Result<SendResult, ArbError> {
+        let config = &self.config;
+        let rpc_client = get_rpc_client()?;
+        // held until the execution finishes, maintenance tasks wait for it
+        let _execution = inflight::execution().await;
+        let amount_in = opportunity.amount_in;
+        let tip_amount = opportunity.tip_amount;
+        let tip_lamports = opportunity.tip_lamports;
+        let tip_account = jito::get_tip_account().await?;
+        // a batched execution leaves the tip to the shared bundle
+        let batcher = self.batcher.as_ref().filter(|_| !tx::simulate_only());
+
+        // the merged threshold must be reachable or the swap reverts
+        arb::check_sell_threshold(
+            opportunity.quote_sell_response.out_amount,
+            amount_in,
+            tip_amount,
+        )?;
+        let mut quote_response = arb::merge_quotes(
+            opportunity.quote_buy_response.clone(),
+            opportunity.quote_sell_response.clone(),
+            amount_in,
+            tip_amount,
+        )?;
+        if let Some(max_bps) = config.quote_options.max_slippage_bps {
+            let expected_out = amount_in + opportunity.profit as u64;
+            let required = amount_in + tip_amount + min_profit;
+            let slippage_bps = arb::sell_slippage_bps(expected_out, required, max_bps);
+            arb::apply_slippage(&mut quote_response, expected_out, slippage_bps);
+            info!(
+                "Slippage: {} bps, profit: {}, worst case: {}",
+                slippage_bps,
+                opportunity.profit,
+                arb::worst_case_profit(opportunity.profit, expected_out, slippage_bps)
+            );
+        }
+        if let Some(referral) = &config.referral {
+            referral.apply(&mut quote_response);
+        }
+        let fee_account = config
+            .referral
+            .map(|r| r.fee_account(&quote_response.output_mint));
+
+        debug!(
+            "out_amount: {}, other_amount_threshold: {}",
+            quote_response.out_amount, quote_response.other_amount_threshold
+        );
+
+        let compute_unit_price = compute_unit_price(
+            &rpc_client,
+            config.send.send_mode,
+            &[&quote_response],
+            &config.priority_fee,
+        )?;
+
+        let mut tx_config = TransactionConfig::default();
+        tx_config.dynamic_compute_unit_limit = true;
+        tx_config.use_shared_accounts = Some(false);
+        tx_config.wrap_and_unwrap_sol = config.base_mint == spl_token::native_mint::id();
+        tx_config.fee_account = fee_account;
+        tx_config.dynamic_slippage =
+            arb::dynamic_slippage(config.quote_options.dynamic_slippage_max_bps);
+
+        // a guaranteed loss only gets here through broken tip or fee math
+        if let Err(e) = arb::validate_opportunity(opportunity, tip_amount, min_profit) {
+            error!("Refusing to send {}: {}", opportunity.mint, e);
+            metrics::inc_counter("arb_invalid_opportunities_total", &[]);
+            return Err(e.into());
+        }
+
+        let start_swap = Instant::now();
+        let (mut versioned_transactions, separate_tip, tip_spend) = match config.swap_build {
+            SwapBuild::Instructions => {
+                // a batch tips once for the whole bundle, the rpc path not at all
+                let tip_instruction = match (batcher, config.send.send_mode) {
+                    (None, SendMode::Jito | SendMode::Both) => Some(tx::get_tip_instruction(
+                        &payer.pubkey(),
+                        &tip_account,
+                        tip_lamports,
+                    )),
+                    _ => None,
+                };
+                let (instructions, address_lookup_table_addresses) = self
+                    .swap_instructions(
+                        opportunity,
+                        &quote_response,
+                        tip_instruction,
+                        compute_unit_price,
+                        tx_config,
+                        &payer.pubkey(),
+                    )
+                    .await?;
+                latency.swap_instructions = Some(start_swap.elapsed());
+                let versioned_transactions = tx::create_txs_with_address_table_lookup(
+                    &rpc_client,
+                    &instructions,
+                    &address_lookup_table_addresses,
+                    payer,
+                    latency,
+                )?;
+                (versioned_transactions, None, tip_lamports)
+            }
+            SwapBuild::Transaction => {
+                tx_config.compute_unit_price_micro_lamports =
+                    compute_unit_price.map(ComputeUnitPriceMicroLamports::MicroLamports);
+                let versioned_transaction = arb::swap(
+                    &self.jupiter,
+                    config.jupiter_extra_args.clone(),
+                    &payer.pubkey(),
+                    &quote_response,
+                    tx_config,
+                    config.quote_options.swap_timeout,
+                )
+                .await?;
+                latency.swap_instructions = Some(start_swap.elapsed());
+                // the tip goes out as its own tx, dropped on the rpc path
+                let tip_spend = match config.send.send_mode {
+                    SendMode::Rpc => 0,
+                    SendMode::Jito | SendMode::Both => tip_lamports + arb::BASE_FEE_LAMPORTS,
+                };
+                (
+                    vec![versioned_transaction],
+                    (batcher.is_none() && config.send.send_mode != SendMode::Rpc)
+                        .then_some((tip_account, tip_lamports)),
+                    tip_spend,
+                )
+            }
+        };
+        if config.simulate_then_send || config.presimulate.is_some() {
+            tx::presimulate(
+                &rpc_client,
+                payer,
+                &mut versioned_transactions,
+                config.presimulate,
+            )?;
+        }
+        // sol based arbs wrap the input out of the native balance, and the
+        // rent counted against profit is in base units otherwise
+        let (native_in, rent_lamports) = match config.base_mint == spl_token::native_mint::id() {
+            true => (amount_in, opportunity.rent),
+            false => (
+                0,
+                token::ata_rent(&rpc_client, &payer.pubkey(), &opportunity.mint)?,
+            ),
+        };
+        tx::check_headroom(
+            &rpc_client,
+            &payer.pubkey(),
+            &versioned_transactions,
+            native_in + tip_spend + rent_lamports,
+        )?;
+
+        if let Some(batcher) = batcher {
+            let Some(tip_payer) = config.payers.iter().find(|p| p.pubkey() == payer.pubkey())
+            else {
+                return Err(
+                    TxError::Other(anyhow!("{} is not an engine payer", payer.pubkey())).into(),
+                );
+            };
+            let signed = tx::sign_bundle(payer, versioned_transactions, None)?;
+            let sent = batcher
+                .send(signed, tip_lamports, tip_payer.clone())
+                .await?;
+            if let Some(bundle_id) = &sent.bundle_id {
+                self.observer.on_submitted(opportunity, bundle_id);
+            }
+            return Ok(sent);
+        }
+
+        let mut bundle_id = None;
+        let mut retries = 0;
+        let mut sent = loop {
+            let result = tx::send_versioned_transaction(
+                &rpc_client,
+                payer,
+                versioned_transactions.clone(),
+                separate_tip,
+                &config.send,
+                |id| {
+                    bundle_id = Some(id.to_string());
+                    self.observer.on_submitted(opportunity, id);
+                },
+                latency,
+            )
+            .await;
+            match result {
+                Err(e) if e.is_blockhash_expired() && retries < BLOCKHASH_RETRIES => {
+                    retries += 1;
+                    warn!(
+                        "Blockhash expired before {} landed, resending on a fresh one ({}/{})",
+                        opportunity.mint, retries, BLOCKHASH_RETRIES
+                    );
+                    metrics::inc_counter("arb_blockhash_retries_total", &[]);
+                    tx::refresh_blockhash(&rpc_client, &mut versioned_transactions, latency)?;
+                }
+                result => break result?,
+            }
+        };
+        sent.bundle_id = bundle_id;
+        Ok(sent)
+    }
+
+    // rent of the mint's token account in base units, 0 once it exists
+    async fn ata_rent(
+        &self,
+        rpc_client: &RpcClient,
+        owner: &Pubkey,
+        mint: &Pubkey,
+        amount_in: u64,
+    ) -> Result<u64, ArbError> {
+        let config = &self.config;
+        let rent = token::ata_rent(rpc_client, owner, mint)?;
+        if rent == 0 || config.base_mint == spl_token::native_mint::id() {
+            return Ok(rent);
+        }
+        // valued at the price of the trade size
+        let amount_in_lamports = arb::to_lamports(
+            &self.jupiter,
+            config.jupiter_extra_args.clone(),
+            &config.base_mint,
+            amount_in,
+            config.quote_options.timeout,
+        )
+        .await?;
+        Ok((rent as u128 * amount_in as u128 / amount_in_lamports.max(1) as u128) as u64)
+    }
+
+    // instructions of the merged swap, or with --bundle-split of the buy and
+    // sell legs as two txs of one bundle, and the lookup tables they need
+    async fn swap_instructions(
+        &self,
+        opportunity: &Opportunity,
+        quote_response: &QuoteResponse,
+        tip_instruction: Option<Instruction>,
+        compute_unit_price: Option<u64>,
+        tx_config: TransactionConfig,
+        payer: &Pubkey,
+    ) -> Result<(Vec<Vec<Instruction>>, Vec<Pubkey>), ArbError> {
+        let config = &self.config;
+        let layout = config.layout;
+        if !config.bundle_split {
+            let swap_instructions_response = arb::swap_instructions(
+                &self.jupiter,
+                config.jupiter_extra_args.clone(),
+                payer,
+                quote_response,
+                tx_config,
+                config.quote_options.swap_timeout,
+            )
+            .await?;
+            let address_lookup_table_addresses = swap_instructions_response
+                .address_lookup_table_addresses
+                .clone();
+            return Ok((
+                vec![arb::build_instructions(
+                    swap_instructions_response,
+                    tip_instruction,
+                    compute_unit_price,
+                    layout,
+                )],
+                address_lookup_table_addresses,
+            ));
+        }
+
+        // the tip rides on the sell
+        let mut quote_sell_response = opportunity.quote_sell_response.clone();
+        arb::guard_sell_leg(&mut quote_sell_response, quote_response);
+        let (buy_instructions, sell_instructions) = tokio::try_join!(
+            arb::swap_instructions(
+                &self.jupiter,
+                config.jupiter_extra_args.clone(),
+                payer,
+                &opportunity.quote_buy_response,
+                // the referral fee is charged on the sell output
+                TransactionConfig {
+                    fee_account: None,
+                    ..tx_config.clone()
+                },
+                config.quote_options.swap_timeout,
+            ),
+            arb::swap_instructions(
+                &self.jupiter,
+                config.jupiter_extra_args.clone(),
+                payer,
+                &quote_sell_response,
+                tx_config,
+                config.quote_options.swap_timeout,
+            ),
+        )?;
+
+        let mut address_lookup_table_addresses =
+            buy_instructions.address_lookup_table_addresses.clone();
+        for key in &sell_instructions.address_lookup_table_addresses {
+            if !address_lookup_table_addresses.contains(key) {
+                address_lookup_table_addresses.push(*key);
+            }
+        }
+        Ok((
+            vec![
+                arb::build_instructions(buy_instructions, None, compute_unit_price, layout),
+                arb::build_instructions(
+                    sell_instructions,
+                    tip_instruction,
+                    compute_unit_price,
+                    layout,
+                ),
+            ],
+            address_lookup_table_addresses,
+        ))
+    }
+
+    /// Scan `mint` and execute what clears the thresholds with `payer`. The
+    /// outcome is recorded to the trade history and the payer pool.
+    pub async fn run(&self, mint: Pubkey, params: ScanParams, payer: &Keypair) -> ArbOutcome {
+        let execution_id = Uuid::new_v4();
+        // every log of the execution, down to arb and tx, carries these fields
+        let span = info_span!("arb", execution_id = %execution_id, mint = %mint);
+        async move {
+            let outcome = self.run_scan(execution_id, mint, &params, payer).await;
+            self.payers.record(&payer.pubkey(), &outcome);
+            outcome
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn run_scan(
+        &self,
+        execution_id: Uuid,
+        mint: Pubkey,
+        params: &ScanParams,
+        payer: &Keypair,
+    ) -> ArbOutcome {
+        let mut outcome = ArbOutcome::new(execution_id, mint, params.amount_in);
+        outcome.send_mode = Some(self.config.send.send_mode);
+        let start_time = Instant::now();
+        // confirmations that timed out on an earlier iteration
+        self.rechecks.check().await;
+        let scan = match self
+            .scan(
+                execution_id,
+                mint,
+                params,
+                &payer.pubkey(),
+                &mut outcome.latency,
+            )
+            .await
+        {
+            Ok(scan) => scan,
+            Err(e) => {
+                if e.is_benign() {
+                    debug!("Error calculating profit: {}", e);
+                } else {
+                    info!("Error calculating profit: {}", e);
+                }
+                record_error(&e);
+                outcome.status = ArbStatus::QuoteFailed {
+                    error: e.to_string(),
+                };
+                return finish_outcome(outcome);
+            }
+        };
+        outcome.profit = Some(scan.profit);
+        outcome.profit_usd = scan.profit_usd;
+        let Some(opportunity) = scan.opportunity else {
+            self.observer.on_skipped(&execution_id, &mint, scan.profit);
+            return finish_outcome(outcome);
+        };
+        outcome.tip_lamports = Some(opportunity.tip_lamports);
+        outcome.tip_amount = Some(opportunity.tip_amount);
+        outcome.buy_route = Some(arb::route_summary_full(&opportunity.quote_buy_response));
+        outcome.sell_route = Some(arb::route_summary_full(&opportunity.quote_sell_response));
+        if !self.observer.on_opportunity(&opportunity) {
+            outcome.status = ArbStatus::Vetoed;
+            return finish_outcome(outcome);
+        }
+        // another task quoted the same mint and got to execute first
+        let _mint_guard = match self.config.allow_concurrent_per_mint {
+            true => None,
+            false => match inflight::try_execute_mint(mint, payer.pubkey()) {
+                Some(guard) => Some(guard),
+                None => {
+                    debug!("{} is already executing, skipping", mint);
+                    return finish_outcome(outcome);
+                }
+            },
+        };
+
+        let result = self
+            .execute(&opportunity, params.min_profit, payer, &mut outcome.latency)
+            .await;
+        info!(
+            "🕒 Timings({:?}): {}",
+            start_time.elapsed(),
+            outcome.latency
+        );
+
+        outcome.status = match result {
+            Ok(sent) => {
+                outcome.submit_slot = sent.submit_slot;
+                outcome.landed_slot = sent.landed_slot;
+                // only a landed send is confirmed, without a submission
+                // nothing was sent and TX_SIMULATE only simulated it
+                match (sent.landed_slot, sent.bundle_id) {
+                    (Some(_), bundle_id) => {
+                        self.observer.on_confirmed(&opportunity, &sent.signatures);
+                        ArbStatus::Confirmed {
+                            bundle_id,
+                            signatures: sent.signatures,
+                        }
+                    }
+                    (None, Some(bundle_id)) => ArbStatus::Submitted { bundle_id },
+                    (None, None) => ArbStatus::Simulated,
+                }
+            }
+            Err(e) => {
+                let timed_out = match &e {
+                    ArbError::Tx(err) => TimedOut::of(err),
+                    _ => None,
+                };
+                if let Some(sent) = &timed_out {
+                    self.rechecks.push(execution_id, mint, sent.clone());
+                }
+                match timed_out {
+                    Some(sent) if !self.config.send.confirmation.fail_fast => {
+                        info!(
+                            "Confirmation of {} timed out, checking it again later",
+                            sent
+                        );
+                        ArbStatus::Submitted {
+                            bundle_id: sent.id(),
+                        }
+                    }
+                    _ => {
+                        record_error(&e);
+                        self.observer.on_failed(&opportunity, &e);
+                        ArbStatus::Failed {
+                            error: e.to_string(),
+                        }
+                    }
+                }
+            }
+        };
+        finish_outcome(outcome)
+    }
+}
+
+/// Priority fee for the rpc send path, a jito bundle pays the tip instead.
+/// In both mode the shared transaction has to compete on the rpc path too.
+fn compute_unit_price(
+    rpc_client: &RpcClient,
+    send_mode: SendMode,
+    quotes: &[&QuoteResponse],
+    priority_fee: &PriorityFeeConfig,
+) -> Result<Option<u64>, TxError> {
+    match send_mode {
+        SendMode::Jito => Ok(None),
+        SendMode::Rpc | SendMode::Both => {
+            let pools: Vec<Pubkey> = quotes
+                .iter()
+                .flat_map(|quote| &quote.route_plan)
+                .map(|step| step.swap_info.amm_key)
+                .collect();
+            tx::estimate_compute_unit_price(rpc_client, &pools, priority_fee).map(Some)
+        }
+    }
+}
+
+async fn profit_usd(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    jupiter_extra_args: Option<HashMap<String, String>>,
+    base_mint: &Pubkey,
+    profit: i64,
+    timeout: Option<Duration>,
+) -> Option<f64> {
+    let profit = match *base_mint == spl_token::native_mint::id() {
+        true => profit,
+        // a loss can't be quoted, --min-profit decides those
+        false if profit <= 0 => return None,
+        false => match arb::to_lamports(
+            jupiter_swap_api_client,
+            jupiter_extra_args.clone(),
+            base_mint,
+            profit as u64,
+            timeout,
+        )
+        .await
+        {
+            Ok(lamports) => lamports as i64,
+            Err(e) => {
+                warn!("Failed to convert profit to lamports: {}", e);
+                return None;
+            }
+        },
+    };
+    match price::get_sol_price(jupiter_swap_api_client, jupiter_extra_args).await {
+        Ok(sol_price) => {
+            let profit_usd = price::lamports_to_usd(profit, sol_price);
+            debug!("Profit: {:.4} usd", profit_usd);
+            Some(profit_usd)
+        }
+        Err(e) => {
+            warn!("Failed to get SOL price: {}", e);
+            None
+        }
+    }
+}
+
+// profit and quotes of the round trip. Cached quotes only screen the mint,
+// a profitable one is quoted again past the cache so an execution never
+// commits to a stale quote.
+async fn quote_round_trip(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    jupiter_extra_args: Option<HashMap<String, String>>,
+    base_mint: &Pubkey,
+    mint: &Pubkey,
+    params: &ScanParams,
+    quote_options: &QuoteOptions,
+    latency: &mut LatencyBreakdown,
+) -> Result<(i64, QuoteResponse, QuoteResponse), ArbError> {
+    let round_trip = arb::RoundTrip {
+        base_mint,
+        mint,
+        amount_in: params.amount_in,
+        buy_dexes: &params.buy_dexes,
+        sell_dexes: &params.sell_dexes,
+        transfer_fee: params.transfer_fee.as_ref(),
+    };
+    let quoted = arb::caculate_profit(
+        jupiter_swap_api_client,
+        jupiter_extra_args.clone(),
+        &round_trip,
+        quote_options,
+        latency,
+    )
+    .await?;
+    if quote_options.cache_ttl.is_none() || quoted.0 <= 0 {
+        return Ok(quoted);
+    }
+    arb::caculate_profit(
+        jupiter_swap_api_client,
+        jupiter_extra_args,
+        &round_trip,
+        &quote_options.uncached(),
+        latency,
+    )
+    .await
+}
+
+// the usd threshold wins when set, without a price fall back to --min-profit
+fn is_profitable(
+    profit: i64,
+    profit_usd: Option<f64>,
+    min_profit_lamports: u64,
+    min_profit_usd: Option<f64>,
+) -> bool {
+    match (min_profit_usd, profit_usd) {
+        (Some(min_profit_usd), Some(profit_usd)) => profit_usd >= min_profit_usd,
+        _ => profit >= min_profit_lamports as i64,
+    }
+}
+
+// what is left of the guaranteed profit once the tip and the network fee are
+// paid still has to reach min_profit
+fn covers_costs(
+    guaranteed_profit: i64,
+    tip_amount: u64,
+    network_fee: u64,
+    min_profit: u64,
+) -> bool {
+    guaranteed_profit as i128 - tip_amount as i128 - network_fee as i128 >= min_profit as i128
+}
+
+// benign errors are expected market conditions, only provider or
+// execution failures are counted
+fn record_error(err: &ArbError) {
+    if !err.is_benign() {
+        metrics::inc_counter("arb_errors_total", &[("kind", err.kind())]);
+    }
+    breaker::record_error(err);
+}
+
+fn finish_outcome(outcome: ArbOutcome) -> ArbOutcome {
+    outcome.latency.observe();
+    if !matches!(
+        outcome.status,
+        ArbStatus::QuoteFailed { .. } | ArbStatus::Failed { .. }
+    ) {
+        breaker::record_success();
+    }
+    // tells jupiter latency apart from rpc latency
+    if outcome.latency.is_slow() {
+        debug!(
+            "slow scan, {:?}: {}",
+            outcome.latency.total(),
+            outcome.latency
+        );
+    }
+    session::record(&outcome);
+    if let Err(e) = history::record(&outcome) {
+        warn!("Failed to record trade history: {}", e);
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use jupiter_swap_api_client::JupiterSwapApiClient;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::{ScanParams, covers_costs, is_profitable, quote_round_trip};
+    use crate::{
+        arb::QuoteOptions,
+        dex::Dex,
+        latency::LatencyBreakdown,
+        testing::{MockJupiter, quote_fixture},
+    };
+
+    #[test]
+    fn test_is_profitable() {
+        assert!(is_profitable(1_000, None, 1_000, None));
+        assert!(!is_profitable(999, None, 1_000, None));
+        // the usd threshold wins once there is a price
+        assert!(is_profitable(10, Some(0.5), 1_000, Some(0.4)));
+        assert!(!is_profitable(5_000, Some(0.3), 1_000, Some(0.4)));
+        assert!(is_profitable(5_000, None, 1_000, Some(0.4)));
+    }
+
+    #[test]
+    fn test_covers_costs() {
+        // clears min profit 10_000 but not the 20_000 tip and 5_000 fee on top
+        assert!(is_profitable(30_000, None, 10_000, None));
+        assert!(!covers_costs(30_000, 20_000, 5_000, 10_000));
+        assert!(!covers_costs(34_999, 20_000, 5_000, 10_000));
+        assert!(covers_costs(35_000, 20_000, 5_000, 10_000));
+        // a non-sol base pays no network fee out of the profit
+        assert!(covers_costs(30_000, 20_000, 0, 10_000));
+        assert!(!covers_costs(-1, 0, 0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_execution_quotes_skip_cache() {
+        let sol = spl_token::native_mint::id();
+        let mint = Pubkey::new_unique();
+        let buy = serde_json::to_string(&quote_fixture(&sol, &mint, 1_000, 2_000, 1)).unwrap();
+        let sell = serde_json::to_string(&quote_fixture(&mint, &sol, 2_000, 1_100, 1)).unwrap();
+        let mock = MockJupiter::start(move |request| {
+            match request.query.contains(&format!("inputMint={}", sol)) {
+                true => buy.clone(),
+                false => sell.clone(),
+            }
+        })
+        .await;
+        let jupiter = JupiterSwapApiClient::new(mock.url.clone());
+        let params = ScanParams {
+            amount_in: 1_000,
+            min_profit: 0,
+            tip_percentage: 0.5,
+            buy_dexes: Dex::ALL.into(),
+            sell_dexes: Dex::ALL.into(),
+            transfer_fee: None,
+        };
+        let quote_options = QuoteOptions {
+            cache_ttl: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        };
+        let mut latency = LatencyBreakdown::default();
+        // a profitable scan is quoted twice, the second time past the cache
+        let (profit, ..) = quote_round_trip(
+            &jupiter,
+            None,
+            &sol,
+            &mint,
+            &params,
+            &quote_options,
+            &mut latency,
+        )
+        .await
+        .unwrap();
+        assert_eq!(profit, 100);
+        assert_eq!(mock.requests_to("/quote").len(), 4);
+        // the cache answers the screening quotes, never the executed ones
+        quote_round_trip(
+            &jupiter,
+            None,
+            &sol,
+            &mint,
+            &params,
+            &quote_options,
+            &mut latency,
+        )
+        .await
+        .unwrap();
+        assert_eq!(mock.requests_to("/quote").len(), 6);
+    }
+}
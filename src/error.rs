@@ -0,0 +1,219 @@
+use jupiter_swap_api_client::ClientError as JupiterClientError;
+use solana_client::client_error::ClientError as RpcClientError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ArbError {
+    #[error("no route found: {0}")]
+    NoRoute(String),
+    #[error("quote request failed with status {status}: {body}")]
+    QuoteHttp { status: u16, body: String },
+    #[error("quote request failed: {0}")]
+    QuoteTransport(String),
+    #[error("jupiter {call} timed out after {timeout_ms}ms")]
+    JupiterTimeout { call: &'static str, timeout_ms: u64 },
+    #[error("jupiter {call} would wait over {max_wait_ms}ms for the rate limit")]
+    RateLimited {
+        call: &'static str,
+        max_wait_ms: u64,
+    },
+    #[error("{leg} leg price impact {impact_pct:.2}% too high")]
+    HighImpact { leg: &'static str, impact_pct: f64 },
+    #[error("stale quote: {0}")]
+    StaleQuote(String),
+    #[error("invalid leg path: {0}")]
+    InvalidPath(String),
+    #[error("sell leg returns {expected_out}, below the {required} the merged quote requires")]
+    ThresholdUnmet { expected_out: u64, required: u64 },
+    #[error(transparent)]
+    Invalid(#[from] ValidationError),
+    #[error(transparent)]
+    Tx(#[from] TxError),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ArbError {
+    /// Short stable name, used as a metrics label
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ArbError::NoRoute(_) => "no_route",
+            ArbError::QuoteHttp { .. } => "quote_http",
+            ArbError::QuoteTransport(_) => "quote_transport",
+            ArbError::JupiterTimeout { .. } => "jupiter_timeout",
+            ArbError::RateLimited { .. } => "rate_limited",
+            ArbError::HighImpact { .. } => "high_impact",
+            ArbError::StaleQuote(_) => "stale_quote",
+            ArbError::InvalidPath(_) => "invalid_path",
+            ArbError::ThresholdUnmet { .. } => "threshold_unmet",
+            ArbError::Invalid(_) => "invalid_opportunity",
+            ArbError::Tx(err) => err.kind(),
+            ArbError::Other(_) => "other",
+        }
+    }
+
+    /// Benign errors are expected market conditions (no route, stale quote)
+    /// and say nothing about provider health, so they shouldn't count
+    /// toward the circuit breaker or blacklist.
+    pub fn is_benign(&self) -> bool {
+        match self {
+            ArbError::NoRoute(_)
+            | ArbError::HighImpact { .. }
+            | ArbError::StaleQuote(_)
+            | ArbError::ThresholdUnmet { .. }
+            // our own limit, jupiter never saw the call
+            | ArbError::RateLimited { .. } => true,
+            // 429 and 5xx are the provider having a bad time
+            ArbError::QuoteHttp { status, .. } => *status != 429 && *status < 500,
+            ArbError::Tx(err) => err.is_benign(),
+            _ => false,
+        }
+    }
+}
+
+impl ArbError {
+    /// A failed jupiter response, told apart from a missing route by its body
+    pub fn from_quote_response(status: u16, body: String) -> Self {
+        if body.contains("COULD_NOT_FIND_ANY_ROUTE")
+            || body.contains("NO_ROUTES_FOUND")
+            || body.contains("TOKEN_NOT_TRADABLE")
+        {
+            ArbError::NoRoute(body)
+        } else {
+            ArbError::QuoteHttp { status, body }
+        }
+    }
+}
+
+impl From<JupiterClientError> for ArbError {
+    fn from(err: JupiterClientError) -> Self {
+        match err {
+            JupiterClientError::RequestFailed { status, body } => {
+                ArbError::from_quote_response(status.as_u16(), body)
+            }
+            JupiterClientError::DeserializationError(err) => {
+                ArbError::QuoteTransport(err.to_string())
+            }
+        }
+    }
+}
+
+/// An opportunity whose sell leg can't pay for itself. Only broken quote,
+/// tip or fee math gets one past the profit threshold.
+#[derive(Debug, PartialEq, Eq, Error)]
+#[error(
+    "sell leg returns {expected_out}, below the {required} needed for principal {amount_in}, \
+     tip {tip}, network fee {network_fee} and min profit {min_profit}"
+)]
+pub struct ValidationError {
+    pub expected_out: u64,
+    pub required: u64,
+    pub amount_in: u64,
+    pub tip: u64,
+    pub network_fee: u64,
+    pub min_profit: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum TxError {
+    #[error("rpc error: {0}")]
+    Rpc(Box<RpcClientError>),
+    #[error("simulation failed: {0}")]
+    SimulationFailed(String),
+    #[error("insufficient balance: required {required} lamports, available {available}")]
+    InsufficientBalance { required: u64, available: u64 },
+    #[error("failed to compile transaction: {0}")]
+    CompileFailed(String),
+    #[error("bundle rejected: {reason}")]
+    BundleRejected { reason: String },
+    #[error("bundle {bundle_id} confirmation timeout")]
+    ConfirmationTimeout { bundle_id: String },
+    #[error("no rpc endpoint accepted the transaction: {0}")]
+    BroadcastFailed(String),
+    #[error("lookup tables not found: {0}")]
+    MissingLookupTables(String),
+    #[error("transaction {signature} confirmation timeout")]
+    SignatureTimeout { signature: String },
+    #[error("transaction failed: {0}")]
+    TransactionFailed(String),
+    #[error("failed to parse bundle status: {0}")]
+    InvalidBundleStatus(#[from] serde_json::Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl TxError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TxError::Rpc(_) => "rpc",
+            TxError::SimulationFailed(_) => "simulation_failed",
+            TxError::InsufficientBalance { .. } => "insufficient_balance",
+            TxError::CompileFailed(_) => "compile_failed",
+            TxError::BundleRejected { .. } => "bundle_rejected",
+            TxError::ConfirmationTimeout { .. } => "confirmation_timeout",
+            TxError::BroadcastFailed(_) => "broadcast_failed",
+            TxError::MissingLookupTables(_) => "missing_lookup_tables",
+            TxError::SignatureTimeout { .. } => "signature_timeout",
+            TxError::TransactionFailed(_) => "transaction_failed",
+            TxError::InvalidBundleStatus(_) => "invalid_bundle_status",
+            TxError::Other(_) => "other",
+        }
+    }
+
+    pub fn is_benign(&self) -> bool {
+        // a failed simulation means the opportunity was gone by the time we built the tx
+        matches!(self, TxError::SimulationFailed(_))
+    }
+
+    /// The transaction's blockhash was no longer valid when it was
+    /// submitted, it can go out again on a fresh one
+    pub fn is_blockhash_expired(&self) -> bool {
+        let message = match self {
+            TxError::Rpc(err) => err.to_string(),
+            TxError::BroadcastFailed(message) => message.clone(),
+            TxError::BundleRejected { reason } => reason.clone(),
+            _ => return false,
+        };
+        let message = message.to_lowercase();
+        message.contains("blockhashnotfound")
+            || message.contains("blockhash not found")
+            || message.contains("expired blockhash")
+            || message.contains("blockhash expired")
+    }
+}
+
+impl From<RpcClientError> for TxError {
+    fn from(err: RpcClientError) -> Self {
+        TxError::Rpc(Box::new(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_client::client_error::{ClientError, ClientErrorKind};
+
+    use super::TxError;
+
+    #[test]
+    fn test_is_blockhash_expired() {
+        let err = TxError::BroadcastFailed(
+            "http://a: RPC response error -32002: Transaction simulation failed: \
+             Blockhash not found"
+                .to_string(),
+        );
+        assert!(err.is_blockhash_expired());
+        let err = TxError::BundleRejected {
+            reason: "bundle contains an expired blockhash".to_string(),
+        };
+        assert!(err.is_blockhash_expired());
+        let err = TxError::from(ClientError::from(ClientErrorKind::Custom(
+            "BlockhashNotFound".to_string(),
+        )));
+        assert!(err.is_blockhash_expired());
+
+        let err = TxError::BroadcastFailed("http://a: insufficient funds".to_string());
+        assert!(!err.is_blockhash_expired());
+        let err = TxError::SimulationFailed("Blockhash not found".to_string());
+        assert!(!err.is_blockhash_expired());
+    }
+}
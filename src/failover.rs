@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use rand::seq::SliceRandom;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    rpc_client::RpcClient,
+};
+use tracing::warn;
+
+use crate::get_rpc_endpoints;
+
+// how long an endpoint that failed at the transport level is passed over
+const COOLDOWN: Duration = Duration::from_secs(30);
+
+// endpoint url -> end of its cooldown
+static COOLDOWNS: LazyLock<Mutex<HashMap<String, Instant>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Timeouts, refused connections and 5xx responses say the endpoint is
+/// unwell, rpc and transaction errors say nothing about it
+pub fn is_transport_error(err: &ClientError) -> bool {
+    match err.kind() {
+        ClientErrorKind::Io(_) => true,
+        ClientErrorKind::Reqwest(e) => {
+            e.is_timeout() || e.is_connect() || e.status().is_some_and(|s| s.is_server_error())
+        }
+        _ => false,
+    }
+}
+
+pub fn mark_cooldown(url: &str) {
+    COOLDOWNS
+        .lock()
+        .unwrap()
+        .insert(url.to_string(), Instant::now() + COOLDOWN);
+}
+
+pub fn is_cooling_down(url: &str) -> bool {
+    COOLDOWNS
+        .lock()
+        .unwrap()
+        .get(url)
+        .is_some_and(|until| *until > Instant::now())
+}
+
+// a random endpoint other than `failed` that isn't cooling down
+fn pick_fallback(
+    endpoints: &[String],
+    failed: &str,
+    cooling_down: impl Fn(&str) -> bool,
+) -> Option<String> {
+    let candidates: Vec<&String> = endpoints
+        .iter()
+        .filter(|url| url.as_str() != failed && !cooling_down(url.as_str()))
+        .collect();
+    candidates
+        .choose(&mut rand::thread_rng())
+        .map(|url| url.to_string())
+}
+
+/// Run `call` against `client`. On a transport error the endpoint is put on
+/// cooldown and the call is retried once against another endpoint, with the
+/// same commitment so the data stays comparable.
+pub fn with_failover<T>(
+    client: &RpcClient,
+    call: impl Fn(&RpcClient) -> ClientResult<T>,
+) -> ClientResult<T> {
+    let err = match call(client) {
+        Ok(value) => return Ok(value),
+        Err(e) if is_transport_error(&e) => e,
+        Err(e) => return Err(e),
+    };
+    let url = client.url();
+    mark_cooldown(&url);
+    let endpoints = get_rpc_endpoints().unwrap_or_default();
+    let Some(fallback) = pick_fallback(&endpoints, &url, is_cooling_down) else {
+        return Err(err);
+    };
+    warn!("rpc {} failed: {}, retrying on {}", url, err, fallback);
+    call(&RpcClient::new_with_commitment(
+        fallback,
+        client.commitment(),
+    ))
+}
+
+/// A client on a random endpoint other than `client`'s, for data a lagging
+/// node may not have yet
+pub fn other_client(client: &RpcClient) -> Option<RpcClient> {
+    let endpoints = get_rpc_endpoints().unwrap_or_default();
+    let fallback = pick_fallback(&endpoints, &client.url(), is_cooling_down)?;
+    Some(RpcClient::new_with_commitment(
+        fallback,
+        client.commitment(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use solana_client::client_error::{ClientError, ClientErrorKind};
+
+    use super::{is_transport_error, pick_fallback};
+
+    #[test]
+    fn test_is_transport_error() {
+        let err = ClientError::from(io::Error::new(io::ErrorKind::ConnectionRefused, "refused"));
+        assert!(is_transport_error(&err));
+        let err = ClientError::from(ClientErrorKind::Custom("blockhash not found".to_string()));
+        assert!(!is_transport_error(&err));
+    }
+
+    #[test]
+    fn test_pick_fallback() {
+        let endpoints = vec![
+            "http://a".to_string(),
+            "http://b".to_string(),
+            "http://c".to_string(),
+        ];
+        assert_eq!(
+            pick_fallback(&endpoints, "http://a", |url| url == "http://b"),
+            Some("http://c".to_string())
+        );
+        assert_eq!(
+            pick_fallback(&endpoints, "http://a", |url| url != "http://a"),
+            None
+        );
+        assert_eq!(pick_fallback(&endpoints[..1], "http://a", |_| false), None);
+    }
+}
@@ -0,0 +1,204 @@
+use std::{collections::HashMap, env, time::Duration};
+
+use jupiter_swap_api_client::JupiterSwapApiClient;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::signer::Signer;
+use spl_token::amount_to_ui_amount;
+use tokio::time::{Instant, timeout};
+
+use crate::{
+    arb::{QuoteOptions, jupiter_call},
+    dex::Dex,
+    get_payer, get_random_rpc_url, get_rpc_endpoints,
+    jito::api::{TipAccountResult, get_tip_accounts},
+    price::USDC_MINT,
+};
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+// an endpoint this many slots behind the best one is flagged
+const MAX_SLOT_LAG: u64 = 50;
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: String,
+    pub ok: bool,
+    // a failed critical check makes the whole health check fail
+    pub critical: bool,
+    pub latency: Duration,
+    pub detail: String,
+}
+
+/// Run every setup check, each bounded by a timeout
+pub async fn run_checks(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    jupiter_extra_args: Option<HashMap<String, String>>,
+) -> Vec<CheckResult> {
+    let mut results = check_rpc_endpoints().await;
+    results.push(check_payer().await);
+    results.push(check_quote(jupiter_swap_api_client, jupiter_extra_args).await);
+    results.push(check_jito().await);
+    results
+}
+
+/// Probe every RPC_ENDPOINTS endpoint, also run at startup
+pub async fn check_rpc_endpoints() -> Vec<CheckResult> {
+    let endpoints = match get_rpc_endpoints() {
+        Ok(endpoints) => endpoints,
+        Err(e) => {
+            return vec![CheckResult {
+                name: "rpc".to_string(),
+                ok: false,
+                critical: true,
+                latency: Duration::ZERO,
+                detail: format!("RPC_ENDPOINTS: {}", e),
+            }];
+        }
+    };
+
+    let mut probes = vec![];
+    for endpoint in endpoints {
+        let start = Instant::now();
+        let url = endpoint.clone();
+        let probe = tokio::task::spawn_blocking(move || {
+            let client = RpcClient::new_with_timeout(url, CHECK_TIMEOUT);
+            client.get_health()?;
+            client.get_slot()
+        })
+        .await;
+        let result = match probe {
+            Ok(Ok(slot)) => Ok(slot),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        probes.push((endpoint, result, start.elapsed()));
+    }
+
+    let best_slot = probes
+        .iter()
+        .filter_map(|(_, result, _)| result.as_ref().ok())
+        .max()
+        .copied()
+        .unwrap_or_default();
+    probes
+        .into_iter()
+        .map(|(endpoint, result, latency)| {
+            let (ok, detail) = match result {
+                Ok(slot) => {
+                    let lag = best_slot.saturating_sub(slot);
+                    (lag <= MAX_SLOT_LAG, format!("slot {} (lag {})", slot, lag))
+                }
+                Err(e) => (false, e),
+            };
+            CheckResult {
+                name: format!("rpc {}", endpoint),
+                ok,
+                critical: true,
+                latency,
+                detail,
+            }
+        })
+        .collect()
+}
+
+async fn check_payer() -> CheckResult {
+    let start = Instant::now();
+    let (ok, detail) = match get_payer() {
+        Ok(payer) => {
+            let pubkey = payer.pubkey();
+            let balance = tokio::task::spawn_blocking(move || {
+                let client = RpcClient::new_with_timeout(get_random_rpc_url()?, CHECK_TIMEOUT);
+                Ok::<_, anyhow::Error>(client.get_balance(&pubkey)?)
+            })
+            .await;
+            match balance {
+                Ok(Ok(balance)) => (
+                    true,
+                    format!("{} balance {} sol", pubkey, amount_to_ui_amount(balance, 9)),
+                ),
+                Ok(Err(e)) => (false, format!("{}: {}", pubkey, e)),
+                Err(e) => (false, e.to_string()),
+            }
+        }
+        Err(e) => (false, format!("PRIVATE_KEY: {}", e)),
+    };
+    CheckResult {
+        name: "payer".to_string(),
+        ok,
+        critical: true,
+        latency: start.elapsed(),
+        detail,
+    }
+}
+
+async fn check_quote(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    jupiter_extra_args: Option<HashMap<String, String>>,
+) -> CheckResult {
+    let start = Instant::now();
+    let quote_options = QuoteOptions {
+        slippage_bps: 50,
+        timeout: Some(CHECK_TIMEOUT),
+        ..Default::default()
+    };
+    // 0.01 SOL -> USDC
+    let quote_request = quote_options.build_request(
+        &spl_token::native_mint::id(),
+        &USDC_MINT,
+        10_000_000,
+        &Dex::ALL.into(),
+        jupiter_extra_args,
+    );
+    let result = jupiter_call(
+        "quote",
+        quote_options.timeout,
+        jupiter_swap_api_client.quote(&quote_request),
+    )
+    .await;
+    let (ok, detail) = match result {
+        Ok(quote_response) => (
+            true,
+            format!(
+                "0.01 SOL -> {} USDC",
+                amount_to_ui_amount(quote_response.out_amount, 6)
+            ),
+        ),
+        Err(e) => (false, e.to_string()),
+    };
+    CheckResult {
+        name: "jupiter quote".to_string(),
+        ok,
+        critical: true,
+        latency: start.elapsed(),
+        detail,
+    }
+}
+
+async fn check_jito() -> CheckResult {
+    let start = Instant::now();
+    if env::var("JITO_BLOCK_ENGINE_URL").is_err() {
+        return CheckResult {
+            name: "jito block engine".to_string(),
+            ok: false,
+            critical: false,
+            latency: Duration::ZERO,
+            detail: "JITO_BLOCK_ENGINE_URL is not set".to_string(),
+        };
+    }
+    let result = timeout(CHECK_TIMEOUT, get_tip_accounts()).await;
+    let (ok, detail) = match result {
+        Ok(Ok(response)) => match TipAccountResult::try_from(response) {
+            Ok(accounts) => (true, format!("{} tip accounts", accounts.accounts.len())),
+            Err(e) => (false, e.to_string()),
+        },
+        Ok(Err(e)) => (false, e.to_string()),
+        Err(_) => (false, format!("timed out after {:?}", CHECK_TIMEOUT)),
+    };
+    CheckResult {
+        name: "jito block engine".to_string(),
+        ok,
+        // only needed for --send-mode jito
+        critical: false,
+        latency: start.elapsed(),
+        detail,
+    }
+}
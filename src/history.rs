@@ -0,0 +1,28 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::LazyLock,
+};
+
+use anyhow::Result;
+use serde::Serialize;
+
+pub static HISTORY_PATH: LazyLock<String> =
+    LazyLock::new(|| env::var("TRADE_HISTORY_PATH").unwrap_or("logs/trades.jsonl".to_string()));
+
+/// Append a record to the JSONL trade history
+pub fn record<T: Serialize>(record: &T) -> Result<()> {
+    append_jsonl(Path::new(HISTORY_PATH.as_str()), record)
+}
+
+pub fn append_jsonl<T: Serialize>(path: &Path, record: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let line = serde_json::to_string(record)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::{
+    collections::HashSet,
+    sync::{LazyLock, Mutex},
+};
+
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+// shared by every arbitrage execution, taken exclusively by maintenance
+// tasks (e.g. auto wrap) so they never race an in-flight bundle for the
+// same balance or blockhash
+static EXECUTION_LOCK: RwLock<()> = RwLock::const_new(());
+
+/// Held from the moment an opportunity is executed until the task finishes
+pub async fn execution() -> RwLockReadGuard<'static, ()> {
+    EXECUTION_LOCK.read().await
+}
+
+/// Waits for in-flight executions to finish and holds new ones off
+/// until the guard is dropped
+pub async fn maintenance() -> RwLockWriteGuard<'static, ()> {
+    EXECUTION_LOCK.write().await
+}
+
+/// Like `maintenance` but gives up instead of waiting when an execution
+/// is in flight
+pub fn try_maintenance() -> Option<RwLockWriteGuard<'static, ()>> {
+    EXECUTION_LOCK.try_write().ok()
+}
+
+// (mint, wallet) pairs with an execution in flight
+static EXECUTING_MINTS: LazyLock<Mutex<HashSet<(Pubkey, Pubkey)>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Marks a mint as executing for a wallet until dropped
+#[derive(Debug)]
+pub struct MintGuard {
+    key: (Pubkey, Pubkey),
+}
+
+impl Drop for MintGuard {
+    fn drop(&mut self) {
+        EXECUTING_MINTS.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// `None` when the wallet already has an execution for the mint in flight
+pub fn try_execute_mint(mint: Pubkey, wallet: Pubkey) -> Option<MintGuard> {
+    let key = (mint, wallet);
+    EXECUTING_MINTS
+        .lock()
+        .unwrap()
+        .insert(key)
+        .then_some(MintGuard { key })
+}
+
+pub fn is_mint_executing(mint: &Pubkey, wallet: &Pubkey) -> bool {
+    EXECUTING_MINTS.lock().unwrap().contains(&(*mint, *wallet))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::{is_mint_executing, try_execute_mint};
+
+    #[test]
+    fn test_mint_guard() {
+        let mint = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        let guard = try_execute_mint(mint, wallet).unwrap();
+        assert!(is_mint_executing(&mint, &wallet));
+        assert!(try_execute_mint(mint, wallet).is_none());
+        // other mints and wallets are unaffected
+        assert!(try_execute_mint(Pubkey::new_unique(), wallet).is_some());
+        assert!(try_execute_mint(mint, Pubkey::new_unique()).is_some());
+
+        drop(guard);
+        assert!(!is_mint_executing(&mint, &wallet));
+        assert!(try_execute_mint(mint, wallet).is_some());
+    }
+}
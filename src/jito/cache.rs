@@ -0,0 +1,83 @@
+use std::{env, fs, path::Path, sync::LazyLock, time::Duration};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::outcome::now_millis;
+
+pub static TIP_ACCOUNTS_CACHE_PATH: LazyLock<String> = LazyLock::new(|| {
+    env::var("JITO_TIP_ACCOUNTS_CACHE_PATH").unwrap_or("logs/tip_accounts.json".to_string())
+});
+pub static TIP_ACCOUNTS_CACHE_TTL: LazyLock<Duration> = LazyLock::new(|| {
+    let secs = env::var("JITO_TIP_ACCOUNTS_CACHE_TTL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(86400);
+    Duration::from_secs(secs)
+});
+
+/// Tip accounts as last fetched from the block engine
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TipAccountsCache {
+    // unix timestamp in milliseconds
+    pub timestamp: u64,
+    pub accounts: Vec<String>,
+}
+
+impl TipAccountsCache {
+    pub fn new(accounts: Vec<String>) -> Self {
+        Self {
+            timestamp: now_millis(),
+            accounts,
+        }
+    }
+
+    pub fn is_fresh(&self, ttl: Duration) -> bool {
+        now_millis().saturating_sub(self.timestamp) < ttl.as_millis() as u64
+    }
+}
+
+/// None when the file is missing or unreadable
+pub fn read(path: &Path) -> Option<TipAccountsCache> {
+    let data = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&data)
+        .ok()
+        .filter(|cache: &TipAccountsCache| !cache.accounts.is_empty())
+}
+
+pub fn write(path: &Path, cache: &TipAccountsCache) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // write then rename so a crash never leaves a truncated cache
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec(cache)?)?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{TipAccountsCache, read, write};
+
+    #[test]
+    fn test_tip_accounts_cache() {
+        let path = std::env::temp_dir().join(format!("tip_accounts_{}.json", uuid::Uuid::new_v4()));
+        assert!(read(&path).is_none());
+
+        let cache = TipAccountsCache::new(vec!["a".to_string(), "b".to_string()]);
+        write(&path, &cache).unwrap();
+        let loaded = read(&path).unwrap();
+        assert_eq!(loaded.accounts, cache.accounts);
+        assert!(loaded.is_fresh(Duration::from_secs(60)));
+
+        let stale = TipAccountsCache {
+            timestamp: 0,
+            ..loaded
+        };
+        assert!(!stale.is_fresh(Duration::from_secs(60)));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,9 +1,20 @@
-use std::{env, future::Future, str::FromStr, sync::LazyLock, time::Duration};
+use std::{
+    env, fmt,
+    future::Future,
+    path::Path,
+    str::FromStr,
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
 
 use anyhow::{Result, anyhow};
 use api::{TipAccountResult, get_tip_accounts};
+use cache::TipAccountsCache;
 use indicatif::{ProgressBar, ProgressStyle};
-use rand::{seq::IteratorRandom, thread_rng};
+use jito_json_rpc_client::jsonrpc_client::rpc_client::RpcClient as JitoRpcClient;
 use serde::Deserialize;
 use serde_json::Value;
 use solana_sdk::pubkey::Pubkey;
@@ -13,7 +24,10 @@ use tokio::{
 };
 use tracing::{debug, error, info, warn};
 
+use crate::error::TxError;
+
 pub mod api;
+pub mod cache;
 pub mod ws;
 
 pub static TIPS_PERCENTILE: LazyLock<RwLock<Option<TipPercentileData>>> =
@@ -41,28 +55,200 @@ pub static TIP_PERCENTILE: LazyLock<String> = LazyLock::new(|| {
     env::var("JITO_TIP_PERCENTILE").expect("Environment variable JITO_TIP_PERCENTILE is not set")
 });
 
+// every tip is clamped into these bounds, validated by `TipPolicy::from_env`
+// at startup
+pub static TIP_POLICY: LazyLock<TipPolicy> =
+    LazyLock::new(|| TipPolicy::from_env().expect("invalid TIP_MAX_SOL or TIP_MIN_LAMPORTS"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TipPolicy {
+    pub max_lamports: u64,
+    pub min_lamports: u64,
+}
+
+impl TipPolicy {
+    pub fn new(max_sol: f64, min_lamports: u64) -> Result<Self> {
+        if !(max_sol > 0.0 && max_sol <= 1.0) {
+            return Err(anyhow!("tip cap must be in (0, 1] SOL, got {}", max_sol));
+        }
+        let max_lamports = (max_sol * 1e9) as u64;
+        if min_lamports > max_lamports {
+            return Err(anyhow!(
+                "tip floor {} lamports exceeds the cap {} lamports",
+                min_lamports,
+                max_lamports
+            ));
+        }
+        Ok(Self {
+            max_lamports,
+            min_lamports,
+        })
+    }
+
+    /// TIP_MAX_SOL defaults to 0.1, TIP_MIN_LAMPORTS to jito's 1000 minimum
+    pub fn from_env() -> Result<Self> {
+        let max_sol = match env::var("TIP_MAX_SOL") {
+            Ok(value) => f64::from_str(&value).map_err(|e| anyhow!("TIP_MAX_SOL: {}", e))?,
+            Err(_) => 0.1,
+        };
+        let min_lamports = match env::var("TIP_MIN_LAMPORTS") {
+            Ok(value) => u64::from_str(&value).map_err(|e| anyhow!("TIP_MIN_LAMPORTS: {}", e))?,
+            Err(_) => 1000,
+        };
+        Self::new(max_sol, min_lamports)
+    }
+
+    pub fn clamp(&self, tip_lamports: u64) -> u64 {
+        let clamped = tip_lamports.clamp(self.min_lamports, self.max_lamports);
+        if clamped != tip_lamports {
+            warn!(
+                "jito: tip {} lamports clamped to {} (floor: {}, cap: {})",
+                tip_lamports, clamped, self.min_lamports, self.max_lamports
+            );
+        }
+        clamped
+    }
+}
+
+/// Tip shares by profit tier, the highest tier a profit reaches picks its
+/// share. Thresholds are in the base mint's smallest unit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TipSchedule {
+    // ascending by min_profit
+    tiers: Vec<(u64, f64)>,
+}
+
+impl TipSchedule {
+    /// Parses "min_profit:tip_percentage" tiers separated by commas, e.g.
+    /// "0.001:0.3,0.01:0.5,0.1:0.7", min_profit as a ui amount of a mint
+    /// with `decimals`
+    pub fn parse(s: &str, decimals: u8) -> Result<Self> {
+        let mut tiers: Vec<(u64, f64)> = vec![];
+        for tier in s.split(',').map(str::trim).filter(|tier| !tier.is_empty()) {
+            let (min_profit, share) = tier
+                .split_once(':')
+                .ok_or_else(|| anyhow!("tip tier {:?} is not min_profit:tip_percentage", tier))?;
+            let min_profit = f64::from_str(min_profit.trim())
+                .map_err(|e| anyhow!("tip tier {:?}: {}", tier, e))?;
+            let share =
+                f64::from_str(share.trim()).map_err(|e| anyhow!("tip tier {:?}: {}", tier, e))?;
+            if !(min_profit >= 0.0 && min_profit.is_finite()) {
+                return Err(anyhow!("tip tier {:?}: min_profit must be >= 0", tier));
+            }
+            if !(0.0..=1.0).contains(&share) {
+                return Err(anyhow!(
+                    "tip tier {:?}: tip_percentage must be in [0, 1]",
+                    tier
+                ));
+            }
+            let min_profit = (min_profit * 10f64.powi(decimals as i32)) as u64;
+            if tiers.last().is_some_and(|(last, _)| min_profit <= *last) {
+                return Err(anyhow!(
+                    "tip tier {:?}: thresholds must be strictly ascending",
+                    tier
+                ));
+            }
+            tiers.push((min_profit, share));
+        }
+        if tiers.is_empty() {
+            return Err(anyhow!("tip schedule has no tier"));
+        }
+        Ok(Self { tiers })
+    }
+
+    /// The share tipped of `profit`, None below the lowest tier
+    pub fn tip_percentage(&self, profit: u64) -> Option<f64> {
+        self.tiers
+            .iter()
+            .rev()
+            .find(|(min_profit, _)| profit >= *min_profit)
+            .map(|(_, share)| *share)
+    }
+}
+
 pub static TIP_ACCOUNTS: LazyLock<RwLock<Vec<String>>> = LazyLock::new(|| RwLock::new(vec![]));
 
+/// Load tip accounts from the disk cache when fresh and refresh it in the
+/// background, otherwise fetch them live. A stale cache is only used when
+/// the block engine can't be reached.
 pub async fn init_tip_accounts() -> Result<()> {
+    let path = Path::new(cache::TIP_ACCOUNTS_CACHE_PATH.as_str());
+    let cached = cache::read(path);
+    let ttl = *cache::TIP_ACCOUNTS_CACHE_TTL;
+    if let Some(fresh) = cached.as_ref().filter(|cached| cached.is_fresh(ttl)) {
+        debug!(
+            "jito: {} tip accounts from {:?}",
+            fresh.accounts.len(),
+            path
+        );
+        set_tip_accounts(fresh.accounts.clone()).await;
+        tokio::spawn(async move {
+            if let Err(e) = refresh_tip_accounts(path).await {
+                warn!("jito: failed to refresh tip accounts: {}", e);
+            }
+        });
+        return Ok(());
+    }
+    match refresh_tip_accounts(path).await {
+        Ok(()) => Ok(()),
+        Err(e) => match cached {
+            Some(cached) => {
+                warn!(
+                    "jito: failed to fetch tip accounts, using stale cache: {}",
+                    e
+                );
+                set_tip_accounts(cached.accounts).await;
+                Ok(())
+            }
+            None => Err(e),
+        },
+    }
+}
+
+async fn refresh_tip_accounts(path: &Path) -> Result<()> {
     let accounts: TipAccountResult = get_tip_accounts().await?.try_into()?;
-    let mut tip_accounts = TIP_ACCOUNTS.write().await;
+    if let Err(e) = cache::write(path, &TipAccountsCache::new(accounts.accounts.clone())) {
+        warn!("jito: failed to write tip accounts cache {:?}: {}", path, e);
+    }
+    set_tip_accounts(accounts.accounts).await;
+    Ok(())
+}
+
+async fn set_tip_accounts(accounts: Vec<String>) {
+    *TIP_ACCOUNTS.write().await = accounts;
+}
+
+// debug: pin every tip to a single account
+pub static FIXED_TIP_ACCOUNT: LazyLock<Option<String>> =
+    LazyLock::new(|| env::var("JITO_TIP_ACCOUNT").ok().filter(|s| !s.is_empty()));
+static TIP_ACCOUNT_CURSOR: AtomicUsize = AtomicUsize::new(0);
 
+pub async fn all_tip_accounts() -> Result<Vec<Pubkey>> {
+    let accounts = TIP_ACCOUNTS.read().await;
     accounts
-        .accounts
         .iter()
-        .for_each(|account| tip_accounts.push(account.to_string()));
-    Ok(())
+        .map(|acc| {
+            Pubkey::from_str(acc).map_err(|err| anyhow!("jito: failed to parse Pubkey: {:?}", err))
+        })
+        .collect()
 }
 
+/// Round-robin over the tip accounts so consecutive submissions don't
+/// write-lock the same account
 pub async fn get_tip_account() -> Result<Pubkey> {
+    if let Some(account) = FIXED_TIP_ACCOUNT.as_ref() {
+        return Ok(Pubkey::from_str(account).inspect_err(|err| {
+            error!("jito: failed to parse JITO_TIP_ACCOUNT: {:?}", err);
+        })?);
+    }
     let accounts = TIP_ACCOUNTS.read().await;
-    let mut rng = thread_rng();
-    match accounts.iter().choose(&mut rng) {
-        Some(acc) => Ok(Pubkey::from_str(acc).inspect_err(|err| {
-            error!("jito: failed to parse Pubkey: {:?}", err);
-        })?),
-        None => Err(anyhow!("jito: no tip accounts available")),
+    if accounts.is_empty() {
+        return Err(anyhow!("jito: no tip accounts available"));
     }
+    let index = TIP_ACCOUNT_CURSOR.fetch_add(1, Ordering::Relaxed) % accounts.len();
+    Ok(Pubkey::from_str(&accounts[index]).inspect_err(|err| {
+        error!("jito: failed to parse Pubkey: {:?}", err);
+    })?)
 }
 
 pub async fn init_tip_amounts() -> Result<()> {
@@ -101,7 +287,7 @@ pub async fn get_tip_value() -> Result<f64> {
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct BundleStatus {
     pub bundle_id: String,
     pub transactions: Vec<String>,
@@ -109,10 +295,88 @@ pub struct BundleStatus {
     pub confirmation_status: String,
     pub err: ErrorStatus,
 }
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct ErrorStatus {
     #[serde(rename = "Ok")]
     pub ok: Option<()>,
+    // transaction error of a landed but failed bundle
+    #[serde(rename = "Err", default)]
+    pub err: Option<Value>,
+}
+
+impl BundleStatus {
+    pub fn is_confirmed(&self) -> bool {
+        matches!(self.confirmation_status.as_str(), "finalized" | "confirmed")
+    }
+}
+
+impl fmt::Display for BundleStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "bundle: {}", self.bundle_id)?;
+        writeln!(f, "slot: {}", self.slot)?;
+        writeln!(f, "status: {}", self.confirmation_status)?;
+        if let Some(err) = &self.err.err {
+            writeln!(f, "error: {}", err)?;
+        }
+        for tx in &self.transactions {
+            writeln!(f, "https://solscan.io/tx/{}", tx)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn new_jito_client() -> Arc<JitoRpcClient> {
+    Arc::new(JitoRpcClient::new(format!(
+        "{}/api/v1/bundles",
+        BLOCK_ENGINE_URL.to_string()
+    )))
+}
+
+/// Current status of a bundle, `None` until it has landed
+pub async fn get_bundle_status(
+    client: &JitoRpcClient,
+    bundle_id: &str,
+) -> Result<Option<BundleStatus>, TxError> {
+    let statuses = client
+        .get_bundle_statuses(&[bundle_id.to_string()])
+        .await
+        .map_err(|e| anyhow!("failed to get bundle statuses: {}", e))?;
+    match statuses.value.first() {
+        Some(status) => Ok(Some(serde_json::from_value(status.clone())?)),
+        None => Ok(None),
+    }
+}
+
+/// Query a bundle once, or with `watch` poll every `interval` until it is
+/// confirmed or `timeout` passes
+pub async fn bundle_status(
+    bundle_id: &str,
+    watch: bool,
+    interval: Duration,
+    timeout: Duration,
+) -> Result<Option<BundleStatus>> {
+    let client = new_jito_client();
+    if !watch {
+        return Ok(get_bundle_status(&client, bundle_id).await?);
+    }
+    let status = wait_for_bundle_status(
+        move |id: String| {
+            let client = Arc::clone(&client);
+            async move {
+                let response = client.get_bundle_statuses(&[id]).await;
+                let statuses = response.inspect_err(|err| {
+                    error!("Error fetching bundle status: {:?}", err);
+                })?;
+                Ok(statuses.value)
+            }
+        },
+        bundle_id.to_string(),
+        interval,
+        timeout,
+        true,
+    )
+    .await?;
+    Ok(Some(status))
 }
 
 pub async fn wait_for_bundle_confirmation<F, Fut>(
@@ -121,7 +385,24 @@ pub async fn wait_for_bundle_confirmation<F, Fut>(
     interval: Duration,
     timeout: Duration,
     show_progress: bool,
-) -> Result<Vec<String>>
+) -> Result<Vec<String>, TxError>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<Vec<Value>>>,
+{
+    let bundle_status =
+        wait_for_bundle_status(fetch_statuses, bundle_id, interval, timeout, show_progress).await?;
+    Ok(bundle_status.transactions)
+}
+
+/// Poll until the bundle is confirmed, returns its full status
+pub async fn wait_for_bundle_status<F, Fut>(
+    fetch_statuses: F,
+    bundle_id: String,
+    interval: Duration,
+    timeout: Duration,
+    show_progress: bool,
+) -> Result<BundleStatus, TxError>
 where
     F: Fn(String) -> Fut,
     Fut: Future<Output = Result<Vec<Value>>>,
@@ -146,8 +427,8 @@ where
                 })?;
 
             debug!("{:?}", bundle_status);
-            match bundle_status.confirmation_status.as_str() {
-                "finalized" | "confirmed" => {
+            match bundle_status.is_confirmed() {
+                true => {
                     progress_bar.as_ref().map(|pb| {
                         pb.finish_and_clear();
                     });
@@ -160,9 +441,9 @@ where
                         .transactions
                         .iter()
                         .for_each(|tx| info!("https://solscan.io/tx/{}", tx));
-                    return Ok(bundle_status.transactions);
+                    return Ok(bundle_status);
                 }
-                _ => {
+                false => {
                     progress_bar.as_ref().map(|pb| {
                         pb.set_message(format!(
                             "Finalizing bundle {}: {}",
@@ -183,7 +464,7 @@ where
                 "Bundle {} confirmation timeout after {:?}",
                 bundle_id, timeout
             );
-            return Err(anyhow!("Bundle {} confirmation timeout", bundle_id,));
+            return Err(TxError::ConfirmationTimeout { bundle_id });
         }
 
         // Wait for a certain duration before retrying
@@ -207,7 +488,39 @@ mod tests {
 
     use serde_json::{Value, json};
 
-    use super::wait_for_bundle_confirmation;
+    use super::{TipPolicy, TipSchedule, wait_for_bundle_confirmation};
+
+    #[test]
+    fn test_tip_policy() {
+        let policy = TipPolicy::new(0.1, 1000).unwrap();
+        assert_eq!(policy.max_lamports, 100_000_000);
+        assert_eq!(policy.clamp(500), 1000);
+        assert_eq!(policy.clamp(50_000), 50_000);
+        assert_eq!(policy.clamp(2_500_000_000), 100_000_000);
+
+        assert!(TipPolicy::new(-0.1, 0).is_err());
+        assert!(TipPolicy::new(1.5, 0).is_err());
+        assert!(TipPolicy::new(0.000001, 10_000).is_err());
+    }
+
+    #[test]
+    fn test_tip_schedule() {
+        let schedule = TipSchedule::parse("0.001:0.3, 0.01:0.5,0.1:0.7", 9).unwrap();
+        assert_eq!(schedule.tip_percentage(999_999), None);
+        assert_eq!(schedule.tip_percentage(1_000_000), Some(0.3));
+        assert_eq!(schedule.tip_percentage(50_000_000), Some(0.5));
+        assert_eq!(schedule.tip_percentage(2_000_000_000), Some(0.7));
+        // usdc profits, 6 decimals
+        let schedule = TipSchedule::parse("1:0.4", 6).unwrap();
+        assert_eq!(schedule.tip_percentage(1_000_000), Some(0.4));
+
+        assert!(TipSchedule::parse("", 9).is_err());
+        assert!(TipSchedule::parse("0.01", 9).is_err());
+        assert!(TipSchedule::parse("0.01:1.5", 9).is_err());
+        assert!(TipSchedule::parse("-1:0.5", 9).is_err());
+        assert!(TipSchedule::parse("0.1:0.5,0.01:0.7", 9).is_err());
+        assert!(TipSchedule::parse("0.1:0.5,0.1:0.7", 9).is_err());
+    }
 
     fn generate_statuses(bundle_id: String, confirmation_status: &str) -> Vec<Value> {
         vec![json!({
@@ -0,0 +1,109 @@
+use std::{env, fmt, sync::LazyLock, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::metrics;
+
+// executions slower than this log their breakdown at debug
+pub static SLOW_SCAN_THRESHOLD: LazyLock<Duration> = LazyLock::new(|| {
+    let ms = env::var("SLOW_SCAN_MS")
+        .ok()
+        .and_then(|ms| ms.parse().ok())
+        .unwrap_or(1000);
+    Duration::from_millis(ms)
+});
+
+/// Per-stage timings of a single arbitrage execution
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LatencyBreakdown {
+    #[serde(default, with = "duration_ms")]
+    pub buy_quote: Option<Duration>,
+    #[serde(default, with = "duration_ms")]
+    pub sell_quote: Option<Duration>,
+    #[serde(default, with = "duration_ms")]
+    pub swap_instructions: Option<Duration>,
+    #[serde(default, with = "duration_ms")]
+    pub alt_fetch: Option<Duration>,
+    #[serde(default, with = "duration_ms")]
+    pub blockhash: Option<Duration>,
+    // message compile against the lookup tables
+    #[serde(default, with = "duration_ms")]
+    pub compile: Option<Duration>,
+    #[serde(default, with = "duration_ms")]
+    pub sign: Option<Duration>,
+    #[serde(default, with = "duration_ms")]
+    pub bundle_submit: Option<Duration>,
+    #[serde(default, with = "duration_ms")]
+    pub confirmation: Option<Duration>,
+}
+
+impl LatencyBreakdown {
+    fn stages(&self) -> [(&'static str, Option<Duration>); 9] {
+        [
+            ("buy_quote", self.buy_quote),
+            ("sell_quote", self.sell_quote),
+            ("swap_instructions", self.swap_instructions),
+            ("alt_fetch", self.alt_fetch),
+            ("blockhash", self.blockhash),
+            ("compile", self.compile),
+            ("sign", self.sign),
+            ("bundle_submit", self.bundle_submit),
+            ("confirmation", self.confirmation),
+        ]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.stages().iter().filter_map(|(_, d)| *d).sum()
+    }
+
+    /// Export the measured stages and their total to the metrics registry
+    pub fn observe(&self) {
+        for (stage, duration) in self.stages() {
+            if let Some(duration) = duration {
+                metrics::observe(
+                    "arb_stage_latency_seconds",
+                    &[("stage", stage)],
+                    duration.as_secs_f64(),
+                );
+            }
+        }
+        metrics::observe(
+            "arb_execution_latency_seconds",
+            &[],
+            self.total().as_secs_f64(),
+        );
+    }
+
+    pub fn is_slow(&self) -> bool {
+        self.total() >= *SLOW_SCAN_THRESHOLD
+    }
+}
+
+impl fmt::Display for LatencyBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stages = self
+            .stages()
+            .iter()
+            .filter_map(|(stage, d)| d.map(|d| format!("{}={:?}", stage, d)))
+            .collect::<Vec<_>>();
+        write!(f, "{}", stages.join(", "))
+    }
+}
+
+mod duration_ms {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(d) => s.serialize_some(&(d.as_secs_f64() * 1000.0)),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+        let ms: Option<f64> = Option::deserialize(d)?;
+        Ok(ms.map(|ms| Duration::from_secs_f64(ms / 1000.0)))
+    }
+}
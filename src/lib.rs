@@ -1,27 +1,215 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use rand::seq::SliceRandom;
+use reqwest::Url;
 use solana_client::{self, rpc_client::RpcClient};
-use solana_sdk::signature::Keypair;
-use std::{env, sync::Arc};
-use tracing::debug;
+use solana_sdk::signature::{Keypair, read_keypair_file};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::{debug, info, warn};
 
+pub mod alt;
+pub mod amount;
 pub mod arb;
+pub mod backtest;
+pub mod balance;
+pub mod batch;
+pub mod breaker;
+pub mod decay;
 pub mod dex;
+pub mod discovery;
+pub mod engine;
+pub mod error;
+pub mod failover;
+pub mod health;
+pub mod history;
+pub mod inflight;
 pub mod jito;
+pub mod latency;
 pub mod logger;
+pub mod metrics;
+pub mod notify;
+pub mod observer;
+pub mod outcome;
+pub mod output;
+pub mod payer;
+pub mod price;
+pub mod program_error;
+pub mod ratelimit;
+pub mod recheck;
+pub mod referral;
+pub mod session;
+pub mod state;
+pub mod stats;
+pub mod sweep;
+#[cfg(test)]
+mod testing;
 pub mod token;
+pub mod trigger;
 pub mod tx;
+pub mod watchlist;
+pub mod wsol;
 
-pub fn get_random_rpc_url() -> Result<String> {
-    let cluster_urls = env::var("RPC_ENDPOINTS")?
+// how often RPC_ENDPOINTS_FILE is checked for changes
+const ENDPOINTS_FILE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The rpc selection pool. RPC_ENDPOINTS is parsed once, RPC_ENDPOINTS_FILE
+/// is re-read whenever its mtime changes.
+struct EndpointPool {
+    file: Option<PathBuf>,
+    // a bad entry fails every caller with the same message
+    endpoints: Result<Vec<String>, String>,
+    modified: Option<SystemTime>,
+    checked: Instant,
+}
+
+impl EndpointPool {
+    fn from_env() -> Self {
+        let file = env::var("RPC_ENDPOINTS_FILE")
+            .ok()
+            .filter(|path| !path.trim().is_empty())
+            .map(PathBuf::from);
+        // the inline list is only read without a file, reload fills it in
+        let endpoints = match file {
+            Some(_) => Err("RPC_ENDPOINTS_FILE is not loaded".to_string()),
+            None => env::var("RPC_ENDPOINTS")
+                .map_err(|e| format!("RPC_ENDPOINTS: {}", e))
+                .and_then(|value| parse_rpc_endpoints(&value).map_err(|e| e.to_string())),
+        };
+        let mut pool = Self {
+            file,
+            endpoints,
+            modified: None,
+            checked: Instant::now(),
+        };
+        pool.reload();
+        pool
+    }
+
+    fn refresh(&mut self) {
+        let Some(path) = &self.file else {
+            return;
+        };
+        if self.checked.elapsed() < ENDPOINTS_FILE_CHECK_INTERVAL {
+            return;
+        }
+        self.checked = Instant::now();
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        if modified.is_some() && modified != self.modified {
+            self.reload();
+        }
+    }
+
+    // a bad edit keeps the previous list so the bot stays up
+    fn reload(&mut self) {
+        let Some(path) = &self.file else {
+            return;
+        };
+        self.modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        let loaded = fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| parse_rpc_endpoints_file(&content))
+            .map_err(|e| format!("RPC_ENDPOINTS_FILE {}: {}", path.display(), e));
+        match loaded {
+            Ok(endpoints) => {
+                if self.endpoints.as_ref().ok() != Some(&endpoints) {
+                    info!(
+                        "Loaded {} rpc endpoints from {}",
+                        endpoints.len(),
+                        path.display()
+                    );
+                }
+                self.endpoints = Ok(endpoints);
+            }
+            Err(e) => match &self.endpoints {
+                Ok(previous) => warn!("{}, keeping the {} loaded endpoints", e, previous.len()),
+                Err(_) => self.endpoints = Err(e),
+            },
+        }
+    }
+}
+
+static RPC_ENDPOINTS: LazyLock<Mutex<EndpointPool>> =
+    LazyLock::new(|| Mutex::new(EndpointPool::from_env()));
+
+// an http(s) url with a host, errors name the entry as `what`
+fn parse_rpc_endpoint(what: &str, entry: &str) -> Result<String> {
+    if entry.is_empty() {
+        return Err(anyhow!("{} is empty", what));
+    }
+    let url = Url::parse(entry).map_err(|e| anyhow!("{} ({}): {}", what, entry, e))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(anyhow!("{} ({}) is not an http(s) url", what, entry));
+    }
+    Ok(entry.to_string())
+}
+
+/// Comma-separated http(s) urls, an empty or malformed entry is an error
+/// naming it and its index
+pub fn parse_rpc_endpoints(value: &str) -> Result<Vec<String>> {
+    value
         .split(",")
-        .map(|s| s.trim().to_string())
-        .collect::<Vec<String>>();
+        .map(str::trim)
+        .enumerate()
+        .map(|(index, entry)| parse_rpc_endpoint(&format!("RPC_ENDPOINTS entry {}", index), entry))
+        .collect()
+}
 
-    let random_url = cluster_urls
-        .choose(&mut rand::thread_rng())
-        .expect("No RPC endpoints configured")
-        .clone();
+/// One http(s) url per line, blank lines and `#` comments are skipped. A
+/// malformed line is an error naming its line number.
+pub fn parse_rpc_endpoints_file(content: &str) -> Result<Vec<String>> {
+    let endpoints = content
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(index, line)| parse_rpc_endpoint(&format!("line {}", index + 1), line))
+        .collect::<Result<Vec<String>>>()?;
+    if endpoints.is_empty() {
+        return Err(anyhow!("no endpoints"));
+    }
+    Ok(endpoints)
+}
+
+/// Replace the pool with `url` alone for the rest of the run, RPC_ENDPOINTS
+/// and RPC_ENDPOINTS_FILE are ignored from here on
+pub fn pin_rpc_endpoint(url: &str) -> Result<()> {
+    let url = parse_rpc_endpoint("--rpc-url", url.trim())?;
+    info!("Pinned rpc endpoint: {}", url);
+    *RPC_ENDPOINTS.lock().unwrap() = EndpointPool {
+        file: None,
+        endpoints: Ok(vec![url]),
+        modified: None,
+        checked: Instant::now(),
+    };
+    Ok(())
+}
+
+/// The current pool, RPC_ENDPOINTS_FILE changes are picked up within a few
+/// seconds
+pub fn get_rpc_endpoints() -> Result<Vec<String>> {
+    let mut pool = RPC_ENDPOINTS.lock().unwrap();
+    pool.refresh();
+    pool.endpoints.clone().map_err(|e| anyhow!(e))
+}
+
+pub fn get_random_rpc_url() -> Result<String> {
+    let endpoints = get_rpc_endpoints()?;
+    // endpoints on cooldown only when every one of them is
+    let healthy: Vec<&String> = endpoints
+        .iter()
+        .filter(|url| !failover::is_cooling_down(url))
+        .collect();
+    let random_url = match healthy.choose(&mut rand::thread_rng()) {
+        Some(url) => url.to_string(),
+        None => endpoints
+            .choose(&mut rand::thread_rng())
+            .ok_or_else(|| anyhow!("RPC_ENDPOINTS is empty"))?
+            .clone(),
+    };
     debug!("Choose rpc: {}", random_url);
     return Ok(random_url);
 }
@@ -37,11 +225,64 @@ pub fn get_payer() -> Result<Arc<Keypair>> {
     return Ok(Arc::new(wallet));
 }
 
+/// PRIVATE_KEYS: comma-separated base58 keys or keypair files, falls back
+/// to the single PRIVATE_KEY
+pub fn get_payers() -> Result<Vec<Arc<Keypair>>> {
+    let keys = match env::var("PRIVATE_KEYS") {
+        Ok(keys) if !keys.trim().is_empty() => keys,
+        _ => return Ok(vec![get_payer()?]),
+    };
+    keys.split(",")
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|key| {
+            let wallet = if Path::new(key).is_file() {
+                read_keypair_file(key).map_err(|e| anyhow!("{}: {}", key, e))?
+            } else {
+                Keypair::from_base58_string(key)
+            };
+            Ok(Arc::new(wallet))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{parse_rpc_endpoints, parse_rpc_endpoints_file};
+
     #[ctor::ctor]
     fn init() {
         crate::logger::init(true);
         dotenvy::dotenv().ok();
     }
+
+    #[test]
+    fn test_parse_rpc_endpoints() {
+        let endpoints =
+            parse_rpc_endpoints("https://a.example.com, http://127.0.0.1:8899").unwrap();
+        assert_eq!(
+            endpoints,
+            vec!["https://a.example.com", "http://127.0.0.1:8899"]
+        );
+
+        let err = parse_rpc_endpoints("https://a.example.com,").unwrap_err();
+        assert_eq!(err.to_string(), "RPC_ENDPOINTS entry 1 is empty");
+        let err = parse_rpc_endpoints("https://a.example.com,wss://b.example.com").unwrap_err();
+        assert!(err.to_string().contains("entry 1 (wss://b.example.com)"));
+        let err = parse_rpc_endpoints("a.example.com").unwrap_err();
+        assert!(err.to_string().contains("entry 0 (a.example.com)"));
+    }
+
+    #[test]
+    fn test_parse_rpc_endpoints_file() {
+        let content = "# primary\nhttps://a.example.com\n\n  http://127.0.0.1:8899  \n";
+        assert_eq!(
+            parse_rpc_endpoints_file(content).unwrap(),
+            vec!["https://a.example.com", "http://127.0.0.1:8899"]
+        );
+
+        let err = parse_rpc_endpoints_file("https://a.example.com\nb.example.com\n").unwrap_err();
+        assert!(err.to_string().contains("line 2 (b.example.com)"));
+        assert!(parse_rpc_endpoints_file("# none yet\n").is_err());
+    }
 }
@@ -1,15 +1,32 @@
+use std::env;
+
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+/// LOG_FORMAT=json writes the log file as json lines, span fields such as
+/// an arbitrage's execution_id and mint become keys of the `span` object
 pub fn init(enable_console: bool) {
     let file_appender = RollingFileAppender::new(Rotation::DAILY, "logs", "app.log");
     let file_layer = tracing_subscriber::fmt::layer().with_writer(file_appender);
+    let (file_layer, json_file_layer) = match env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => (
+            None,
+            Some(
+                file_layer
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(false),
+            ),
+        ),
+        _ => (Some(file_layer), None),
+    };
 
     let registry = tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
         )
-        .with(file_layer);
+        .with(file_layer)
+        .with(json_file_layer);
 
     if enable_console {
         registry.with(tracing_subscriber::fmt::layer()).init();
@@ -1,22 +1,61 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
+use std::fs;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{Result, anyhow};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use jupiter_swap_api_client::quote::SwapMode;
 use jupiter_swap_api_client::transaction_config::ComputeUnitPriceMicroLamports;
-use jupiter_swap_api_client::{
-    JupiterSwapApiClient, quote::QuoteRequest, swap::SwapRequest,
-    transaction_config::TransactionConfig,
+use jupiter_swap_api_client::{JupiterSwapApiClient, transaction_config::TransactionConfig};
+use rand::Rng;
+use serde::Serialize;
+use solana_arb::amount::format_sol_signed;
+use solana_arb::arb::{InstructionLayout, QuoteOptions, TipPlacement};
+use solana_arb::backtest::{self, BacktestParams};
+use solana_arb::balance::{self, BalanceSource, LowBalanceConfig, PositionSizing};
+use solana_arb::breaker::{self, BreakerConfig};
+use solana_arb::decay::{self, DecayOverride, DecayPolicy};
+use solana_arb::dex::{self, Dex, DexSelection};
+use solana_arb::discovery::{self, DiscoveryConfig, DiscoverySource};
+use solana_arb::engine::{ArbEngine, EngineConfig, ScanParams, SwapBuild};
+use solana_arb::error::ArbError;
+use solana_arb::jito::{TipPolicy, TipSchedule};
+use solana_arb::latency::LatencyBreakdown;
+use solana_arb::outcome;
+use solana_arb::output::{
+    BalanceResult, BreakEvenResult, BundleResult, OutputFormat, QuoteResult, TipInfoResult,
 };
-use solana_arb::dex::Dex;
-use solana_arb::token::get_mint;
-use solana_arb::tx::create_tx_with_address_table_lookup;
-use solana_arb::{arb, get_payer, get_rpc_client, jito, logger, tx};
+use solana_arb::ratelimit::{self, RateLimiter};
+use solana_arb::referral::{self, Referral, partner_fee_bps};
+use solana_arb::session::{self, ReportConfig};
+use solana_arb::state::{self, RuntimeState};
+use solana_arb::stats::{self, StatsFilter, StatsReport, TradeStats};
+use solana_arb::sweep::{self, SweepConfig};
+use solana_arb::token::{self, get_ata, get_mint};
+use solana_arb::trigger::{self, TriggerConfig};
+use solana_arb::tx::{
+    BroadcastConfig, Commitment, ConfirmationConfig, PriorityFeeConfig, SendMode, SendOptions,
+};
+use solana_arb::watchlist::{MintOverrides, Watchlist, read_mints_file, read_watchlist_file};
+use solana_arb::wsol::{self, AutoUnwrapConfig, AutoWrapConfig};
+use solana_arb::{
+    alt, arb, get_payer, get_payers, get_rpc_client, health, history, inflight, jito, logger,
+    metrics, pin_rpc_endpoint, tx,
+};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Keypair;
 use solana_sdk::signer::Signer;
-use solana_sdk::transaction::VersionedTransaction;
+use solana_sdk::transaction::{Transaction, VersionedTransaction};
 use spl_token::{amount_to_ui_amount, ui_amount_to_amount};
+use spl_token_2022::extension::transfer_fee::TransferFee;
+use tokio::sync::mpsc;
 use tokio::time::Instant;
 use tracing::{debug, info, warn};
 
@@ -25,6 +64,254 @@ use tracing::{debug, info, warn};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        help = "Result format, json keeps stdout free of console logs",
+        default_value_t = OutputFormat::Human
+    )]
+    output: OutputFormat,
+    #[arg(
+        long,
+        global = true,
+        help = "Don't probe RPC_ENDPOINTS at startup, e.g. for offline simulation"
+    )]
+    skip_rpc_probe: bool,
+    #[arg(
+        long,
+        global = true,
+        help = "Send every rpc call of the run to this endpoint, ignoring RPC_ENDPOINTS"
+    )]
+    rpc_url: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        env = "JUP_RPS",
+        help = "Jupiter calls per second allowed across every scan, e.g. 10 for a 600/min key"
+    )]
+    jup_rps: Option<f64>,
+    #[arg(
+        long,
+        global = true,
+        env = "JUP_MAX_WAIT_MS",
+        help = "A jupiter call that would wait longer than this for --jup-rps fails the scan instead",
+        default_value_t = 500
+    )]
+    jup_max_wait_ms: u64,
+}
+
+#[derive(Args, Clone)]
+struct QuoteArgs {
+    #[arg(
+        long,
+        env = "JUP_RESTRICT_INTERMEDIATE_TOKENS",
+        help = "Only route through liquid intermediate tokens"
+    )]
+    restrict_intermediate_tokens: bool,
+    #[arg(
+        long,
+        env = "JUP_PLATFORM_FEE_BPS",
+        help = "Platform fee in bps, collected by --referral-account"
+    )]
+    platform_fee_bps: Option<u8>,
+    #[arg(
+        long,
+        env = "REFERRAL_ACCOUNT",
+        help = "Jupiter referral account that collects the platform fee"
+    )]
+    referral_account: Option<Pubkey>,
+    #[arg(
+        long,
+        env = "JUP_AS_LEGACY_TRANSACTION",
+        help = "Quote routes that fit a legacy transaction"
+    )]
+    as_legacy_transaction: bool,
+    #[arg(
+        long,
+        env = "JUP_TIMEOUT_MS",
+        help = "Timeout for jupiter quote calls in milliseconds",
+        default_value_t = 1500
+    )]
+    jupiter_timeout_ms: u64,
+    #[arg(
+        long,
+        env = "JUP_SWAP_TIMEOUT_MS",
+        help = "Timeout for jupiter swap and swap-instructions calls in milliseconds",
+        default_value_t = 3000
+    )]
+    jupiter_swap_timeout_ms: u64,
+    #[arg(
+        long,
+        env = "JUP_MAX_PRICE_IMPACT_PCT",
+        help = "Reject quotes whose price impact exceeds this percentage",
+        default_value_t = 5.0
+    )]
+    max_price_impact_pct: f64,
+    #[arg(
+        long,
+        help = "Let jupiter pick the swap slippage, bounded by --dynamic-slippage-max-bps"
+    )]
+    dynamic_slippage: bool,
+    #[arg(
+        long,
+        help = "Upper bound for --dynamic-slippage in bps",
+        default_value_t = 50
+    )]
+    dynamic_slippage_max_bps: u16,
+    #[arg(
+        long,
+        help = "Slippage in bps allowed on the sell leg as long as the worst case stays above --min-profit",
+        default_value_t = 0
+    )]
+    max_slippage_bps: u16,
+    #[arg(
+        long,
+        env = "JUP_QUOTE_POST",
+        help = "Send quotes as POST requests so long routes don't overflow the url, GET where unsupported"
+    )]
+    quote_post: bool,
+    #[arg(
+        long,
+        env = "BUY_DECAY_FACTOR",
+        help = "Share of the buy leg's quoted output trusted to fill, in (0, 1]",
+        value_parser = decay::parse_factor,
+        default_value_t = 1.0
+    )]
+    buy_decay: f64,
+    #[arg(
+        long,
+        env = "SELL_DECAY_FACTOR",
+        help = "Share of the sell leg's quoted output trusted to fill, in (0, 1]",
+        value_parser = decay::parse_factor,
+        default_value_t = 1.0
+    )]
+    sell_decay: f64,
+    #[arg(
+        long = "decay-override",
+        help = "Decay factor for legs routed mostly through a dex, e.g. \"Meteora DLMM=0.97\", repeatable"
+    )]
+    decay_overrides: Vec<DecayOverride>,
+    #[arg(
+        long,
+        env = "JUP_QUOTE_CACHE_TTL_MS",
+        help = "Answer identical scan quotes within this many milliseconds from the last response, executions always quote fresh, 0 = off",
+        default_value_t = 300
+    )]
+    quote_cache_ttl_ms: u64,
+}
+
+impl QuoteArgs {
+    fn to_options(&self, slippage_bps: u16, only_direct_routes: Option<bool>) -> QuoteOptions {
+        QuoteOptions {
+            slippage_bps,
+            only_direct_routes,
+            // unset flags leave jupiter's defaults untouched
+            restrict_intermediate_tokens: self.restrict_intermediate_tokens.then_some(true),
+            platform_fee_bps: self.platform_fee_bps,
+            as_legacy_transaction: self.as_legacy_transaction.then_some(true),
+            swap_mode: None,
+            timeout: Some(Duration::from_millis(self.jupiter_timeout_ms)),
+            swap_timeout: Some(Duration::from_millis(self.jupiter_swap_timeout_ms)),
+            max_price_impact_pct: Some(self.max_price_impact_pct),
+            dynamic_slippage_max_bps: self
+                .dynamic_slippage
+                .then_some(self.dynamic_slippage_max_bps),
+            max_slippage_bps: (self.max_slippage_bps > 0).then_some(self.max_slippage_bps),
+            post: self.quote_post,
+            decay: DecayPolicy::new(self.buy_decay, self.sell_decay, &self.decay_overrides),
+            cache_ttl: (self.quote_cache_ttl_ms > 0)
+                .then(|| Duration::from_millis(self.quote_cache_ttl_ms)),
+        }
+    }
+
+    /// Referral collecting `fee_bps` in `fee_mint`, checked on-chain
+    fn referral(
+        &self,
+        rpc_client: &RpcClient,
+        fee_bps: u8,
+        fee_mint: &Pubkey,
+    ) -> Result<Option<Referral>> {
+        let referral = Referral::new(self.referral_account, fee_bps)?;
+        if let Some(referral) = &referral {
+            referral.validate(rpc_client, fee_mint)?;
+            info!(
+                "Collecting {} bps platform fee via referral account {}",
+                referral.fee_bps, referral.account
+            );
+        }
+        Ok(referral)
+    }
+}
+
+#[derive(Args, Clone)]
+struct ConfirmArgs {
+    #[arg(
+        long,
+        env = "CONFIRM_POLL_MS",
+        help = "Confirmation status poll interval in milliseconds [default: 1000]"
+    )]
+    confirm_poll_ms: Option<u64>,
+    #[arg(
+        long,
+        alias = "confirm-timeout",
+        env = "CONFIRM_TIMEOUT_MS",
+        help = "Give up waiting for confirmation after this many milliseconds [default: 5000, send-bundle 30000, bundle-status 120000]"
+    )]
+    confirm_timeout_ms: Option<u64>,
+}
+
+impl ConfirmArgs {
+    /// The flags over the command's own `timeout`
+    fn to_config(
+        &self,
+        timeout: Duration,
+        commitment: CommitmentConfig,
+    ) -> Result<ConfirmationConfig> {
+        let defaults = ConfirmationConfig::default();
+        ConfirmationConfig::new(
+            self.confirm_poll_ms
+                .map_or(defaults.poll_interval, Duration::from_millis),
+            self.confirm_timeout_ms
+                .map_or(timeout, Duration::from_millis),
+            commitment,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Mode {
+    /// amount is the exact input, slippage bounds the minimum output
+    ExactIn,
+    /// amount is the exact output, slippage bounds the maximum input
+    ExactOut,
+}
+
+impl From<Mode> for SwapMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::ExactIn => SwapMode::ExactIn,
+            Mode::ExactOut => SwapMode::ExactOut,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum AltAction {
+    /// Create a lookup table owned by the payer
+    Create,
+    /// Append addresses to the table, skipping the ones it already holds
+    Extend {
+        #[arg(long, help = "File with one address per line")]
+        addresses: PathBuf,
+        #[arg(long, env = "OWN_ALT", help = "Lookup table to extend")]
+        table: Pubkey,
+    },
+    /// Print the table's addresses
+    Show {
+        #[arg(long, env = "OWN_ALT", help = "Lookup table to show")]
+        table: Pubkey,
+    },
 }
 
 #[derive(Subcommand)]
@@ -33,20 +320,216 @@ enum Commands {
         mint: Pubkey,
         #[clap(help = "Swap direction: [buy, sell]")]
         direction: String,
-        #[clap(help = "WSOL ui amount for swap")]
+        #[clap(help = "Ui amount for swap, the output amount in exact-out mode")]
         amount_in: f64,
+        #[arg(long, value_enum, default_value_t = Mode::ExactIn)]
+        mode: Mode,
+        #[arg(
+            long,
+            help = "Use the existing WSOL account instead of wrapping and unwrapping native SOL"
+        )]
+        no_wrap_sol: bool,
+        #[command(flatten)]
+        quote_args: QuoteArgs,
     },
 
-    Arb {
+    Quote {
+        mint: Pubkey,
+        #[clap(help = "WSOL ui amount for the round trip")]
+        amount_in: f64,
+        #[command(flatten)]
+        quote_args: QuoteArgs,
+    },
+
+    BreakEven {
         mint: Pubkey,
-        #[clap(help = "WSOL ui amount for arbitrage")]
+        #[clap(help = "WSOL ui amount for the round trip")]
         amount_in: f64,
         #[arg(
             long,
-            help = "Interval between each arbitrage attempt in seconds",
-            default_value_t = 1
+            help = "Priority fee in micro-lamports per compute unit",
+            default_value_t = 0
+        )]
+        compute_unit_price: u64,
+        #[arg(
+            long,
+            help = "Compute units the arb tx consumes",
+            default_value_t = 300_000
+        )]
+        compute_unit_limit: u32,
+        #[command(flatten)]
+        quote_args: QuoteArgs,
+    },
+
+    Balance,
+
+    Health,
+
+    ReferralSetup {
+        #[clap(
+            help = "Mint to initialize the referral fee account for",
+            required_unless_present = "all"
+        )]
+        mint: Option<Pubkey>,
+        #[arg(
+            long,
+            conflicts_with = "mint",
+            help = "Initialize every mint in --token-list"
+        )]
+        all: bool,
+        #[arg(
+            long,
+            env = "TOKEN_LIST_PATH",
+            help = "File with one mint per line",
+            default_value = "tokens.txt"
+        )]
+        token_list: PathBuf,
+        #[arg(long, env = "REFERRAL_ACCOUNT", help = "Jupiter referral account")]
+        referral_account: Pubkey,
+    },
+
+    Backtest {
+        #[clap(help = "Quotes recorded via QUOTE_RECORD_PATH")]
+        file: PathBuf,
+        #[arg(long, help = "Minimum profit in SOL", default_value_t = 0.0001)]
+        min_profit: f64,
+        #[arg(
+            long,
+            help = "Share of the profit paid as tip in bps",
+            default_value_t = 5000
+        )]
+        tip_bps: u16,
+        #[arg(long, default_value_t = 1.0)]
+        buy_decay_factor: f64,
+        #[arg(long, default_value_t = 1.0)]
+        sell_decay_factor: f64,
+    },
+
+    #[command(alias = "report")]
+    Stats {
+        #[arg(long, help = "Trade history file, defaults to TRADE_HISTORY_PATH")]
+        file: Option<PathBuf>,
+        #[arg(long, value_parser = stats::parse_since, help = "Only trades of the last window, e.g. 30m, 24h, 7d")]
+        since: Option<Duration>,
+        #[arg(long, help = "Only trades of this mint")]
+        mint: Option<Pubkey>,
+    },
+
+    TipInfo {
+        #[arg(long, help = "Show the tip paid for a hypothetical profit in SOL")]
+        profit: Option<f64>,
+        #[arg(
+            long,
+            help = "Tip percentage (0.0-1.0) of profit, as used by arb",
+            default_value_t = 0.5
+        )]
+        tip_percentage: f64,
+    },
+
+    BundleStatus {
+        bundle_id: String,
+        #[arg(long, help = "Poll until the bundle is confirmed")]
+        watch: bool,
+        #[command(flatten)]
+        confirm_args: ConfirmArgs,
+    },
+
+    SendBundle {
+        #[clap(help = "File with one base64 encoded transaction per line")]
+        path: PathBuf,
+        #[arg(long, help = "Wait for confirmation", default_value_t = false)]
+        wait: bool,
+        #[arg(
+            long,
+            help = "Sign unsigned transactions with the payer instead of refusing them"
+        )]
+        sign: bool,
+        #[command(flatten)]
+        confirm_args: ConfirmArgs,
+    },
+
+    AltManage {
+        #[command(subcommand)]
+        action: AltAction,
+    },
+
+    Arb {
+        mint: Pubkey,
+        #[clap(
+            help = "Base mint ui amount for arbitrage",
+            required_unless_present = "amount_pct"
+        )]
+        amount_in: Option<f64>,
+        #[arg(
+            long,
+            help = "Mint the round trip starts and ends in, amount_in and --min-profit are in its units",
+            default_value = "So11111111111111111111111111111111111111112"
+        )]
+        base_mint: Pubkey,
+        #[arg(
+            long,
+            conflicts_with = "amount_in",
+            help = "Size each trade as a percentage (0-100] of the wallet balance instead of a fixed amount_in"
         )]
-        interval: u64,
+        amount_pct: Option<f64>,
+        #[arg(long, help = "Maximum trade size in SOL when using --amount-pct")]
+        amount_max: Option<f64>,
+        #[arg(
+            long,
+            help = "Skip the iteration when --amount-pct sizes the trade below this many SOL",
+            default_value_t = 0.01
+        )]
+        amount_min: f64,
+        #[arg(
+            long,
+            help = "Round --amount-pct trade sizes down to a multiple of this many lamports",
+            default_value_t = 1_000_000
+        )]
+        amount_granularity: u64,
+        #[arg(
+            long,
+            help = "SOL kept in the wallet for fees and tips, never used for --amount-pct sizing",
+            default_value_t = 0.01
+        )]
+        reserve: f64,
+        #[arg(
+            long,
+            value_enum,
+            help = "Balance used by --amount-pct",
+            default_value_t = BalanceSource::Sol
+        )]
+        balance_source: BalanceSource,
+        #[arg(
+            long,
+            help = "How long a fetched balance is reused in milliseconds",
+            default_value_t = 5000
+        )]
+        balance_ttl_ms: u64,
+        #[arg(
+            long,
+            hide = true,
+            conflicts_with = "interval_ms",
+            help = "Deprecated: use --interval-ms. Interval between each arbitrage attempt in seconds"
+        )]
+        interval: Option<u64>,
+        #[arg(
+            long,
+            help = "Interval between each arbitrage attempt in milliseconds",
+            default_value_t = 1000
+        )]
+        interval_ms: u64,
+        #[arg(
+            long,
+            help = "Random 0..N milliseconds added to each interval sleep",
+            default_value_t = 0
+        )]
+        jitter_ms: u64,
+        #[arg(
+            long,
+            help = "Spread each mint's scan by a random share of the interval, keeping its period within ±this fraction, ignored with --jitter-ms",
+            default_value_t = 0.2
+        )]
+        interval_jitter: f64,
         #[arg(
             long,
             help = "Minimum profit in SOL to trigger arbitrage",
@@ -55,7 +538,14 @@ enum Commands {
         min_profit: f64,
         #[arg(
             long,
-            help = "Jupiter partner referral fee, e.g. 0.002 = 0.2%",
+            help = "Minimum profit in USD, replaces --min-profit while a SOL price is available"
+        )]
+        min_profit_usd: Option<f64>,
+        #[arg(long, help = "Also log profit in USD, see SOL_PRICE_API")]
+        usd: bool,
+        #[arg(
+            long,
+            help = "Jupiter partner fee, e.g. 0.002 = 0.2%, shorthand for --platform-fee-bps",
             default_value_t = 0.0
         )]
         partner_fee: f64,
@@ -66,24 +556,326 @@ enum Commands {
             default_value_t = 0.5
         )]
         tip_percentage: f64,
+        #[arg(
+            long,
+            help = "Tip share by profit tier, min_profit:tip_percentage pairs in the base mint, e.g. 0.001:0.3,0.01:0.5,0.1:0.7; --tip-percentage applies below the lowest tier"
+        )]
+        tip_schedule: Option<String>,
 
         #[arg(long, help = "Wait for confirmation", default_value_t = false)]
         wait_for_confirmation: bool,
+        #[command(flatten)]
+        confirm_args: ConfirmArgs,
+        #[arg(
+            long,
+            env = "CONFIRM_FAIL_FAST",
+            default_value_t = true,
+            action = ArgAction::Set,
+            help = "Fail an execution whose confirmation timed out, false records it as submitted and checks it again on the next iteration"
+        )]
+        confirm_fail_fast: bool,
+        #[arg(
+            long,
+            value_enum,
+            help = "How transactions are submitted",
+            default_value_t = SendMode::Jito
+        )]
+        send_mode: SendMode,
+        #[arg(
+            long,
+            default_value_t = true,
+            action = ArgAction::Set,
+            help = "Skip the preflight simulation when sending over rpc"
+        )]
+        skip_preflight: bool,
+        #[arg(
+            long,
+            value_enum,
+            help = "Commitment rpc sent transactions are preflighted and confirmed at",
+            default_value_t = Commitment::Confirmed
+        )]
+        commitment: Commitment,
+        #[arg(
+            long,
+            help = "Priority fee percentile of recent fees on the route's pools, --send-mode rpc and both only",
+            default_value_t = 75
+        )]
+        priority_fee_percentile: u8,
+        #[arg(
+            long,
+            help = "Cap on the priority fee in micro-lamports per compute unit",
+            default_value_t = 1_000_000
+        )]
+        max_compute_unit_price: u64,
+        #[arg(
+            long,
+            help = "Send buy and sell as two transactions of one jito bundle, for routes too big for one tx"
+        )]
+        bundle_split: bool,
+        #[arg(
+            long,
+            value_enum,
+            help = "Where the jito tip goes in the transaction, --version 1 only",
+            default_value_t = TipPlacement::AfterSwap
+        )]
+        tip_placement: TipPlacement,
+        #[arg(
+            long,
+            help = "Include jupiter's token ledger instruction, --version 1 only"
+        )]
+        include_token_ledger: bool,
+        #[arg(long, help = "Include jupiter's other instructions, --version 1 only")]
+        include_other_instructions: bool,
+        #[arg(
+            long,
+            help = "Simulate before sending and abort on failure, a passing transaction is sent as simulated"
+        )]
+        simulate_then_send: bool,
+        #[arg(
+            long,
+            help = "--simulate-then-send that also sizes the compute unit limit to the units consumed"
+        )]
+        presimulate: bool,
+        #[arg(
+            long,
+            help = "Margin in percent added to the simulated compute units, with --presimulate",
+            default_value_t = 10
+        )]
+        compute_unit_margin_pct: u16,
+        #[arg(
+            long,
+            help = "Pause scanning after this many consecutive rpc, jupiter or jito failures, 0 = never",
+            default_value_t = 5
+        )]
+        breaker_threshold: u32,
+        #[arg(
+            long,
+            help = "First pause in milliseconds, doubled on every trip until a scan succeeds",
+            default_value_t = 10_000
+        )]
+        breaker_cooldown_ms: u64,
+        #[arg(
+            long,
+            help = "Execute a mint again while a previous execution for it is still in flight"
+        )]
+        allow_concurrent_per_mint: bool,
+        #[arg(
+            long,
+            help = "Combine the executions ready within this many milliseconds into one jito bundle of up to 5 txs with a shared tip, --send-mode jito only"
+        )]
+        batch_window_ms: Option<u64>,
         #[arg(long, help = "Arbitrage version to use: 1 or 2", default_value_t = 1)]
         version: u8,
+        #[arg(
+            long,
+            help = "Comma-separated venues to quote, e.g. \"Raydium,Whirlpool\" [default: all but Meteora]"
+        )]
+        dexes: Option<DexSelection>,
+        #[arg(long, help = "Venues for the buy leg, overrides --dexes")]
+        buy_dexes: Option<DexSelection>,
+        #[arg(long, help = "Venues for the sell leg, overrides --dexes")]
+        sell_dexes: Option<DexSelection>,
+        #[arg(
+            long,
+            help = "Pass dex labels this build doesn't know to jupiter as they are instead of failing"
+        )]
+        allow_unknown_dexes: bool,
+        #[arg(
+            long,
+            help = "Only quote the venues that have a SOL pool for the mint, looked up on-chain once per mint"
+        )]
+        discover_pools: bool,
+        #[arg(
+            long,
+            help = "Create the payers' missing token accounts for the mint and watchlist before trading, mints that fail are left out"
+        )]
+        prewarm_atas: bool,
+        #[arg(
+            long,
+            conflicts_with = "ws_trigger",
+            help = "Scan every mint once and exit: 0 = landed, 2 = no opportunity, 3 = submitted but not confirmed, 1 = error"
+        )]
+        once: bool,
+        #[arg(
+            long,
+            help = "Serve prometheus metrics on this address, e.g. 127.0.0.1:9100"
+        )]
+        metrics_addr: Option<SocketAddr>,
+        #[arg(
+            long,
+            value_parser = stats::parse_since,
+            help = "Log a session performance report this often, e.g. 10m"
+        )]
+        report_interval: Option<Duration>,
+        #[arg(
+            long,
+            env = "NOTIFY_WEBHOOK_URL",
+            help = "Post the session report and alerts as json to this webhook"
+        )]
+        notify_webhook: Option<String>,
+        #[arg(
+            long,
+            value_parser = stats::parse_since,
+            default_value = "1h",
+            help = "How often the session report goes to --notify-webhook"
+        )]
+        notify_interval: Duration,
+        #[arg(
+            long,
+            help = "Alert once a payer's SOL balance drops below this, logged and posted to --notify-webhook"
+        )]
+        low_balance_sol: Option<f64>,
+        #[command(flatten)]
+        quote_args: QuoteArgs,
+        #[arg(
+            long,
+            value_enum,
+            help = "Periodically add trending mints from this source to the scan set"
+        )]
+        discover: Option<DiscoverySource>,
+        #[arg(
+            long,
+            help = "Seconds between discovery refreshes",
+            default_value_t = 300
+        )]
+        discover_interval: u64,
+        #[arg(
+            long,
+            help = "Number of discovered mints to scan",
+            default_value_t = 10
+        )]
+        discover_top: usize,
+        #[arg(
+            long,
+            help = "Minimum 24h volume in USD for a discovered mint",
+            default_value_t = 0.0
+        )]
+        discover_min_volume: f64,
+        #[arg(
+            long,
+            env = "DISCOVER_DENYLIST",
+            value_delimiter = ',',
+            help = "Comma-separated mints discovery must never add"
+        )]
+        discover_denylist: Vec<Pubkey>,
+        #[arg(
+            long,
+            env = "WATCHLIST_FILE",
+            help = "JSON list of extra mints to scan, each with optional amount_in, min_profit, tip_percentage and dexes overrides"
+        )]
+        watchlist_file: Option<PathBuf>,
+        #[arg(
+            long,
+            env = "STATE_FILE",
+            help = "Where the breaker state and lifetime totals are kept across restarts",
+            default_value = "logs/state.json"
+        )]
+        state_file: PathBuf,
+        #[arg(
+            long,
+            help = "Start without restoring --state-file, it is still written"
+        )]
+        fresh_state: bool,
+        #[arg(
+            long,
+            help = "Keep the WSOL account topped up by wrapping native SOL, never below --reserve"
+        )]
+        auto_wrap: bool,
+        #[arg(
+            long,
+            help = "WSOL balance in SOL restored by --auto-wrap",
+            default_value_t = 2.0
+        )]
+        wsol_target: f64,
+        #[arg(
+            long,
+            help = "WSOL balance in SOL below which --auto-wrap tops up",
+            default_value_t = 0.5
+        )]
+        wsol_min: f64,
+        #[arg(
+            long,
+            conflicts_with = "auto_wrap",
+            help = "Periodically close the WSOL account back to native SOL, skipped while a trade is in flight"
+        )]
+        auto_unwrap: bool,
+        #[arg(
+            long,
+            help = "Seconds between two --auto-unwrap rounds",
+            default_value_t = 300
+        )]
+        unwrap_interval_secs: u64,
+        #[arg(
+            long,
+            requires = "sweep_above",
+            help = "Cold wallet the payer balance above --sweep-above is moved to"
+        )]
+        sweep_to: Option<Pubkey>,
+        #[arg(
+            long,
+            requires = "sweep_to",
+            help = "Payer balance in SOL above which --sweep-to sweeps"
+        )]
+        sweep_above: Option<f64>,
+        #[arg(
+            long,
+            help = "SOL left in the payer after a sweep, never below --reserve",
+            default_value_t = 1.0
+        )]
+        sweep_keep: f64,
+        #[arg(
+            long,
+            help = "Scan a mint as soon as a pool in its route changes, see RPC_WS_ENDPOINTS. Falls back to interval polling while the websocket is down"
+        )]
+        ws_trigger: bool,
+        #[arg(
+            long,
+            help = "Minimum milliseconds between two websocket triggers for the same mint",
+            default_value_t = 500
+        )]
+        trigger_debounce_ms: u64,
+        #[arg(
+            long,
+            help = "Seconds between full scans while --ws-trigger is connected, refreshes the subscribed pools",
+            default_value_t = 30
+        )]
+        trigger_refresh_secs: u64,
     },
 }
 
+impl Commands {
+    // offline commands, or ones that only talk to jupiter and jito
+    fn needs_rpc(&self) -> bool {
+        !matches!(
+            self,
+            Commands::Health
+                | Commands::Backtest { .. }
+                | Commands::Stats { .. }
+                | Commands::TipInfo { .. }
+                | Commands::BundleStatus { .. }
+        )
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
     let cli = Cli::parse();
-    logger::init(true);
-
-    let rpc_client = get_rpc_client()?;
-    let payer = get_payer()?;
+    // logs still go to the file in json mode
+    logger::init(cli.output == OutputFormat::Human);
+    // fail on a bad tip cap/floor before anything tips
+    let tip_policy = TipPolicy::from_env()?;
+    debug!("tip policy: {:?}", tip_policy);
+    if let Some(rpc_url) = &cli.rpc_url {
+        pin_rpc_endpoint(rpc_url)?;
+    }
+    if let Some(rps) = cli.jup_rps {
+        let limiter = RateLimiter::new(rps, Duration::from_millis(cli.jup_max_wait_ms))
+            .map_err(|e| anyhow!("--jup-rps: {}", e))?;
+        ratelimit::configure(limiter);
+    }
 
-    let api_base_url = env::var("JUP_QUOTE_API").unwrap_or("https://quote-api.jup.ag/v6".into());
+    let api_base_url = arb::JUP_QUOTE_API.clone();
     info!("Using jupiter quote api url: {}", api_base_url);
     let jupiter_extra_args: Option<HashMap<String, String>> =
         env::var("JUP_QUOTE_API_KEY").ok().map(|api_key| {
@@ -93,15 +885,60 @@ async fn main() -> Result<()> {
         });
     let jupiter_swap_api_client = JupiterSwapApiClient::new(api_base_url);
 
+    // health reports a bad rpc or key instead of failing on it
+    if let Commands::Health = cli.command {
+        let results = health::run_checks(&jupiter_swap_api_client, jupiter_extra_args).await;
+        for result in &results {
+            println!(
+                "{:<4} {:<50} {:>8}ms  {}",
+                if result.ok { "ok" } else { "FAIL" },
+                result.name,
+                result.latency.as_millis(),
+                result.detail
+            );
+        }
+        if results.iter().any(|r| r.critical && !r.ok) {
+            return Err(anyhow!("health check failed"));
+        }
+        return Ok(());
+    }
+
+    if !cli.skip_rpc_probe && cli.command.needs_rpc() {
+        let results = health::check_rpc_endpoints().await;
+        for result in &results {
+            let line = format!(
+                "{:<4} {:<50} {:>8}ms  {}",
+                if result.ok { "ok" } else { "FAIL" },
+                result.name,
+                result.latency.as_millis(),
+                result.detail
+            );
+            match result.ok {
+                true => info!("{}", line),
+                false => warn!("{}", line),
+            }
+        }
+        if !results.iter().any(|r| r.ok) {
+            return Err(anyhow!(
+                "no RPC endpoint passed the startup probe, pass --skip-rpc-probe to run without one"
+            ));
+        }
+    }
+
+    let rpc_client = get_rpc_client()?;
+
     match &cli.command {
         Commands::Swap {
             mint,
             direction,
             amount_in,
+            mode,
+            no_wrap_sol,
+            quote_args,
         } => {
             info!(
-                "mint: {}, direction: {}, amount_in: {}",
-                mint, direction, amount_in
+                "mint: {}, direction: {}, amount: {}, mode: {:?}",
+                mint, direction, amount_in, mode
             );
 
             let native_mint = spl_token::native_mint::id();
@@ -112,41 +949,79 @@ async fn main() -> Result<()> {
                 _ => panic!("Invalid direction"),
             };
             let in_mint = get_mint(&rpc_client, &token_in)?;
-
-            let quote_request = QuoteRequest {
-                amount: ui_amount_to_amount(*amount_in, in_mint.decimals),
-                input_mint: token_in,
-                output_mint: token_out,
-                dexes: Some("Raydium,Meteora DLMM,Whirlpool".into()),
-                slippage_bps: 500,
-                quote_args: jupiter_extra_args.clone(),
-                ..QuoteRequest::default()
+            let out_mint = get_mint(&rpc_client, &token_out)?;
+            // exact-out quotes the amount the wallet receives
+            let amount = match mode {
+                Mode::ExactIn => ui_amount_to_amount(*amount_in, in_mint.decimals),
+                Mode::ExactOut => ui_amount_to_amount(*amount_in, out_mint.decimals),
             };
-            // GET /quote
-            let quote_response = jupiter_swap_api_client.quote(&quote_request).await.unwrap();
+
+            // the swap is built from this quote right away
+            let mut quote_options = quote_args.to_options(500, None).uncached();
+            quote_options.swap_mode = Some((*mode).into());
+            let referral = quote_args.referral(
+                &rpc_client,
+                quote_args.platform_fee_bps.unwrap_or(0),
+                &token_out,
+            )?;
+            let quote_request = quote_options.build_request(
+                &token_in,
+                &token_out,
+                amount,
+                &(Dex::RAYDIUM | Dex::METEORA_DLMM | Dex::WHIRLPOOL).into(),
+                jupiter_extra_args.clone(),
+            );
+            // GET /quote, POST with --quote-post
+            let quote_response =
+                arb::quote(&jupiter_swap_api_client, &quote_request, &quote_options).await?;
             println!("{quote_response:#?}");
+            let ui_in = amount_to_ui_amount(quote_response.in_amount, in_mint.decimals);
+            let ui_out = amount_to_ui_amount(quote_response.out_amount, out_mint.decimals);
+            match mode {
+                Mode::ExactIn => info!(
+                    "in: {}, out: {}, min out (slippage {} bps): {}",
+                    ui_in,
+                    ui_out,
+                    quote_response.slippage_bps,
+                    amount_to_ui_amount(quote_response.other_amount_threshold, out_mint.decimals)
+                ),
+                Mode::ExactOut => info!(
+                    "out: {}, in: {}, max in (slippage {} bps): {}",
+                    ui_out,
+                    ui_in,
+                    quote_response.slippage_bps,
+                    amount_to_ui_amount(quote_response.other_amount_threshold, in_mint.decimals)
+                ),
+            }
+            if ui_out > 0.0 {
+                info!("effective price: {} in per out", ui_in / ui_out);
+            }
             let mut tx_config = TransactionConfig::default();
-            tx_config.wrap_and_unwrap_sol = true;
+            tx_config.wrap_and_unwrap_sol = !*no_wrap_sol;
+            if tx_config.wrap_and_unwrap_sol && token_in != native_mint {
+                warn!(
+                    "wrapping SOL although the input mint {} isn't native SOL, only the output is unwrapped",
+                    token_in
+                );
+            }
             tx_config.compute_unit_price_micro_lamports =
                 Some(ComputeUnitPriceMicroLamports::MicroLamports(50000));
+            tx_config.fee_account = referral.map(|r| r.fee_account(&token_out));
+            tx_config.dynamic_slippage =
+                arb::dynamic_slippage(quote_options.dynamic_slippage_max_bps);
             // POST /swap
-            let swap_response = jupiter_swap_api_client
-                .swap(
-                    &SwapRequest {
-                        user_public_key: payer.pubkey(),
-                        quote_response: quote_response.clone(),
-                        config: TransactionConfig::default(),
-                    },
-                    jupiter_extra_args,
-                )
-                .await
-                .unwrap();
+            let payer = get_payer()?;
+            let versioned_transaction = arb::swap(
+                &jupiter_swap_api_client,
+                jupiter_extra_args,
+                &payer.pubkey(),
+                &quote_response,
+                tx_config,
+                quote_options.swap_timeout,
+            )
+            .await?;
 
-            println!("Raw tx len: {}", swap_response.swap_transaction.len());
-            println!("Raw tx: {:?}", swap_response);
-
-            let versioned_transaction: VersionedTransaction =
-                bincode::deserialize(&swap_response.swap_transaction).unwrap();
+            println!("Raw tx: {:?}", versioned_transaction);
 
             let signed_versioned_transaction =
                 VersionedTransaction::try_new(versioned_transaction.message, &[&payer]).unwrap();
@@ -160,319 +1035,1129 @@ async fn main() -> Result<()> {
             }
         }
 
+        Commands::ReferralSetup {
+            mint,
+            all,
+            token_list,
+            referral_account,
+        } => {
+            let mints = match mint {
+                Some(mint) => vec![*mint],
+                None if *all => read_mints_file(token_list)?,
+                None => unreachable!("clap requires mint or --all"),
+            };
+            let payer = get_payer()?;
+            setup_referral_token_accounts(&rpc_client, &payer, referral_account, &mints)?;
+        }
+
+        Commands::Backtest {
+            file,
+            min_profit,
+            tip_bps,
+            buy_decay_factor,
+            sell_decay_factor,
+        } => {
+            let records = backtest::read_records(file)?;
+            let params = BacktestParams {
+                min_profit: ui_amount_to_amount(*min_profit, 9),
+                tip_bps: *tip_bps,
+                buy_decay_factor: *buy_decay_factor,
+                sell_decay_factor: *sell_decay_factor,
+            };
+            let report = backtest::replay(&records, &params);
+            match cli.output {
+                OutputFormat::Human => {
+                    println!("records: {}", report.records);
+                    println!("opportunities: {}", report.opportunities);
+                    println!(
+                        "total profit: {} sol (tips: {} sol)",
+                        format_sol_signed(report.total_profit),
+                        amount_to_ui_amount(report.total_tip, 9)
+                    );
+                    if let Some(best) = report.best_profit {
+                        println!("best: {} sol", format_sol_signed(best));
+                    }
+                }
+                OutputFormat::Json => print_json(&report)?,
+            }
+        }
+
+        Commands::Stats { file, since, mint } => {
+            let path = file
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(history::HISTORY_PATH.as_str()));
+            let filter = StatsFilter {
+                since: since
+                    .map(|since| outcome::now_millis().saturating_sub(since.as_millis() as u64)),
+                mint: mint.map(|mint| mint.to_string()),
+            };
+            let file = fs::File::open(&path)
+                .map_err(|e| anyhow!("failed to open {}: {}", path.display(), e))?;
+            let report = stats::summarize(BufReader::new(file), &filter)?;
+            match cli.output {
+                OutputFormat::Human => print_stats(&report),
+                OutputFormat::Json => print_json(&report)?,
+            }
+        }
+
+        Commands::TipInfo {
+            profit,
+            tip_percentage,
+        } => {
+            jito::init_tip_accounts().await?;
+            jito::init_tip_amounts().await?;
+            let tip_accounts = jito::all_tip_accounts().await?;
+            let tip_floor = match jito::TIPS_PERCENTILE.read().await.as_ref() {
+                Some(data) => vec![
+                    ("p25", data.landed_tips_25th_percentile),
+                    ("p50", data.landed_tips_50th_percentile),
+                    ("p75", data.landed_tips_75th_percentile),
+                    ("p95", data.landed_tips_95th_percentile),
+                    ("p99", data.landed_tips_99th_percentile),
+                    ("ema p50", data.ema_landed_tips_50th_percentile),
+                ],
+                None => vec![],
+            };
+            let result = TipInfoResult {
+                tip_accounts: tip_accounts.iter().map(|a| a.to_string()).collect(),
+                tip_floor: tip_floor
+                    .into_iter()
+                    .map(|(name, sol)| (name.to_string(), ui_amount_to_amount(sol, 9)))
+                    .collect(),
+                tip_for_profit: profit.map(|profit| {
+                    let tip = ui_amount_to_amount(profit, 9) as f64 * tip_percentage.min(1.0);
+                    jito::TIP_POLICY.clamp(tip as u64)
+                }),
+            };
+            match cli.output {
+                OutputFormat::Human => {
+                    println!("tip accounts:");
+                    for account in &result.tip_accounts {
+                        println!("  {}", account);
+                    }
+                    println!("tip floor:");
+                    for (name, lamports) in &result.tip_floor {
+                        println!(
+                            "  {:<8} {:>12} lamports  {} sol",
+                            name,
+                            lamports,
+                            amount_to_ui_amount(*lamports, 9)
+                        );
+                    }
+                    if let Ok(tip) = jito::get_tip_value().await {
+                        println!("configured tip (JITO_TIP_VALUE/JITO_TIP_PERCENTILE): {tip} sol");
+                    }
+                    if let (Some(profit), Some(tip)) = (profit, result.tip_for_profit) {
+                        println!(
+                            "tip for {} sol profit at {}: {} lamports ({} sol)",
+                            profit,
+                            tip_percentage,
+                            tip,
+                            amount_to_ui_amount(tip, 9)
+                        );
+                    }
+                }
+                OutputFormat::Json => print_json(&result)?,
+            }
+        }
+
+        Commands::BundleStatus {
+            bundle_id,
+            watch,
+            confirm_args,
+        } => {
+            let confirmation =
+                confirm_args.to_config(Duration::from_secs(120), CommitmentConfig::confirmed())?;
+            let status = jito::bundle_status(
+                bundle_id,
+                *watch,
+                confirmation.poll_interval,
+                confirmation.timeout,
+            )
+            .await?;
+            match status {
+                Some(status) => print!("{}", status),
+                None => println!(
+                    "bundle {} not found, it hasn't landed or expired",
+                    bundle_id
+                ),
+            }
+        }
+
+        Commands::SendBundle {
+            path,
+            wait,
+            sign,
+            confirm_args,
+        } => {
+            let content = fs::read_to_string(path)?;
+            let mut bundle = tx::decode_transactions(&content)?;
+            if bundle.is_empty() {
+                return Err(anyhow!("no transactions in {}", path.display()));
+            }
+            // only signing needs a key
+            let payer = match *sign {
+                true => Some(get_payer()?),
+                false => None,
+            };
+            tx::prepare_bundle(&mut bundle, payer.as_deref())?;
+            info!("Sending bundle of {} transactions", bundle.len());
+            // prepared bundles may take a while to be picked up
+            let confirmation =
+                confirm_args.to_config(Duration::from_secs(30), CommitmentConfig::confirmed())?;
+            let mut bundle_id = String::new();
+            let sent = tx::send_bundle(
+                &rpc_client,
+                bundle,
+                *wait,
+                &confirmation,
+                |id| bundle_id = id.to_string(),
+                &mut LatencyBreakdown::default(),
+            )
+            .await?;
+            match cli.output {
+                OutputFormat::Human => {
+                    println!("bundle_id: {}", bundle_id);
+                    for tx in &sent.signatures {
+                        println!("https://solscan.io/tx/{}", tx);
+                    }
+                }
+                OutputFormat::Json => print_json(&BundleResult {
+                    bundle_id,
+                    signatures: sent.signatures,
+                })?,
+            }
+        }
+
+        Commands::AltManage { action } => match action {
+            AltAction::Create => {
+                let table = alt::create(&rpc_client, &get_payer()?)?;
+                alt::wait_until_active(&rpc_client, &table)?;
+                println!("lookup table: {}", table);
+                println!("set OWN_ALT={} to use it for arb transactions", table);
+            }
+            AltAction::Extend { addresses, table } => {
+                let addresses = read_mints_file(addresses)?;
+                let signatures = alt::extend(&rpc_client, &get_payer()?, table, &addresses)?;
+                for signature in signatures {
+                    println!("https://solscan.io/tx/{}", signature);
+                }
+            }
+            AltAction::Show { table } => {
+                let state = alt::fetch(&rpc_client, table)?;
+                println!("lookup table: {}", table);
+                match state.meta.authority {
+                    Some(authority) => println!("authority: {}", authority),
+                    None => println!("authority: none (frozen)"),
+                }
+                println!("last extended slot: {}", state.meta.last_extended_slot);
+                if state.meta.deactivation_slot != u64::MAX {
+                    println!("deactivated at slot: {}", state.meta.deactivation_slot);
+                }
+                println!("addresses: {}", state.addresses.len());
+                for (index, address) in state.addresses.iter().enumerate() {
+                    println!("  {:>3} {}", index, address);
+                }
+            }
+        },
+
+        Commands::Quote {
+            mint,
+            amount_in,
+            quote_args,
+        } => {
+            let amount_in = ui_amount_to_amount(*amount_in, 9);
+            let transfer_fee = load_transfer_fee(&rpc_client, mint)?;
+            let mut latency = LatencyBreakdown::default();
+            let dexes = Dex::ALL.into();
+            let round_trip = arb::RoundTrip {
+                base_mint: &spl_token::native_mint::id(),
+                mint,
+                amount_in,
+                buy_dexes: &dexes,
+                sell_dexes: &dexes,
+                transfer_fee: transfer_fee.as_ref(),
+            };
+            let (profit, quote_buy_response, quote_sell_response) = arb::caculate_profit(
+                &jupiter_swap_api_client,
+                jupiter_extra_args,
+                &round_trip,
+                &quote_args.to_options(0, Some(true)),
+                &mut latency,
+            )
+            .await?;
+            let result = QuoteResult::new(
+                *mint,
+                amount_in,
+                profit,
+                &quote_buy_response,
+                &quote_sell_response,
+            );
+            match cli.output {
+                OutputFormat::Human => {
+                    println!("mint: {}", result.mint);
+                    println!("amount in: {} sol", amount_to_ui_amount(amount_in, 9));
+                    println!(
+                        "buy: {} ({})",
+                        result.buy_out_amount,
+                        result.buy_route.join(" -> ")
+                    );
+                    println!(
+                        "sell: {} ({})",
+                        result.sell_out_amount,
+                        result.sell_route.join(" -> ")
+                    );
+                    println!("profit: {} sol ({} lamports)", result.profit_sol, profit);
+                    println!("timings: {}", latency);
+                }
+                OutputFormat::Json => print_json(&result)?,
+            }
+        }
+
+        Commands::BreakEven {
+            mint,
+            amount_in,
+            compute_unit_price,
+            compute_unit_limit,
+            quote_args,
+        } => {
+            let amount_in = ui_amount_to_amount(*amount_in, 9);
+            let transfer_fee = load_transfer_fee(&rpc_client, mint)?;
+            let mut latency = LatencyBreakdown::default();
+            let dexes = Dex::ALL.into();
+            let round_trip = arb::RoundTrip {
+                base_mint: &spl_token::native_mint::id(),
+                mint,
+                amount_in,
+                buy_dexes: &dexes,
+                sell_dexes: &dexes,
+                transfer_fee: transfer_fee.as_ref(),
+            };
+            let (break_even, quote_buy_response, quote_sell_response) = arb::break_even(
+                &jupiter_swap_api_client,
+                jupiter_extra_args,
+                &round_trip,
+                &quote_args.to_options(0, Some(true)),
+                *compute_unit_price,
+                *compute_unit_limit,
+                &mut latency,
+            )
+            .await?;
+            let result = BreakEvenResult::new(
+                *mint,
+                amount_in,
+                &break_even,
+                &quote_buy_response,
+                &quote_sell_response,
+            );
+            match cli.output {
+                OutputFormat::Human => {
+                    println!("mint: {}", result.mint);
+                    println!("amount in: {} sol", amount_to_ui_amount(amount_in, 9));
+                    println!(
+                        "route: {} | {}",
+                        result.buy_route.join(" -> "),
+                        result.sell_route.join(" -> ")
+                    );
+                    println!(
+                        "gross profit: {} sol ({} lamports)",
+                        format_sol_signed(break_even.gross_profit),
+                        break_even.gross_profit
+                    );
+                    println!(
+                        "fees: {} sol ({} lamports)",
+                        amount_to_ui_amount(break_even.fees, 9),
+                        break_even.fees
+                    );
+                    println!(
+                        "break-even tip: {} sol ({} lamports)",
+                        result.break_even_tip_sol, result.break_even_tip_lamports
+                    );
+                }
+                OutputFormat::Json => print_json(&result)?,
+            }
+        }
+
+        Commands::Health => unreachable!("handled before the rpc client is created"),
+
+        Commands::Balance => {
+            let owner = get_payer()?.pubkey();
+            let result = BalanceResult::new(
+                owner,
+                rpc_client.get_balance(&owner)?,
+                wsol::get_wsol_balance(&rpc_client, &owner)?,
+            );
+            match cli.output {
+                OutputFormat::Human => {
+                    println!("owner: {}", owner);
+                    println!("sol: {}", result.sol);
+                    println!("wsol: {}", result.wsol);
+                }
+                OutputFormat::Json => print_json(&result)?,
+            }
+        }
+
         Commands::Arb {
             mint,
             amount_in,
+            base_mint,
+            amount_pct,
+            amount_max,
+            amount_min,
+            amount_granularity,
+            reserve,
+            balance_source,
+            balance_ttl_ms,
             interval,
+            interval_ms,
+            jitter_ms,
+            interval_jitter,
             min_profit,
+            min_profit_usd,
+            usd,
             partner_fee,
             tip_percentage,
+            tip_schedule,
             wait_for_confirmation,
+            confirm_args,
+            confirm_fail_fast,
+            send_mode,
+            skip_preflight,
+            commitment,
+            priority_fee_percentile,
+            max_compute_unit_price,
+            bundle_split,
+            tip_placement,
+            include_token_ledger,
+            include_other_instructions,
+            simulate_then_send,
+            presimulate,
+            compute_unit_margin_pct,
+            breaker_threshold,
+            breaker_cooldown_ms,
+            allow_concurrent_per_mint,
+            batch_window_ms,
             version,
+            dexes,
+            buy_dexes,
+            sell_dexes,
+            allow_unknown_dexes,
+            discover_pools,
+            prewarm_atas,
+            once,
+            metrics_addr,
+            report_interval,
+            notify_webhook,
+            notify_interval,
+            low_balance_sol,
+            quote_args,
+            discover,
+            discover_interval,
+            discover_top,
+            discover_min_volume,
+            discover_denylist,
+            watchlist_file,
+            state_file,
+            fresh_state,
+            auto_wrap,
+            wsol_target,
+            wsol_min,
+            auto_unwrap,
+            unwrap_interval_secs,
+            sweep_to,
+            sweep_above,
+            sweep_keep,
+            ws_trigger,
+            trigger_debounce_ms,
+            trigger_refresh_secs,
         } => {
+            let interval = match interval {
+                Some(secs) => {
+                    warn!("--interval is deprecated, use --interval-ms instead");
+                    Duration::from_secs(*secs)
+                }
+                None => Duration::from_millis(*interval_ms),
+            };
+            if !(0.0..1.0).contains(interval_jitter) {
+                return Err(anyhow!(
+                    "--interval-jitter must be in [0, 1), got {}",
+                    interval_jitter
+                ));
+            }
+            // --jitter-ms already moves the whole tick, the two don't stack
+            let interval_jitter = match *jitter_ms > 0 {
+                true => 0.0,
+                false => *interval_jitter,
+            };
+            // clap keeps unknown labels, they only reach jupiter when opted in
+            if !*allow_unknown_dexes {
+                for selection in [dexes, buy_dexes, sell_dexes].into_iter().flatten() {
+                    if let Some(label) = selection.custom.first() {
+                        return Err(anyhow!(
+                            "unknown dex {}, expected one of: {} (or pass --allow-unknown-dexes)",
+                            label,
+                            Dex::ALL | Dex::METEORA
+                        ));
+                    }
+                }
+            }
             info!(
-                "mint: {}, amount_in: {}, interval: {}s, min_profit: {} SOL",
-                mint, amount_in, interval, min_profit
+                "mint: {}, base_mint: {}, amount_in: {:?}, amount_pct: {:?}, interval: {:?}, jitter: {}ms, min_profit: {}",
+                mint, base_mint, amount_in, amount_pct, interval, jitter_ms, min_profit
+            );
+            let payers = get_payers()?;
+            if payers.is_empty() {
+                return Err(anyhow!("PRIVATE_KEYS holds no key"));
+            }
+            // every payer trades out of its own base mint account
+            let mut base_decimals = 0;
+            for payer in &payers {
+                base_decimals = base_mint_decimals(&rpc_client, &payer.pubkey(), base_mint)?;
+            }
+            if base_mint == mint {
+                return Err(anyhow!("mint and --base-mint must differ"));
+            }
+            if *base_mint != spl_token::native_mint::id() && amount_pct.is_some() {
+                return Err(anyhow!("--amount-pct only supports the SOL base mint"));
+            }
+            let min_profit_lamports = ui_amount_to_amount(*min_profit, base_decimals);
+            let tip_schedule = tip_schedule
+                .as_deref()
+                .map(|schedule| TipSchedule::parse(schedule, base_decimals))
+                .transpose()
+                .map_err(|e| anyhow!("--tip-schedule: {}", e))?;
+            let confirmation = ConfirmationConfig {
+                fail_fast: *confirm_fail_fast,
+                ..confirm_args
+                    .to_config(ConfirmationConfig::default().timeout, commitment.config())?
+            };
+            if *priority_fee_percentile > 100 {
+                return Err(anyhow!(
+                    "--priority-fee-percentile must be in [0, 100], got {}",
+                    priority_fee_percentile
+                ));
+            }
+            if tip_schedule.is_some() && *send_mode == SendMode::Rpc {
+                return Err(anyhow!("--tip-schedule needs --send-mode jito or both"));
+            }
+            if batch_window_ms.is_some() && *send_mode != SendMode::Jito {
+                return Err(anyhow!("--batch-window-ms needs --send-mode jito"));
+            }
+            if *bundle_split && (*send_mode != SendMode::Jito || *version != 1) {
+                return Err(anyhow!(
+                    "--bundle-split needs --send-mode jito and --version 1"
+                ));
+            }
+            let layout = InstructionLayout {
+                tip: *tip_placement,
+                token_ledger: *include_token_ledger,
+                other_instructions: *include_other_instructions,
+            };
+            if layout != InstructionLayout::default() && *version != 1 {
+                return Err(anyhow!(
+                    "--tip-placement, --include-token-ledger and --include-other-instructions need --version 1"
+                ));
+            }
+            let swap_build = match *version {
+                1 => SwapBuild::Instructions,
+                2 => SwapBuild::Transaction,
+                _ => return Err(anyhow!("--version must be 1 or 2, got {}", version)),
+            };
+            let presimulate = presimulate.then_some(*compute_unit_margin_pct);
+            let priority_fee = PriorityFeeConfig {
+                percentile: *priority_fee_percentile,
+                max_compute_unit_price: *max_compute_unit_price,
+            };
+            breaker::configure(BreakerConfig {
+                threshold: *breaker_threshold,
+                cooldown: Duration::from_millis(*breaker_cooldown_ms),
+                ..Default::default()
+            });
+            match *fresh_state {
+                true => info!("--fresh-state: not restoring {}", state_file.display()),
+                false => state::load(state_file),
+            }
+            tokio::spawn(state::run_save(state_file.clone()));
+            tokio::spawn(state::save_on_shutdown(state_file.clone()));
+
+            if let Some(pct) = amount_pct {
+                if *pct <= 0.0 || *pct > 100.0 {
+                    return Err(anyhow!("--amount-pct must be in (0, 100], got {}", pct));
+                }
+            }
+            let fee_bps = match quote_args.platform_fee_bps {
+                Some(fee_bps) => fee_bps,
+                None => partner_fee_bps(*partner_fee)?,
+            };
+            let mut quote_options = quote_args.to_options(0, Some(true));
+            quote_options.platform_fee_bps = (fee_bps > 0).then_some(fee_bps);
+            // the round trip ends in the base mint, that's where the fee is taken
+            let referral = quote_args.referral(&rpc_client, fee_bps, base_mint)?;
+            // per-mint transfer fee, loaded the first time a mint is scheduled
+            let mut transfer_fees: HashMap<Pubkey, Option<TransferFee>> = HashMap::new();
+            // per-mint venues for --discover-pools
+            let mut pool_dexes: HashMap<Pubkey, Dex> = HashMap::new();
+            let dexes = dexes.clone().unwrap_or_else(|| Dex::ALL.into());
+            let buy_dexes = buy_dexes.clone().unwrap_or_else(|| dexes.clone());
+            let sell_dexes = sell_dexes.clone().unwrap_or(dexes);
+            transfer_fees.insert(*mint, load_transfer_fee(&rpc_client, mint)?);
+            let sizing = amount_pct.map(|pct| PositionSizing {
+                pct,
+                reserve: ui_amount_to_amount(*reserve, 9),
+                granularity: *amount_granularity,
+                min: ui_amount_to_amount(*amount_min, 9),
+                max: amount_max.map(|max| ui_amount_to_amount(max, 9)),
+            });
+            let reserve_lamports = ui_amount_to_amount(*reserve, 9);
+
+            // a fat-fingered mint would otherwise scan silently forever, only
+            // a missing route aborts, a jupiter hiccup is retried by the loop
+            let round_trip = arb::RoundTrip {
+                base_mint,
+                mint,
+                amount_in: ui_amount_to_amount(ROUTE_PROBE_UI_AMOUNT, base_decimals),
+                buy_dexes: &buy_dexes,
+                sell_dexes: &sell_dexes,
+                transfer_fee: None,
+            };
+            match arb::probe_route(
+                &jupiter_swap_api_client,
+                jupiter_extra_args.clone(),
+                &round_trip,
+                &quote_options,
+            )
+            .await
+            {
+                Ok(()) => debug!("route probe: {} is tradeable", mint),
+                Err(e @ ArbError::NoRoute(_)) => return Err(e.into()),
+                Err(e) => warn!("route probe for {} failed, scanning anyway: {}", mint, e),
+            }
+
+            let engine = Arc::new(
+                ArbEngine::new(EngineConfig {
+                    jupiter_api_url: arb::JUP_QUOTE_API.clone(),
+                    jupiter_extra_args: jupiter_extra_args.clone(),
+                    base_mint: *base_mint,
+                    payers,
+                    balance_source: *balance_source,
+                    balance_ttl: Duration::from_millis(*balance_ttl_ms),
+                    swap_build,
+                    quote_options,
+                    referral,
+                    min_profit_usd: *min_profit_usd,
+                    report_usd: *usd,
+                    send: SendOptions {
+                        send_mode: *send_mode,
+                        broadcast: BroadcastConfig {
+                            skip_preflight: *skip_preflight,
+                            preflight_commitment: commitment.config(),
+                            ..Default::default()
+                        },
+                        wait_for_confirmation: *wait_for_confirmation,
+                        confirmation,
+                    },
+                    priority_fee,
+                    simulate_then_send: *simulate_then_send,
+                    presimulate,
+                    bundle_split: *bundle_split,
+                    layout,
+                    allow_concurrent_per_mint: *allow_concurrent_per_mint,
+                    batch_window: batch_window_ms.map(Duration::from_millis),
+                    tip_schedule,
+                })
+                .await?,
             );
-            let min_profit_lamports = ui_amount_to_amount(*min_profit, 9);
+            let payers = engine.payers().clone();
+            if payers.len() > 1 {
+                info!("Rotating {} payers", payers.len());
+                let payers = payers.clone();
+                let rpc_client = rpc_client.clone();
+                tokio::spawn(async move {
+                    loop {
+                        tokio::time::sleep(Duration::from_secs(300)).await;
+                        payers.log_summary(&rpc_client).await;
+                    }
+                });
+            }
+            if let Some(addr) = *metrics_addr {
+                tokio::spawn(async move {
+                    if let Err(e) = metrics::serve(addr).await {
+                        warn!("Metrics server stopped: {}", e);
+                    }
+                });
+            }
+            if let Some(interval) = *report_interval {
+                tokio::spawn(session::run_report(ReportConfig {
+                    interval,
+                    webhook: notify_webhook.clone(),
+                    notify_interval: *notify_interval,
+                }));
+            }
+            if let Some(threshold) = *low_balance_sol {
+                let wallets = engine.config().payers.iter().map(|p| p.pubkey()).collect();
+                let config = LowBalanceConfig {
+                    threshold: ui_amount_to_amount(threshold, 9),
+                    interval: Duration::from_secs(60),
+                    webhook: notify_webhook.clone(),
+                };
+                tokio::spawn(balance::run_low_balance_alert(wallets, config));
+            }
 
-            // init tip accounts
-            jito::init_tip_accounts().await?;
-            let amount_in_lamports = ui_amount_to_amount(*amount_in, 9);
+            // per-mint settings, unset ones fall back to the flags above
+            let mut mint_overrides: HashMap<Pubkey, MintOverrides> = HashMap::new();
+            let mut static_mints = vec![*mint];
+            if let Some(path) = watchlist_file {
+                for (mint, overrides) in read_watchlist_file(path, *allow_unknown_dexes)? {
+                    static_mints.push(mint);
+                    mint_overrides.insert(mint, overrides);
+                }
+                info!(
+                    "Loaded {} watchlist entries from {}",
+                    mint_overrides.len(),
+                    path.display()
+                );
+            }
+            if *prewarm_atas {
+                let mut failed = HashSet::new();
+                for payer in &engine.config().payers {
+                    let prewarm = token::prewarm_atas(&rpc_client, payer, &static_mints)?;
+                    for (mint, reason) in prewarm.failed {
+                        warn!("Excluding {} from trading: {}", mint, reason);
+                        failed.insert(mint);
+                    }
+                }
+                static_mints.retain(|mint| !failed.contains(mint));
+                if static_mints.is_empty() {
+                    return Err(anyhow!("no mint left to trade after --prewarm-atas"));
+                }
+            }
+            let watchlist = Arc::new(Watchlist::new(static_mints));
+            if let Some(source) = *discover {
+                let config = DiscoveryConfig {
+                    source,
+                    interval: Duration::from_secs(*discover_interval),
+                    top: *discover_top,
+                    min_volume_24h: *discover_min_volume,
+                    denylist: discover_denylist.clone(),
+                };
+                tokio::spawn(discovery::run_discovery(watchlist.clone(), config));
+            }
+            if *auto_wrap {
+                if wsol_min > wsol_target {
+                    return Err(anyhow!("--wsol-min must not exceed --wsol-target"));
+                }
+                let config = AutoWrapConfig {
+                    target: ui_amount_to_amount(*wsol_target, 9),
+                    min: ui_amount_to_amount(*wsol_min, 9),
+                    reserve: ui_amount_to_amount(*reserve, 9),
+                    interval: Duration::from_secs(60),
+                    compute_unit_price: 10_000,
+                };
+                for payer in &engine.config().payers {
+                    tokio::spawn(wsol::run_auto_wrap(payer.clone(), config.clone()));
+                }
+            }
+            if *auto_unwrap {
+                let config = AutoUnwrapConfig {
+                    interval: Duration::from_secs(*unwrap_interval_secs),
+                    compute_unit_price: 10_000,
+                };
+                for payer in &engine.config().payers {
+                    tokio::spawn(wsol::run_auto_unwrap(payer.clone(), config.clone()));
+                }
+            }
+            if let (Some(to), Some(above)) = (sweep_to, sweep_above) {
+                if sweep_keep > above {
+                    return Err(anyhow!("--sweep-keep must not exceed --sweep-above"));
+                }
+                let config = SweepConfig {
+                    to: *to,
+                    above: ui_amount_to_amount(*above, 9),
+                    keep: ui_amount_to_amount(*sweep_keep, 9),
+                    reserve: reserve_lamports,
+                    interval: Duration::from_secs(300),
+                };
+                for payer in &engine.config().payers {
+                    tokio::spawn(sweep::run_sweep(payer.clone(), config.clone()));
+                }
+            }
+
+            let mut triggers = None;
+            if *ws_trigger {
+                let config = TriggerConfig {
+                    ws_urls: trigger::get_ws_endpoints()?,
+                    debounce: Duration::from_millis(*trigger_debounce_ms),
+                    resubscribe_interval: Duration::from_secs(*trigger_refresh_secs),
+                };
+                let (sender, receiver) = mpsc::channel(1024);
+                tokio::spawn(trigger::run_triggers(config, sender));
+                triggers = Some(receiver);
+            }
+            let trigger_refresh = Duration::from_secs(*trigger_refresh_secs);
+            let mut last_full_scan: Option<Instant> = None;
 
+            let mut next_tick = Instant::now();
             loop {
-                let jupiter_swap_api_client = jupiter_swap_api_client.clone();
-                let jupiter_extra_args = jupiter_extra_args.clone();
-                let payer = payer.clone();
-                let mint = *mint;
-                let partner_fee = *partner_fee;
-                let tip_percentage = *tip_percentage;
-                let wait_for_confirmation = *wait_for_confirmation;
-                let version = *version;
-                tokio::spawn(async move {
-                    match version {
-                        1 => {
-                            run_arbitrage(
-                                jupiter_swap_api_client,
-                                jupiter_extra_args,
-                                mint,
-                                amount_in_lamports,
-                                min_profit_lamports,
-                                partner_fee,
-                                tip_percentage,
-                                &payer,
-                                wait_for_confirmation,
-                            )
-                            .await
+                // providers are failing, the trip was logged with its cooldown
+                if let Some(remaining) = breaker::remaining() {
+                    tokio::time::sleep(remaining).await;
+                }
+                let mints = match triggers.as_mut() {
+                    None => watchlist.mints().await,
+                    // a triggered mint is scanned right away, the interval
+                    // only drives full scans while the websocket is down
+                    Some(receiver) => tokio::select! {
+                        Some(mint) = receiver.recv() => vec![mint],
+                        _ = sleep_next_tick(&mut next_tick, interval, *jitter_ms) => {
+                            let fresh = last_full_scan
+                                .is_some_and(|last| last.elapsed() < trigger_refresh);
+                            if trigger::is_connected() && fresh {
+                                continue;
+                            }
+                            last_full_scan = Some(Instant::now());
+                            watchlist.mints().await
                         }
-                        2 => {
-                            run_arbitrage_v2(
-                                jupiter_swap_api_client,
-                                jupiter_extra_args,
-                                mint,
-                                amount_in_lamports,
-                                min_profit_lamports,
-                                partner_fee,
-                                tip_percentage,
-                                &payer,
-                                wait_for_confirmation,
-                            )
-                            .await
+                    },
+                };
+                session::record_iteration();
+                let mut executions = Vec::new();
+                for mint in mints {
+                    // a multi-wallet setup skips wallets below the reserve
+                    let need_balance = sizing.is_some() || payers.len() > 1;
+                    let Some((payer, balance)) = payers
+                        .next(&rpc_client, reserve_lamports, need_balance)
+                        .await
+                    else {
+                        warn!("No payer above --reserve, skipping");
+                        break;
+                    };
+                    let overrides = mint_overrides.get(&mint).cloned().unwrap_or_default();
+                    let amount_in_lamports = match (
+                        overrides.amount_in.or(*amount_in),
+                        &sizing,
+                        balance,
+                    ) {
+                        (Some(amount_in), _, _) => ui_amount_to_amount(amount_in, base_decimals),
+                        (None, Some(sizing), Some(balance)) => {
+                            let amount = sizing.size(balance);
+                            if amount < sizing.min {
+                                warn!(
+                                    "Trade size {} lamports (balance: {}) is below --amount-min, skipping",
+                                    amount, balance
+                                );
+                                continue;
+                            }
+                            amount
                         }
-                        _ => {
-                            warn!("Invalid version number: {}", version);
-                            return;
+                        _ => unreachable!("clap requires amount_in or --amount-pct"),
+                    };
+                    debug!("amount_in: {} lamports", amount_in_lamports);
+                    if !*allow_concurrent_per_mint
+                        && inflight::is_mint_executing(&mint, &payer.pubkey())
+                    {
+                        debug!("{} is still executing, skipping", mint);
+                        continue;
+                    }
+                    let transfer_fee = match transfer_fees.get(&mint) {
+                        Some(transfer_fee) => *transfer_fee,
+                        None => match load_transfer_fee(&rpc_client, &mint) {
+                            Ok(transfer_fee) => *transfer_fees.entry(mint).or_insert(transfer_fee),
+                            Err(e) => {
+                                warn!("Failed to load mint {}, skipping: {}", mint, e);
+                                continue;
+                            }
+                        },
+                    };
+                    let (buy_dexes, sell_dexes) = match overrides.dexes {
+                        Some(dexes) => (dexes.clone(), dexes),
+                        None => (buy_dexes.clone(), sell_dexes.clone()),
+                    };
+                    let (buy_dexes, sell_dexes) = match *discover_pools {
+                        true => {
+                            let pools = *pool_dexes.entry(mint).or_insert_with(|| {
+                                dex::discover_dexes(
+                                    &rpc_client,
+                                    &mint,
+                                    base_mint,
+                                    buy_dexes.dexes | sell_dexes.dexes,
+                                )
+                            });
+                            (buy_dexes.intersect(pools), sell_dexes.intersect(pools))
                         }
+                        false => (buy_dexes, sell_dexes),
+                    };
+                    if buy_dexes.is_empty() || sell_dexes.is_empty() {
+                        debug!("{} has no pool on the buy or sell venues, skipping", mint);
+                        continue;
                     }
-                });
+                    let params = ScanParams {
+                        amount_in: amount_in_lamports,
+                        min_profit: overrides
+                            .min_profit
+                            .map_or(min_profit_lamports, |min_profit| {
+                                ui_amount_to_amount(min_profit, base_decimals)
+                            }),
+                        tip_percentage: overrides.tip_percentage.unwrap_or(*tip_percentage),
+                        buy_dexes,
+                        sell_dexes,
+                        transfer_fee,
+                    };
+                    // a triggered mint is scanned right away
+                    let delay = match *once || triggers.is_some() {
+                        true => Duration::ZERO,
+                        false => scan_jitter(interval, interval_jitter),
+                    };
+                    let engine = engine.clone();
+                    let execution = tokio::spawn(async move {
+                        tokio::time::sleep(delay).await;
+                        engine.run(mint, params, &payer).await
+                    });
+                    if *once {
+                        executions.push(execution);
+                    }
+                }
 
-                tokio::time::sleep(tokio::time::Duration::from_secs(*interval)).await;
+                if *once {
+                    let mut outcomes = Vec::new();
+                    for execution in executions {
+                        outcomes.push(execution.await?);
+                    }
+                    let code = outcome::exit_code(&outcomes);
+                    if let Err(e) = state::write(state_file, &RuntimeState::capture()) {
+                        warn!("Failed to save state to {}: {}", state_file.display(), e);
+                    }
+                    info!(
+                        "--once: {} mints scanned, exit code {}",
+                        outcomes.len(),
+                        code
+                    );
+                    std::process::exit(code);
+                }
+                if triggers.is_none() {
+                    sleep_next_tick(&mut next_tick, interval, *jitter_ms).await;
+                }
             }
         }
     };
     Ok(())
 }
 
-pub async fn run_arbitrage(
-    jupiter_swap_api_client: JupiterSwapApiClient,
-    jupiter_extra_args: Option<HashMap<String, String>>,
-    mint: Pubkey,
-    amount_in_lamports: u64,
-    min_profit_lamports: u64,
-    partner_fee: f64,
-    tip_percentage: f64,
+// decimals of the base mint, a non-SOL base must have a funded token account
+fn base_mint_decimals(rpc_client: &RpcClient, owner: &Pubkey, base_mint: &Pubkey) -> Result<u8> {
+    if *base_mint == spl_token::native_mint::id() {
+        return Ok(spl_token::native_mint::DECIMALS);
+    }
+    let decimals = get_mint(rpc_client, base_mint)?.decimals;
+    let ata = get_ata(owner, base_mint);
+    let balance = rpc_client.get_token_account_balance(&ata).map_err(|e| {
+        anyhow!(
+            "no token account {} for base mint {}: {}",
+            ata,
+            base_mint,
+            e
+        )
+    })?;
+    if balance.amount.parse::<u64>()? == 0 {
+        return Err(anyhow!(
+            "token account {} for base mint {} is empty",
+            ata,
+            base_mint
+        ));
+    }
+    info!(
+        "base mint: {}, balance: {}",
+        base_mint, balance.ui_amount_string
+    );
+    Ok(decimals)
+}
+
+// create missing referral fee accounts in batches, then print every
+// account with its balance
+fn setup_referral_token_accounts(
+    rpc_client: &RpcClient,
     payer: &Keypair,
-    wait_for_confirmation: bool,
-) {
-    let execution_id = uuid::Uuid::new_v4();
-
-    let rpc_client = match get_rpc_client() {
-        Ok(client) => client,
-        Err(e) => {
-            warn!("[{}] Failed to get RPC client: {}", execution_id, e);
-            return;
-        }
-    };
+    referral_account: &Pubkey,
+    mints: &[Pubkey],
+) -> Result<()> {
+    let account = rpc_client.get_account(referral_account)?;
+    if account.owner != referral::REFERRAL_PROGRAM_ID {
+        return Err(anyhow!("{} is not a referral account", referral_account));
+    }
+    let project = referral::get_project(&account.data)?;
 
-    let start_time = Instant::now();
-    match arb::caculate_profit(
-        &jupiter_swap_api_client,
-        jupiter_extra_args.clone(),
-        &amount_in_lamports,
-        &spl_token::native_mint::id(),
-        &mint,
-        Dex::ALL,
-        partner_fee,
-    )
-    .await
-    {
-        Ok((profit, quote_buy_response, quote_sell_response)) => {
-            let quote_time = start_time.elapsed();
-            let profit_ui_amount = if profit < 0 {
-                -1.0 * amount_to_ui_amount(profit.abs() as u64, 9)
-            } else {
-                amount_to_ui_amount(profit as u64, 9)
-            };
+    let fee_accounts: Vec<Pubkey> = mints
+        .iter()
+        .map(|mint| referral::referral_token_account(referral_account, mint))
+        .collect();
+    let existing = rpc_client.get_multiple_accounts(&fee_accounts)?;
+    let missing: Vec<Pubkey> = mints
+        .iter()
+        .zip(&existing)
+        .filter(|(_, account)| account.is_none())
+        .map(|(mint, _)| *mint)
+        .collect();
+    info!(
+        "{} of {} referral token accounts need initializing",
+        missing.len(),
+        mints.len()
+    );
 
-            if profit < min_profit_lamports as i64 {
-                debug!(
-                    "[{}] ⏭️ Skip: {}, Profit: {} sol too small",
-                    execution_id, mint, profit_ui_amount,
-                );
-            } else {
-                info!(
-                    "[{}] 💰 Found opportunity: {}, Profit: {} sol",
-                    execution_id, mint, profit_ui_amount
-                );
-                match async {
-                    let tip_lamports = ((profit as u64) as f64 * tip_percentage.min(1.0)) as u64;
-                    let tip_account = jito::get_tip_account().await?;
-                    let tip_instruction =
-                        tx::get_tip_instruction(&payer.pubkey(), &tip_account, tip_lamports);
-
-                    let quote_response = arb::merge_quotes(
-                        quote_buy_response,
-                        quote_sell_response,
-                        amount_in_lamports,
-                        tip_lamports,
-                    );
+    for batch in missing.chunks(5) {
+        let mut ixs = vec![];
+        for (mint, mint_account) in batch.iter().zip(rpc_client.get_multiple_accounts(batch)?) {
+            let Some(mint_account) = mint_account else {
+                warn!("Mint {} not found, skipping", mint);
+                continue;
+            };
+            ixs.push(referral::initialize_referral_token_account(
+                &payer.pubkey(),
+                &project,
+                referral_account,
+                mint,
+                &mint_account.owner,
+            ));
+        }
+        if ixs.is_empty() {
+            continue;
+        }
+        let tx = Transaction::new_signed_with_payer(
+            &ixs,
+            Some(&payer.pubkey()),
+            &[payer],
+            rpc_client.get_latest_blockhash()?,
+        );
+        match rpc_client.send_and_confirm_transaction(&tx) {
+            Ok(signature) => info!("Initialized {} accounts: {}", ixs.len(), signature),
+            Err(e) => warn!("Failed to initialize batch {:?}: {}", batch, e),
+        }
+    }
 
-                    debug!(
-                        "[{}] out_amount: {}, other_amount_threshold: {}",
-                        execution_id,
-                        quote_response.out_amount,
-                        quote_response.other_amount_threshold
-                    );
+    for (mint, fee_account) in mints.iter().zip(&fee_accounts) {
+        match rpc_client.get_token_account_balance(fee_account) {
+            Ok(balance) => println!(
+                "{} {} balance: {}",
+                mint, fee_account, balance.ui_amount_string
+            ),
+            Err(_) => println!("{} {} not initialized", mint, fee_account),
+        }
+    }
+    Ok(())
+}
 
-                    let mut tx_config = TransactionConfig::default();
-                    tx_config.dynamic_compute_unit_limit = true;
-                    tx_config.use_shared_accounts = Some(false);
-
-                    let start_swap = Instant::now();
-                    let swap_instructions_response = arb::swap_instructions(
-                        &jupiter_swap_api_client,
-                        jupiter_extra_args,
-                        &payer.pubkey(),
-                        &quote_response,
-                    )
-                    .await?;
-                    let swap_time = start_swap.elapsed();
-
-                    let mut ixs = arb::build_instructions(
-                        swap_instructions_response.clone(),
-                        tip_instruction,
-                    );
+fn print_stats(report: &StatsReport) {
+    let total = &report.total;
+    println!("attempts: {}", total.attempts);
+    println!("executions: {}", total.executions);
+    println!(
+        "landed: {} ({}), failed: {}",
+        total.landed,
+        format_land_rate(total.land_rate),
+        total.failed
+    );
+    println!(
+        "gross quoted profit: {} sol",
+        format_sol_signed(total.gross_profit)
+    );
+    println!("tips paid: {} sol", amount_to_ui_amount(total.tips_paid, 9));
+    if let Some(average_tip) = total.average_tip {
+        println!("average tip: {} sol", amount_to_ui_amount(average_tip, 9));
+    }
+    println!(
+        "realized profit: {} sol",
+        format_sol_signed(total.realized_profit)
+    );
+    if let (Some(best), Some(worst)) = (total.best, total.worst) {
+        println!(
+            "best: {} sol, worst: {} sol",
+            format_sol_signed(best),
+            format_sol_signed(worst)
+        );
+    }
+    if report.skipped > 0 {
+        println!("skipped {} malformed records", report.skipped);
+    }
+    print_stats_table("mint", &report.by_mint);
+    print_stats_table("send mode", &report.by_send_mode);
+}
 
-                    // println!("ixs: {:#?}", ixs);
-                    let start_create_tx = Instant::now();
-                    let versioned_transaction = create_tx_with_address_table_lookup(
-                        &rpc_client,
-                        &mut ixs,
-                        &swap_instructions_response.address_lookup_table_addresses,
-                        &payer,
-                    )?;
-                    let create_tx_time = start_create_tx.elapsed();
-
-
-                    let start_send_tx = Instant::now();
-                    let result = tx::send_versioned_transaction(
-                        &rpc_client,
-                        &payer,
-                        versioned_transaction,
-                        None,
-                        wait_for_confirmation,
-                    )
-                    .await;
-                    let send_tx_time = start_send_tx.elapsed();
-                    info!("[{}] 🕒 Timings({:?}): quote_time={:?}, swap_time={:?}, create_tx={:?}, send_tx={:?}",
-                        execution_id, start_time.elapsed(), quote_time, swap_time, create_tx_time, send_tx_time);
-                    result
-                }
-                .await
-                {
-                    Ok(_) => info!("[{}] 🚀 Arbitrage executed successfully", execution_id),
-                    Err(e) => warn!("[{}] ⚠️ Failed to execute arbitrage: {}", execution_id, e),
-                }
-            }
-        }
-        Err(e) => {
-            info!("Error calculating profit: {}", e);
-        }
+fn print_stats_table(label: &str, rows: &BTreeMap<String, TradeStats>) {
+    if rows.is_empty() {
+        return;
+    }
+    println!();
+    println!(
+        "{:<44} {:>8} {:>6} {:>6} {:>6} {:>14} {:>14}",
+        label, "attempts", "execs", "landed", "rate", "tips", "realized"
+    );
+    for (key, stats) in rows {
+        println!(
+            "{:<44} {:>8} {:>6} {:>6} {:>6} {:>14} {:>14}",
+            key,
+            stats.attempts,
+            stats.executions,
+            stats.landed,
+            format_land_rate(stats.land_rate),
+            amount_to_ui_amount(stats.tips_paid, 9),
+            format_sol_signed(stats.realized_profit)
+        );
     }
 }
 
-pub async fn run_arbitrage_v2(
-    jupiter_swap_api_client: JupiterSwapApiClient,
-    jupiter_extra_args: Option<HashMap<String, String>>,
-    mint: Pubkey,
-    amount_in_lamports: u64,
-    min_profit_lamports: u64,
-    partner_fee: f64,
-    tip_percentage: f64,
-    payer: &Keypair,
-    wait_for_confirmation: bool,
-) {
-    let execution_id = uuid::Uuid::new_v4();
-
-    let rpc_client = match get_rpc_client() {
-        Ok(client) => client,
-        Err(e) => {
-            warn!("[{}] Failed to get RPC client: {}", execution_id, e);
-            return;
-        }
-    };
+fn format_land_rate(land_rate: Option<f64>) -> String {
+    land_rate
+        .map(|rate| format!("{:.1}%", rate * 100.0))
+        .unwrap_or("-".to_string())
+}
 
-    let start_time = Instant::now();
-    match arb::caculate_profit(
-        &jupiter_swap_api_client,
-        jupiter_extra_args.clone(),
-        &amount_in_lamports,
-        &spl_token::native_mint::id(),
-        &mint,
-        Dex::ALL,
-        partner_fee,
-    )
-    .await
-    {
-        Ok((profit, quote_buy_response, quote_sell_response)) => {
-            let quote_time = start_time.elapsed();
-            let profit_ui_amount = if profit < 0 {
-                -1.0 * amount_to_ui_amount(profit.abs() as u64, 9)
-            } else {
-                amount_to_ui_amount(profit as u64, 9)
-            };
+// base mint ui amount quoted by the startup route probe
+const ROUTE_PROBE_UI_AMOUNT: f64 = 0.01;
 
-            if profit < min_profit_lamports as i64 {
-                debug!(
-                    "[{}] ⏭️ Skip: {}, Profit: {} sol too small",
-                    execution_id, mint, profit_ui_amount,
-                );
-            } else {
-                info!(
-                    "[{}] 💰 Found opportunity: {}, Profit: {} sol",
-                    execution_id, mint, profit_ui_amount
-                );
-                match async {
-                    let tip_lamports = ((profit as u64) as f64 * tip_percentage.min(1.0)) as u64;
-                    let tip_account = jito::get_tip_account().await?;
-
-                    let quote_response = arb::merge_quotes(
-                        quote_buy_response,
-                        quote_sell_response,
-                        amount_in_lamports,
-                        tip_lamports,
-                    );
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
 
-                    debug!(
-                        "[{}] out_amount: {}, other_amount_threshold: {}",
-                        execution_id,
-                        quote_response.out_amount,
-                        quote_response.other_amount_threshold
-                    );
+fn load_transfer_fee(rpc_client: &RpcClient, mint: &Pubkey) -> Result<Option<TransferFee>> {
+    let mint_info = get_mint(rpc_client, mint)?;
+    if mint_info.transfer_fee_config.is_none() {
+        return Ok(None);
+    }
+    let epoch = rpc_client.get_epoch_info()?.epoch;
+    let transfer_fee = mint_info.transfer_fee(epoch);
+    if let Some(transfer_fee) = &transfer_fee {
+        info!(
+            "Transfer fee detected on {}: {} bps, max {}",
+            mint,
+            u16::from(transfer_fee.transfer_fee_basis_points),
+            u64::from(transfer_fee.maximum_fee)
+        );
+    }
+    Ok(transfer_fee)
+}
 
-                    let mut tx_config = TransactionConfig::default();
-                    tx_config.dynamic_compute_unit_limit = true;
-                    tx_config.use_shared_accounts = Some(false);
-
-                    let start_swap = Instant::now();
-                    let versioned_transaction = arb::swap(
-                        &jupiter_swap_api_client,
-                        jupiter_extra_args,
-                        &payer.pubkey(),
-                        &quote_response,
-                    )
-                    .await?;
-                    let swap_time = start_swap.elapsed();
-
-                    let start_send_tx = Instant::now();
-                    let result = tx::send_versioned_transaction(
-                        &rpc_client,
-                        &payer,
-                        versioned_transaction,
-                        Some((tip_account, tip_lamports)),
-                        wait_for_confirmation,
-                    )
-                    .await;
-                    let send_tx_time = start_send_tx.elapsed();
-                    info!(
-                        "[{}] 🕒 Timings({:?}): quote_time={:?}, swap_time={:?}, send_tx={:?}",
-                        execution_id,
-                        start_time.elapsed(),
-                        quote_time,
-                        swap_time,
-                        send_tx_time
-                    );
-                    result
-                }
-                .await
-                {
-                    Ok(_) => info!("[{}] 🚀 Arbitrage executed successfully", execution_id),
-                    Err(e) => warn!("[{}] ⚠️ Failed to execute arbitrage: {}", execution_id, e),
-                }
-            }
-        }
-        Err(e) => {
-            info!("Error calculating profit: {}", e);
-        }
+// fixed-rate: the next tick is computed from the previous one, jitter is
+// only added to the sleep so it doesn't accumulate
+async fn sleep_next_tick(next_tick: &mut Instant, interval: Duration, jitter_ms: u64) {
+    *next_tick += interval;
+    let now = Instant::now();
+    if *next_tick < now {
+        // fell behind, don't burst to catch up
+        *next_tick = now;
+    }
+    let jitter = if jitter_ms > 0 {
+        Duration::from_millis(rand::thread_rng().gen_range(0..=jitter_ms))
+    } else {
+        Duration::ZERO
+    };
+    tokio::time::sleep_until(*next_tick + jitter).await;
+}
+
+// delay of one mint's scan within the tick, uniform in [0, jitter) of the
+// interval so the time between two scans of a mint stays within ±jitter of
+// it and the average rate is unchanged
+fn scan_jitter(interval: Duration, jitter: f64) -> Duration {
+    if jitter <= 0.0 {
+        return Duration::ZERO;
     }
+    interval.mul_f64(rand::thread_rng().gen_range(0.0..jitter))
 }
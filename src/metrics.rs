@@ -0,0 +1,178 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    net::SocketAddr,
+    sync::{LazyLock, Mutex},
+};
+
+use anyhow::Result;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+use tracing::{debug, info};
+
+// seconds
+const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            counts: vec![0; DEFAULT_BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (i, bound) in DEFAULT_BUCKETS.iter().enumerate() {
+            if value <= *bound {
+                self.counts[i] += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    counters: BTreeMap<(String, String), u64>,
+    gauges: BTreeMap<(String, String), f64>,
+    histograms: BTreeMap<(String, String), Histogram>,
+}
+
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(|| Mutex::new(Registry::default()));
+
+fn labels_key(labels: &[(&str, &str)]) -> String {
+    labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, v))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn inc_counter(name: &str, labels: &[(&str, &str)]) {
+    add_counter(name, labels, 1);
+}
+
+pub fn add_counter(name: &str, labels: &[(&str, &str)], value: u64) {
+    let mut registry = REGISTRY.lock().unwrap();
+    *registry
+        .counters
+        .entry((name.to_string(), labels_key(labels)))
+        .or_default() += value;
+}
+
+pub fn set_gauge(name: &str, labels: &[(&str, &str)], value: f64) {
+    let mut registry = REGISTRY.lock().unwrap();
+    registry
+        .gauges
+        .insert((name.to_string(), labels_key(labels)), value);
+}
+
+pub fn observe(name: &str, labels: &[(&str, &str)], value: f64) {
+    let mut registry = REGISTRY.lock().unwrap();
+    registry
+        .histograms
+        .entry((name.to_string(), labels_key(labels)))
+        .or_insert_with(Histogram::new)
+        .observe(value);
+}
+
+fn with_labels(name: &str, labels: &str, extra: Option<String>) -> String {
+    let all = match (labels.is_empty(), extra) {
+        (true, None) => return name.to_string(),
+        (true, Some(extra)) => extra,
+        (false, None) => labels.to_string(),
+        (false, Some(extra)) => format!("{},{}", labels, extra),
+    };
+    format!("{}{{{}}}", name, all)
+}
+
+/// Render all metrics in the prometheus text format
+pub fn render() -> String {
+    let registry = REGISTRY.lock().unwrap();
+    let mut out = String::new();
+
+    for ((name, labels), value) in &registry.counters {
+        let _ = writeln!(out, "{} {}", with_labels(name, labels, None), value);
+    }
+    for ((name, labels), value) in &registry.gauges {
+        let _ = writeln!(out, "{} {}", with_labels(name, labels, None), value);
+    }
+    for ((name, labels), histogram) in &registry.histograms {
+        for (bound, count) in DEFAULT_BUCKETS.iter().zip(histogram.counts.iter()) {
+            let bucket = format!("{}_bucket", name);
+            let le = Some(format!("le=\"{}\"", bound));
+            let _ = writeln!(out, "{} {}", with_labels(&bucket, labels, le), count);
+        }
+        let bucket = format!("{}_bucket", name);
+        let inf = Some("le=\"+Inf\"".to_string());
+        let _ = writeln!(
+            out,
+            "{} {}",
+            with_labels(&bucket, labels, inf),
+            histogram.count
+        );
+        let sum = format!("{}_sum", name);
+        let _ = writeln!(out, "{} {}", with_labels(&sum, labels, None), histogram.sum);
+        let count = format!("{}_count", name);
+        let _ = writeln!(
+            out,
+            "{} {}",
+            with_labels(&count, labels, None),
+            histogram.count
+        );
+    }
+    out
+}
+
+/// Serve the metrics over plain http, every path returns the same body
+pub async fn serve(addr: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Serving metrics on http://{}/metrics", addr);
+
+    loop {
+        let (mut stream, peer) = listener.accept().await?;
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).await;
+            let body = render();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                debug!("metrics: failed to write response to {}: {}", peer, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inc_counter, observe, render};
+
+    #[test]
+    fn test_render() {
+        inc_counter("test_render_total", &[("mint", "abc")]);
+        observe("test_render_seconds", &[("stage", "quote")], 0.02);
+
+        let out = render();
+        assert!(out.contains("test_render_total{mint=\"abc\"} 1"));
+        assert!(out.contains("test_render_seconds_bucket{stage=\"quote\",le=\"0.01\"} 0"));
+        assert!(out.contains("test_render_seconds_bucket{stage=\"quote\",le=\"0.025\"} 1"));
+        assert!(out.contains("test_render_seconds_count{stage=\"quote\"} 1"));
+    }
+}
@@ -0,0 +1,21 @@
+use serde::Serialize;
+
+/// Post `text` to a slack style webhook, `details` goes along as json under
+/// `key` for anything that isn't a chat
+pub async fn post(
+    client: &reqwest::Client,
+    webhook: &str,
+    text: String,
+    key: &str,
+    details: &impl Serialize,
+) -> reqwest::Result<()> {
+    let mut body = serde_json::json!({ "text": text });
+    body[key] = serde_json::json!(details);
+    client
+        .post(webhook)
+        .json(&body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
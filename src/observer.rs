@@ -0,0 +1,79 @@
+use jupiter_swap_api_client::quote::QuoteResponse;
+use solana_sdk::pubkey::Pubkey;
+use spl_token::amount_to_ui_amount;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{amount::lamports_to_ui_signed, arb::route_summary, error::ArbError};
+
+/// A profitable round trip found by a scan
+#[derive(Debug, Clone)]
+pub struct Opportunity {
+    pub execution_id: Uuid,
+    pub mint: Pubkey,
+    pub amount_in: u64,
+    pub profit: i64,
+    // the tip in base units, and in lamports as paid to jito, after
+    // TIP_POLICY clamps it
+    pub tip_amount: u64,
+    pub tip_lamports: u64,
+    // rent of the mint's token account counted against the profit
+    pub rent: u64,
+    pub quote_buy_response: QuoteResponse,
+    pub quote_sell_response: QuoteResponse,
+}
+
+/// Hooks into the arbitrage lifecycle, all methods default to no-op
+pub trait ArbObserver: Send + Sync {
+    /// Called when an opportunity passes the profit threshold.
+    /// Returning false vetoes the execution.
+    fn on_opportunity(&self, _opportunity: &Opportunity) -> bool {
+        true
+    }
+
+    fn on_submitted(&self, _opportunity: &Opportunity, _bundle_id: &str) {}
+
+    /// `signatures` is empty unless confirmation was awaited
+    fn on_confirmed(&self, _opportunity: &Opportunity, _signatures: &[String]) {}
+
+    fn on_skipped(&self, _execution_id: &Uuid, _mint: &Pubkey, _profit: i64) {}
+
+    fn on_failed(&self, _opportunity: &Opportunity, _err: &ArbError) {}
+}
+
+/// Default observer, logs every event
+pub struct LogObserver;
+
+impl ArbObserver for LogObserver {
+    fn on_opportunity(&self, opportunity: &Opportunity) -> bool {
+        info!(
+            "💰 Found opportunity: {}, Amount in: {} sol, Profit: {} sol, Buy: {}, Sell: {}",
+            opportunity.mint,
+            amount_to_ui_amount(opportunity.amount_in, 9),
+            lamports_to_ui_signed(opportunity.profit, 9),
+            route_summary(&opportunity.quote_buy_response),
+            route_summary(&opportunity.quote_sell_response)
+        );
+        true
+    }
+
+    fn on_submitted(&self, opportunity: &Opportunity, bundle_id: &str) {
+        debug!("📦 Submitted bundle: {}", bundle_id);
+    }
+
+    fn on_confirmed(&self, opportunity: &Opportunity, _signatures: &[String]) {
+        info!("🚀 Arbitrage executed successfully");
+    }
+
+    fn on_skipped(&self, _execution_id: &Uuid, mint: &Pubkey, profit: i64) {
+        debug!(
+            "⏭️ Skip: {}, Profit: {} sol too small",
+            mint,
+            lamports_to_ui_signed(profit, 9),
+        );
+    }
+
+    fn on_failed(&self, opportunity: &Opportunity, err: &ArbError) {
+        warn!("⚠️ Failed to execute arbitrage: {}", err);
+    }
+}
@@ -0,0 +1,172 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use uuid::Uuid;
+
+use crate::{latency::LatencyBreakdown, tx::SendMode};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ArbStatus {
+    QuoteFailed {
+        error: String,
+    },
+    Skipped,
+    Vetoed,
+    Submitted {
+        bundle_id: String,
+    },
+    Confirmed {
+        bundle_id: Option<String>,
+        signatures: Vec<String>,
+    },
+    // TX_SIMULATE: the transactions simulated clean and were never sent
+    Simulated,
+    Failed {
+        error: String,
+    },
+}
+
+impl ArbStatus {
+    /// Process exit code for `arb --once`: 0 = landed, 2 = no opportunity,
+    /// 3 = submitted but not confirmed, 1 = error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ArbStatus::Confirmed { .. } | ArbStatus::Simulated => 0,
+            ArbStatus::Submitted { .. } => 3,
+            ArbStatus::Skipped | ArbStatus::Vetoed => 2,
+            ArbStatus::QuoteFailed { .. } | ArbStatus::Failed { .. } => 1,
+        }
+    }
+}
+
+/// Exit code of a scan over several mints, any error wins over an
+/// execution, which wins over an unconfirmed submission, which wins over
+/// no opportunity
+pub fn exit_code(outcomes: &[ArbOutcome]) -> i32 {
+    let codes: Vec<i32> = outcomes.iter().map(|o| o.status.exit_code()).collect();
+    [1, 0, 3]
+        .into_iter()
+        .find(|code| codes.contains(code))
+        .unwrap_or(2)
+}
+
+/// Structured result of a single arbitrage execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArbOutcome {
+    // unix timestamp in milliseconds
+    pub timestamp: u64,
+    pub execution_id: Uuid,
+    #[serde(with = "pubkey_str")]
+    pub mint: Pubkey,
+    pub amount_in: u64,
+    pub profit: Option<i64>,
+    pub profit_usd: Option<f64>,
+    pub tip_lamports: Option<u64>,
+    // the same tip in base mint units, what profit is netted against
+    #[serde(default)]
+    pub tip_amount: Option<u64>,
+    // slot before submission and the one the transactions landed in
+    #[serde(default)]
+    pub submit_slot: Option<u64>,
+    #[serde(default)]
+    pub landed_slot: Option<u64>,
+    // arb::route_summary_full of each leg
+    #[serde(default)]
+    pub buy_route: Option<String>,
+    #[serde(default)]
+    pub sell_route: Option<String>,
+    #[serde(default)]
+    pub send_mode: Option<SendMode>,
+    #[serde(flatten)]
+    pub status: ArbStatus,
+    pub latency: LatencyBreakdown,
+}
+
+impl ArbOutcome {
+    pub fn new(execution_id: Uuid, mint: Pubkey, amount_in: u64) -> Self {
+        Self {
+            timestamp: now_millis(),
+            execution_id,
+            mint,
+            amount_in,
+            profit: None,
+            profit_usd: None,
+            tip_lamports: None,
+            tip_amount: None,
+            submit_slot: None,
+            landed_slot: None,
+            buy_route: None,
+            sell_route: None,
+            send_mode: None,
+            status: ArbStatus::Skipped,
+            latency: LatencyBreakdown::default(),
+        }
+    }
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+pub(crate) mod pubkey_str {
+    use std::str::FromStr;
+
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+    use solana_sdk::pubkey::Pubkey;
+
+    pub fn serialize<S: Serializer>(value: &Pubkey, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Pubkey, D::Error> {
+        let s = String::deserialize(d)?;
+        Pubkey::from_str(&s).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+    use uuid::Uuid;
+
+    use super::{ArbOutcome, ArbStatus, exit_code};
+
+    fn outcome(status: ArbStatus) -> ArbOutcome {
+        let mut outcome = ArbOutcome::new(Uuid::new_v4(), Pubkey::new_unique(), 1);
+        outcome.status = status;
+        outcome
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let confirmed = ArbStatus::Confirmed {
+            bundle_id: None,
+            signatures: vec![],
+        };
+        let submitted = ArbStatus::Submitted {
+            bundle_id: "b".to_string(),
+        };
+        let failed = ArbStatus::Failed {
+            error: "boom".to_string(),
+        };
+        assert_eq!(exit_code(&[]), 2);
+        assert_eq!(exit_code(&[outcome(ArbStatus::Skipped)]), 2);
+        assert_eq!(
+            exit_code(&[outcome(ArbStatus::Skipped), outcome(confirmed.clone())]),
+            0
+        );
+        assert_eq!(exit_code(&[outcome(ArbStatus::Simulated)]), 0);
+        // a submission nobody waited on isn't reported as executed
+        assert_eq!(exit_code(&[outcome(submitted.clone())]), 3);
+        assert_eq!(
+            exit_code(&[outcome(submitted), outcome(confirmed.clone())]),
+            0
+        );
+        assert_eq!(exit_code(&[outcome(confirmed), outcome(failed)]), 1);
+    }
+}
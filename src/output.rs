@@ -0,0 +1,131 @@
+use clap::ValueEnum;
+use jupiter_swap_api_client::quote::QuoteResponse;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use spl_token::amount_to_ui_amount;
+
+use crate::{amount::lamports_to_ui_signed, arb::BreakEven, outcome::pubkey_str};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Human,
+    /// One serde serialized result per command on stdout
+    Json,
+}
+
+/// Round trip quote without executing it
+#[derive(Debug, Clone, Serialize)]
+pub struct QuoteResult {
+    #[serde(with = "pubkey_str")]
+    pub mint: Pubkey,
+    pub amount_in: u64,
+    pub profit_lamports: i64,
+    pub profit_sol: f64,
+    pub buy_out_amount: u64,
+    pub sell_out_amount: u64,
+    pub buy_route: Vec<String>,
+    pub sell_route: Vec<String>,
+    pub context_slot: u64,
+}
+
+impl QuoteResult {
+    pub fn new(
+        mint: Pubkey,
+        amount_in: u64,
+        profit: i64,
+        quote_buy_response: &QuoteResponse,
+        quote_sell_response: &QuoteResponse,
+    ) -> Self {
+        Self {
+            mint,
+            amount_in,
+            profit_lamports: profit,
+            profit_sol: lamports_to_ui_signed(profit, 9),
+            buy_out_amount: quote_buy_response.out_amount,
+            sell_out_amount: quote_sell_response.out_amount,
+            buy_route: route_labels(quote_buy_response),
+            sell_route: route_labels(quote_sell_response),
+            context_slot: quote_sell_response.context_slot,
+        }
+    }
+}
+
+/// Largest tip a round trip can afford
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakEvenResult {
+    #[serde(with = "pubkey_str")]
+    pub mint: Pubkey,
+    pub amount_in: u64,
+    pub gross_profit_lamports: i64,
+    pub fees_lamports: u64,
+    pub break_even_tip_lamports: u64,
+    pub break_even_tip_sol: f64,
+    pub buy_route: Vec<String>,
+    pub sell_route: Vec<String>,
+}
+
+impl BreakEvenResult {
+    pub fn new(
+        mint: Pubkey,
+        amount_in: u64,
+        break_even: &BreakEven,
+        quote_buy_response: &QuoteResponse,
+        quote_sell_response: &QuoteResponse,
+    ) -> Self {
+        Self {
+            mint,
+            amount_in,
+            gross_profit_lamports: break_even.gross_profit,
+            fees_lamports: break_even.fees,
+            break_even_tip_lamports: break_even.max_tip,
+            break_even_tip_sol: amount_to_ui_amount(break_even.max_tip, 9),
+            buy_route: route_labels(quote_buy_response),
+            sell_route: route_labels(quote_sell_response),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceResult {
+    #[serde(with = "pubkey_str")]
+    pub owner: Pubkey,
+    pub sol_lamports: u64,
+    pub sol: f64,
+    pub wsol_lamports: u64,
+    pub wsol: f64,
+}
+
+impl BalanceResult {
+    pub fn new(owner: Pubkey, sol_lamports: u64, wsol_lamports: u64) -> Self {
+        Self {
+            owner,
+            sol_lamports,
+            sol: amount_to_ui_amount(sol_lamports, 9),
+            wsol_lamports,
+            wsol: amount_to_ui_amount(wsol_lamports, 9),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BundleResult {
+    pub bundle_id: String,
+    pub signatures: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TipInfoResult {
+    pub tip_accounts: Vec<String>,
+    // (percentile, lamports)
+    pub tip_floor: Vec<(String, u64)>,
+    // lamports the bot would tip for --profit
+    pub tip_for_profit: Option<u64>,
+}
+
+pub fn route_labels(quote_response: &QuoteResponse) -> Vec<String> {
+    quote_response
+        .route_plan
+        .iter()
+        .map(|route| route.swap_info.label.clone())
+        .collect()
+}
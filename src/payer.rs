@@ -0,0 +1,132 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use tracing::{info, warn};
+
+use crate::{
+    balance::{BalanceSource, BalanceTracker},
+    metrics,
+    outcome::{ArbOutcome, ArbStatus},
+};
+
+struct Payer {
+    keypair: Arc<Keypair>,
+    balance: BalanceTracker,
+    executions: AtomicU64,
+    profit: AtomicI64,
+}
+
+/// Payers taking turns executing opportunities
+pub struct PayerPool {
+    payers: Vec<Payer>,
+    cursor: AtomicUsize,
+}
+
+impl PayerPool {
+    pub fn new(keypairs: Vec<Arc<Keypair>>, source: BalanceSource, balance_ttl: Duration) -> Self {
+        let payers = keypairs
+            .into_iter()
+            .map(|keypair| Payer {
+                balance: BalanceTracker::new(keypair.pubkey(), source, balance_ttl),
+                keypair,
+                executions: AtomicU64::new(0),
+                profit: AtomicI64::new(0),
+            })
+            .collect();
+        Self {
+            payers,
+            cursor: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.payers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.payers.is_empty()
+    }
+
+    /// Next payer in turn whose balance is at least `min_balance`, with
+    /// that balance. A single payer is returned without a balance lookup
+    /// unless `need_balance` is set.
+    pub async fn next(
+        &self,
+        client: &RpcClient,
+        min_balance: u64,
+        need_balance: bool,
+    ) -> Option<(Arc<Keypair>, Option<u64>)> {
+        if self.payers.len() == 1 && !need_balance {
+            return Some((self.payers[0].keypair.clone(), None));
+        }
+        let start = self.cursor.fetch_add(1, Ordering::Relaxed);
+        for i in 0..self.payers.len() {
+            let payer = &self.payers[(start + i) % self.payers.len()];
+            let wallet = payer.keypair.pubkey();
+            let balance = match payer.balance.get(client).await {
+                Ok(balance) => balance,
+                Err(e) => {
+                    warn!("Failed to fetch balance of payer {}: {}", wallet, e);
+                    continue;
+                }
+            };
+            metrics::set_gauge(
+                "arb_wallet_balance_lamports",
+                &[("wallet", &wallet.to_string())],
+                balance as f64,
+            );
+            if balance < min_balance {
+                continue;
+            }
+            // the next call starts after the payer we hand out
+            self.cursor.store(start + i + 1, Ordering::Relaxed);
+            return Some((payer.keypair.clone(), Some(balance)));
+        }
+        None
+    }
+
+    /// Count an execution and its profit against the payer that ran it
+    pub fn record(&self, wallet: &Pubkey, outcome: &ArbOutcome) {
+        let Some(payer) = self.payers.iter().find(|p| p.keypair.pubkey() == *wallet) else {
+            return;
+        };
+        if !matches!(
+            outcome.status,
+            ArbStatus::Submitted { .. } | ArbStatus::Confirmed { .. }
+        ) {
+            return;
+        }
+        payer.executions.fetch_add(1, Ordering::Relaxed);
+        payer
+            .profit
+            .fetch_add(outcome.profit.unwrap_or_default(), Ordering::Relaxed);
+        metrics::inc_counter(
+            "arb_wallet_executions_total",
+            &[("wallet", &wallet.to_string())],
+        );
+    }
+
+    /// Log balance, executions and profit of every payer
+    pub async fn log_summary(&self, client: &RpcClient) {
+        for payer in &self.payers {
+            let balance = match payer.balance.get(client).await {
+                Ok(balance) => balance.to_string(),
+                Err(_) => "?".to_string(),
+            };
+            info!(
+                "💼 {}: balance {} lamports, executions {}, profit {} lamports",
+                payer.keypair.pubkey(),
+                balance,
+                payer.executions.load(Ordering::Relaxed),
+                payer.profit.load(Ordering::Relaxed)
+            );
+        }
+    }
+}
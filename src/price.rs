@@ -0,0 +1,128 @@
+use std::{collections::HashMap, env, sync::LazyLock, time::Duration};
+
+use anyhow::{Context, Result, anyhow};
+use jupiter_swap_api_client::JupiterSwapApiClient;
+use reqwest::Proxy;
+use serde_json::Value;
+use solana_sdk::{pubkey, pubkey::Pubkey};
+use tokio::{sync::RwLock, time::Instant};
+use tracing::debug;
+
+use crate::{
+    amount::lamports_to_ui_signed,
+    arb::{QuoteOptions, jupiter_call},
+    dex::Dex,
+};
+
+pub const USDC_MINT: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+
+// a json endpoint returning the SOL price, e.g. jupiter's price api,
+// falls back to quoting 1 SOL -> USDC when unset
+pub static SOL_PRICE_API: LazyLock<Option<String>> =
+    LazyLock::new(|| env::var("SOL_PRICE_API").ok().filter(|s| !s.is_empty()));
+
+const SOL_PRICE_TTL: Duration = Duration::from_secs(30);
+static SOL_PRICE: LazyLock<RwLock<Option<(f64, Instant)>>> = LazyLock::new(|| RwLock::new(None));
+
+/// SOL price in USD, cached for a short time
+pub async fn get_sol_price(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    jupiter_extra_args: Option<HashMap<String, String>>,
+) -> Result<f64> {
+    if let Some((price, fetched_at)) = *SOL_PRICE.read().await {
+        if fetched_at.elapsed() < SOL_PRICE_TTL {
+            return Ok(price);
+        }
+    }
+
+    let price = match SOL_PRICE_API.as_ref() {
+        Some(url) => fetch_price(url).await?,
+        None => quote_price(jupiter_swap_api_client, jupiter_extra_args).await?,
+    };
+    debug!("sol price: {} usd", price);
+    *SOL_PRICE.write().await = Some((price, Instant::now()));
+    Ok(price)
+}
+
+pub fn lamports_to_usd(lamports: i64, sol_price: f64) -> f64 {
+    lamports_to_ui_signed(lamports, 9) * sol_price
+}
+
+async fn fetch_price(url: &str) -> Result<f64> {
+    let mut client_builder = reqwest::Client::builder().timeout(Duration::from_secs(5));
+    if let Ok(http_proxy) = env::var("HTTP_PROXY") {
+        client_builder = client_builder.proxy(Proxy::all(http_proxy)?);
+    }
+    let body = client_builder
+        .build()?
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Value>()
+        .await?;
+    parse_price(&body).with_context(|| format!("no SOL price in response from {}", url))
+}
+
+async fn quote_price(
+    jupiter_swap_api_client: &JupiterSwapApiClient,
+    jupiter_extra_args: Option<HashMap<String, String>>,
+) -> Result<f64> {
+    let quote_options = QuoteOptions {
+        slippage_bps: 50,
+        timeout: Some(Duration::from_secs(5)),
+        ..Default::default()
+    };
+    let quote_request = quote_options.build_request(
+        &spl_token::native_mint::id(),
+        &USDC_MINT,
+        1_000_000_000,
+        &Dex::ALL.into(),
+        jupiter_extra_args,
+    );
+    let quote_response = jupiter_call(
+        "quote",
+        quote_options.timeout,
+        jupiter_swap_api_client.quote(&quote_request),
+    )
+    .await
+    .map_err(|e| anyhow!("failed to quote SOL/USDC: {}", e))?;
+    // usdc has 6 decimals
+    Ok(quote_response.out_amount as f64 / 1e6)
+}
+
+// jupiter price api ({"data": {"<mint>": {"price": "..."}}}),
+// coingecko ({"solana": {"usd": ...}}) or a bare {"price": ...}
+fn parse_price(body: &Value) -> Option<f64> {
+    let native_mint = spl_token::native_mint::id().to_string();
+    [
+        format!("/data/{}/price", native_mint),
+        "/solana/usd".to_string(),
+        "/price".to_string(),
+    ]
+    .iter()
+    .filter_map(|pointer| body.pointer(pointer))
+    .find_map(|value| match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{lamports_to_usd, parse_price};
+
+    #[test]
+    fn test_parse_price() {
+        let jup =
+            json!({"data": {"So11111111111111111111111111111111111111112": {"price": "142.5"}}});
+        assert_eq!(parse_price(&jup), Some(142.5));
+        assert_eq!(parse_price(&json!({"solana": {"usd": 140.0}})), Some(140.0));
+        assert_eq!(parse_price(&json!({"price": 141})), Some(141.0));
+        assert_eq!(parse_price(&json!({"data": {}})), None);
+        assert_eq!(lamports_to_usd(-500_000_000, 140.0), -70.0);
+    }
+}
@@ -0,0 +1,246 @@
+use serde_json::Value;
+use solana_sdk::{
+    instruction::InstructionError, message::VersionedMessage, pubkey, pubkey::Pubkey,
+    transaction::TransactionError,
+};
+
+struct KnownProgram {
+    id: Pubkey,
+    name: &'static str,
+    // custom error codes worth naming, the rest pass through as numbers
+    errors: &'static [(u32, &'static str)],
+}
+
+const TOKEN_ERRORS: &[(u32, &str)] = &[
+    (0, "NotRentExempt"),
+    (1, "InsufficientFunds"),
+    (2, "InvalidMint"),
+    (3, "MintMismatch"),
+    (4, "OwnerMismatch"),
+    (5, "FixedSupply"),
+    (6, "AlreadyInUse"),
+    (7, "InvalidNumberOfProvidedSigners"),
+    (8, "InvalidNumberOfRequiredSigners"),
+    (9, "UninitializedState"),
+    (10, "NativeNotSupported"),
+    (11, "NonNativeHasBalance"),
+    (12, "InvalidInstruction"),
+    (13, "InvalidState"),
+    (14, "Overflow"),
+    (15, "AuthorityTypeNotSupported"),
+    (16, "MintCannotFreeze"),
+    (17, "AccountFrozen"),
+    (18, "MintDecimalsMismatch"),
+    (19, "NonNativeNotSupported"),
+];
+
+const PROGRAMS: &[KnownProgram] = &[
+    KnownProgram {
+        id: pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4"),
+        name: "Jupiter v6",
+        errors: &[
+            (6000, "EmptyRoute"),
+            (6001, "SlippageToleranceExceeded"),
+            (6002, "InvalidCalculation"),
+            (6003, "MissingPlatformFeeAccount"),
+            (6004, "InvalidSlippage"),
+            (6005, "NotEnoughPercent"),
+            (6006, "InvalidInputIndex"),
+            (6007, "InvalidOutputIndex"),
+            (6008, "NotEnoughAccountKeys"),
+            (6009, "NonZeroMinimumOutAmountNotSupported"),
+            (6010, "InvalidRoutePlan"),
+            (6011, "InvalidReferralAuthority"),
+            (6012, "LedgerTokenAccountDoesNotMatch"),
+            (6013, "InvalidTokenLedger"),
+            (6014, "IncorrectTokenProgramID"),
+            (6015, "TokenProgramNotProvided"),
+            (6016, "SwapNotSupported"),
+            (6017, "ExactOutAmountNotMatched"),
+            (6018, "SourceAndDestinationMintCannotBeTheSame"),
+        ],
+    },
+    KnownProgram {
+        id: spl_token::ID,
+        name: "Token",
+        errors: TOKEN_ERRORS,
+    },
+    KnownProgram {
+        id: spl_token_2022::ID,
+        name: "Token-2022",
+        errors: TOKEN_ERRORS,
+    },
+    KnownProgram {
+        id: pubkey!("ComputeBudget111111111111111111111111111111"),
+        name: "Compute Budget",
+        errors: &[],
+    },
+    KnownProgram {
+        id: pubkey!("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8"),
+        name: "Raydium",
+        errors: &[(30, "ExceededSlippage")],
+    },
+    KnownProgram {
+        id: pubkey!("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo"),
+        name: "Meteora DLMM",
+        errors: &[(6003, "ExceededAmountSlippageTolerance")],
+    },
+    KnownProgram {
+        id: pubkey!("Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB"),
+        name: "Meteora",
+        errors: &[],
+    },
+    KnownProgram {
+        id: pubkey!("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc"),
+        name: "Whirlpool",
+        errors: &[
+            (6023, "InvalidTickArraySequence"),
+            (6036, "AmountOutBelowMinimum"),
+        ],
+    },
+    KnownProgram {
+        id: pubkey!("PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY"),
+        name: "Phoenix",
+        errors: &[],
+    },
+];
+
+/// `err` with the failing program and, when known, the name of its custom
+/// error attached. The program comes from the failure line of `logs`, or
+/// from the failing instruction of `message` when there are no logs.
+/// Anything else is returned as `err` displays.
+pub fn decode(
+    err: &TransactionError,
+    message: Option<&VersionedMessage>,
+    logs: &[String],
+) -> String {
+    let TransactionError::InstructionError(index, InstructionError::Custom(code)) = err else {
+        return err.to_string();
+    };
+    let program = failed_program(logs)
+        .filter(|(_, logged)| logged == code)
+        .map(|(program, _)| program)
+        .or_else(|| instruction_program(message?, *index as usize));
+    let Some(program) = program else {
+        return err.to_string();
+    };
+    match PROGRAMS.iter().find(|known| known.id == program) {
+        Some(known) => match known.errors.iter().find(|(c, _)| c == code) {
+            Some((_, name)) => format!("{} ({}: {})", err, known.name, name),
+            None => format!("{} ({} {})", err, known.name, program),
+        },
+        None => format!("{} (program {})", err, program),
+    }
+}
+
+/// `decode` for the json error of a bundle status
+pub fn decode_value(err: &Value, message: Option<&VersionedMessage>) -> String {
+    match serde_json::from_value::<TransactionError>(err.clone()) {
+        Ok(err) => decode(&err, message, &[]),
+        Err(_) => err.to_string(),
+    }
+}
+
+// the program and code of the last
+// "Program <id> failed: custom program error: 0x<code>" line
+fn failed_program(logs: &[String]) -> Option<(Pubkey, u32)> {
+    logs.iter().rev().find_map(|log| {
+        let (program, code) = log
+            .strip_prefix("Program ")?
+            .split_once(" failed: custom program error: 0x")?;
+        Some((
+            program.parse().ok()?,
+            u32::from_str_radix(code.trim(), 16).ok()?,
+        ))
+    })
+}
+
+fn instruction_program(message: &VersionedMessage, index: usize) -> Option<Pubkey> {
+    let ix = message.instructions().get(index)?;
+    message
+        .static_account_keys()
+        .get(ix.program_id_index as usize)
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{
+        instruction::{Instruction, InstructionError},
+        message::{Message, VersionedMessage},
+        pubkey::Pubkey,
+        transaction::TransactionError,
+    };
+
+    use super::{decode, decode_value, failed_program};
+
+    const JUPITER: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+
+    fn custom(index: u8, code: u32) -> TransactionError {
+        TransactionError::InstructionError(index, InstructionError::Custom(code))
+    }
+
+    #[test]
+    fn test_failed_program() {
+        let logs = vec![
+            format!("Program {} invoke [1]", JUPITER),
+            "Program log: Error: SlippageToleranceExceeded".to_string(),
+            format!("Program {} failed: custom program error: 0x1771", JUPITER),
+        ];
+        assert_eq!(
+            failed_program(&logs),
+            Some((JUPITER.parse().unwrap(), 6001))
+        );
+        assert_eq!(failed_program(&logs[..2]), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        let logs = vec![format!(
+            "Program {} failed: custom program error: 0x1771",
+            JUPITER
+        )];
+        assert_eq!(
+            decode(&custom(3, 6001), None, &logs),
+            "Error processing Instruction 3: custom program error: 0x1771 \
+             (Jupiter v6: SlippageToleranceExceeded)"
+        );
+
+        // unknown codes and programs pass through with the program attached
+        assert_eq!(
+            decode(&custom(3, 6100), None, &logs[..0]),
+            "Error processing Instruction 3: custom program error: 0x17d4"
+        );
+        let unknown = Pubkey::new_unique();
+        let logs = vec![format!(
+            "Program {} failed: custom program error: 0x2a",
+            unknown
+        )];
+        assert_eq!(
+            decode(&custom(0, 42), None, &logs),
+            format!(
+                "Error processing Instruction 0: custom program error: 0x2a (program {})",
+                unknown
+            )
+        );
+        assert_eq!(
+            decode(&TransactionError::AccountInUse, None, &logs),
+            TransactionError::AccountInUse.to_string()
+        );
+    }
+
+    #[test]
+    fn test_decode_from_message() {
+        let payer = Pubkey::new_unique();
+        let ixs = [
+            Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![]),
+            Instruction::new_with_bytes(spl_token::ID, &[], vec![]),
+        ];
+        let message = VersionedMessage::Legacy(Message::new(&ixs, Some(&payer)));
+        assert!(decode(&custom(1, 1), Some(&message), &[]).ends_with("(Token: InsufficientFunds)"));
+
+        let err = serde_json::json!({"InstructionError": [1, {"Custom": 1}]});
+        assert!(decode_value(&err, Some(&message)).ends_with("(Token: InsufficientFunds)"));
+        assert_eq!(decode_value(&serde_json::json!("weird"), None), "\"weird\"");
+    }
+}
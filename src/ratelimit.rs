@@ -0,0 +1,133 @@
+use std::{
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use tokio::time::Instant;
+use tracing::debug;
+
+use crate::error::ArbError;
+
+/// Token bucket refilled at `rps` tokens a second, holding up to a second
+/// worth of them. A call that finds it empty reserves the next token and
+/// waits for it, unless that takes longer than `max_wait`.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    rps: f64,
+    burst: f64,
+    max_wait: Duration,
+    // below zero while calls wait for their token
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rps: f64, max_wait: Duration) -> Result<Self> {
+        if !(rps > 0.0 && rps.is_finite()) {
+            return Err(anyhow!("rate limit must be above 0, got {}", rps));
+        }
+        let burst = rps.max(1.0);
+        Ok(Self {
+            rps,
+            burst,
+            max_wait,
+            tokens: burst,
+            refilled_at: Instant::now(),
+        })
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rps).min(self.burst);
+        self.refilled_at = now;
+    }
+
+    // how long the caller waits for its token, None when that's over
+    // max_wait and no token was taken
+    fn reserve(&mut self, now: Instant) -> Option<Duration> {
+        self.refill(now);
+        let wait = Duration::from_secs_f64((1.0 - self.tokens).max(0.0) / self.rps);
+        if wait > self.max_wait {
+            return None;
+        }
+        self.tokens -= 1.0;
+        Some(wait)
+    }
+
+    // share of the bucket in use, 1.0 once calls queue
+    fn utilization(&mut self, now: Instant) -> f64 {
+        self.refill(now);
+        ((self.burst - self.tokens) / self.burst).clamp(0.0, 1.0)
+    }
+}
+
+static LIMITER: LazyLock<Mutex<Option<RateLimiter>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Limit every jupiter call of the process, unset calls go out right away
+pub fn configure(limiter: RateLimiter) {
+    *LIMITER.lock().unwrap() = Some(limiter);
+}
+
+/// Wait for a token before jupiter `call`. A quote waiting longer than the
+/// limit allows is stale by the time it's answered, the call fails instead.
+pub async fn acquire(call: &'static str) -> Result<(), ArbError> {
+    let reserved = {
+        let mut limiter = LIMITER.lock().unwrap();
+        let Some(limiter) = limiter.as_mut() else {
+            return Ok(());
+        };
+        limiter
+            .reserve(Instant::now())
+            .ok_or(ArbError::RateLimited {
+                call,
+                max_wait_ms: limiter.max_wait.as_millis() as u64,
+            })?
+    };
+    if !reserved.is_zero() {
+        debug!("jupiter {} waits {:?} for the rate limit", call, reserved);
+        tokio::time::sleep(reserved).await;
+    }
+    Ok(())
+}
+
+/// Share of the jupiter rate limit in use, None without one
+pub fn utilization() -> Option<f64> {
+    LIMITER
+        .lock()
+        .unwrap()
+        .as_mut()
+        .map(|limiter| limiter.utilization(Instant::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::RateLimiter;
+
+    #[test]
+    fn test_rate_limiter() {
+        assert!(RateLimiter::new(0.0, Duration::ZERO).is_err());
+        assert!(RateLimiter::new(f64::NAN, Duration::ZERO).is_err());
+
+        let mut limiter = RateLimiter::new(2.0, Duration::from_millis(500)).unwrap();
+        let start = Instant::now();
+        limiter.refilled_at = start;
+        // a full bucket lets a burst of two through
+        assert_eq!(limiter.reserve(start), Some(Duration::ZERO));
+        assert_eq!(limiter.reserve(start), Some(Duration::ZERO));
+        assert_eq!(limiter.utilization(start), 1.0);
+        // the third waits half a second for its token, the fourth would
+        // wait a whole one and is turned away without taking one
+        assert_eq!(limiter.reserve(start), Some(Duration::from_millis(500)));
+        assert_eq!(limiter.reserve(start), None);
+        // a second later the queued token is paid back and one is left
+        let later = start + Duration::from_secs(1);
+        assert_eq!(limiter.reserve(later), Some(Duration::ZERO));
+        assert_eq!(limiter.utilization(later), 1.0);
+        assert_eq!(limiter.utilization(later + Duration::from_secs(5)), 0.0);
+    }
+}
@@ -0,0 +1,219 @@
+use std::{fmt, sync::Mutex, time::Duration};
+
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    error::TxError,
+    failover::with_failover,
+    get_rpc_client, history, jito, metrics,
+    outcome::{now_millis, pubkey_str},
+};
+
+// a blockhash is good for about 150 slots, past that nothing still
+// pending can land
+const MAX_AGE: Duration = Duration::from_secs(120);
+
+/// A send whose confirmation timed out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimedOut {
+    Bundle(String),
+    Signature(Signature),
+}
+
+impl TimedOut {
+    pub fn of(err: &TxError) -> Option<Self> {
+        match err {
+            TxError::ConfirmationTimeout { bundle_id } => Some(TimedOut::Bundle(bundle_id.clone())),
+            TxError::SignatureTimeout { signature } => {
+                signature.parse().ok().map(TimedOut::Signature)
+            }
+            _ => None,
+        }
+    }
+
+    /// The bundle id or signature, as `on_submitted` got it
+    pub fn id(&self) -> String {
+        match self {
+            TimedOut::Bundle(bundle_id) => bundle_id.clone(),
+            TimedOut::Signature(signature) => signature.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimedOut::Bundle(bundle_id) => write!(f, "bundle {}", bundle_id),
+            TimedOut::Signature(signature) => write!(f, "transaction {}", signature),
+        }
+    }
+}
+
+struct Pending {
+    execution_id: Uuid,
+    mint: Pubkey,
+    sent: TimedOut,
+    timed_out_at: Instant,
+}
+
+/// Trade history line of a timed out execution that landed after all,
+/// next to the execution's own record
+#[derive(Debug, Serialize)]
+struct LateLanding {
+    timestamp: u64,
+    event: &'static str,
+    execution_id: Uuid,
+    #[serde(with = "pubkey_str")]
+    mint: Pubkey,
+    bundle_id: Option<String>,
+    signature: Option<String>,
+    landed_slot: u64,
+}
+
+impl LateLanding {
+    fn new(pending: &Pending, landed_slot: u64) -> Self {
+        let (bundle_id, signature) = match &pending.sent {
+            TimedOut::Bundle(bundle_id) => (Some(bundle_id.clone()), None),
+            TimedOut::Signature(signature) => (None, Some(signature.to_string())),
+        };
+        Self {
+            timestamp: now_millis(),
+            event: "late_landing",
+            execution_id: pending.execution_id,
+            mint: pending.mint,
+            bundle_id,
+            signature,
+            landed_slot,
+        }
+    }
+}
+
+/// Executions whose confirmation timed out, looked up again on the next
+/// iterations until they land or can't anymore
+#[derive(Default)]
+pub struct Rechecks {
+    pending: Mutex<Vec<Pending>>,
+}
+
+impl Rechecks {
+    pub fn push(&self, execution_id: Uuid, mint: Pubkey, sent: TimedOut) {
+        self.pending.lock().unwrap().push(Pending {
+            execution_id,
+            mint,
+            sent,
+            timed_out_at: Instant::now(),
+        });
+    }
+
+    /// Check every pending send once. A landed one is added to the trade
+    /// history, one past the blockhash lifetime is dropped.
+    pub async fn check(&self) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+        if pending.is_empty() {
+            return;
+        }
+        let client = match get_rpc_client() {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("Skipping rechecks: {}", e);
+                self.pending.lock().unwrap().extend(pending);
+                return;
+            }
+        };
+        let mut still_pending = vec![];
+        for pending in pending {
+            let landed = landed_slot(&client, &pending.sent).await;
+            let expired = pending.timed_out_at.elapsed() >= MAX_AGE;
+            match landed {
+                Ok(Some(slot)) => record(&pending, slot),
+                Ok(None) if expired => debug!("{} never landed", pending.sent),
+                Ok(None) => still_pending.push(pending),
+                Err(e) => {
+                    warn!("Failed to recheck {}: {}", pending.sent, e);
+                    if !expired {
+                        still_pending.push(pending);
+                    }
+                }
+            }
+        }
+        self.pending.lock().unwrap().extend(still_pending);
+    }
+}
+
+async fn landed_slot(client: &RpcClient, sent: &TimedOut) -> Result<Option<u64>, TxError> {
+    match sent {
+        TimedOut::Bundle(bundle_id) => {
+            let status = jito::get_bundle_status(&jito::new_jito_client(), bundle_id).await?;
+            Ok(status
+                .filter(|status| status.is_confirmed() && status.err.err.is_none())
+                .map(|status| status.slot))
+        }
+        TimedOut::Signature(signature) => {
+            let statuses = with_failover(client, |client| {
+                client.get_signature_statuses(&[*signature])
+            })?
+            .value;
+            Ok(statuses
+                .into_iter()
+                .next()
+                .flatten()
+                .filter(|status| status.err.is_none())
+                .map(|status| status.slot))
+        }
+    }
+}
+
+fn record(pending: &Pending, landed_slot: u64) {
+    info!(
+        "🐢 {} of {} landed in slot {} after its confirmation timed out",
+        pending.sent, pending.execution_id, landed_slot
+    );
+    metrics::inc_counter("arb_late_landings_total", &[]);
+    if let Err(e) = history::record(&LateLanding::new(pending, landed_slot)) {
+        warn!("Failed to record trade history: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{pubkey::Pubkey, signature::Signature};
+    use uuid::Uuid;
+
+    use super::{LateLanding, Pending, TimedOut};
+    use crate::error::TxError;
+
+    #[test]
+    fn test_timed_out() {
+        let err = TxError::ConfirmationTimeout {
+            bundle_id: "bundle".to_string(),
+        };
+        assert_eq!(
+            TimedOut::of(&err),
+            Some(TimedOut::Bundle("bundle".to_string()))
+        );
+        let signature = Signature::new_unique();
+        let err = TxError::SignatureTimeout {
+            signature: signature.to_string(),
+        };
+        assert_eq!(TimedOut::of(&err), Some(TimedOut::Signature(signature)));
+        let err = TxError::TransactionFailed("failed".to_string());
+        assert_eq!(TimedOut::of(&err), None);
+
+        let pending = Pending {
+            execution_id: Uuid::new_v4(),
+            mint: Pubkey::new_unique(),
+            sent: TimedOut::Signature(signature),
+            timed_out_at: tokio::time::Instant::now(),
+        };
+        let line = serde_json::to_value(LateLanding::new(&pending, 42)).unwrap();
+        assert_eq!(line["event"], "late_landing");
+        assert_eq!(line["signature"], signature.to_string());
+        assert!(line["bundle_id"].is_null());
+        assert_eq!(line["landed_slot"], 42);
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{Result, anyhow};
+use jupiter_swap_api_client::quote::{PlatformFee, QuoteResponse};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    hash::hash,
+    instruction::{AccountMeta, Instruction},
+    pubkey,
+    pubkey::Pubkey,
+    system_program,
+};
+
+/// Jupiter referral program, owns referral accounts and their fee token accounts
+pub const REFERRAL_PROGRAM_ID: Pubkey = pubkey!("REFER4ZgmyYx9c6He5XfaTMiGfdLwRnkV4RPp9t9iF3");
+
+/// Partner fee collected on-chain through a jupiter referral account
+#[derive(Debug, Clone, Copy)]
+pub struct Referral {
+    pub account: Pubkey,
+    pub fee_bps: u8,
+}
+
+impl Referral {
+    /// A platform fee needs somewhere to go, `None` when no fee is charged
+    pub fn new(account: Option<Pubkey>, fee_bps: u8) -> Result<Option<Self>> {
+        match (account, fee_bps) {
+            (_, 0) => Ok(None),
+            (Some(account), fee_bps) => Ok(Some(Self { account, fee_bps })),
+            (None, fee_bps) => Err(anyhow!(
+                "a platform fee of {} bps requires a referral account, set REFERRAL_ACCOUNT or --referral-account",
+                fee_bps
+            )),
+        }
+    }
+
+    /// Token account that receives fees paid in `mint`
+    pub fn fee_account(&self, mint: &Pubkey) -> Pubkey {
+        referral_token_account(&self.account, mint)
+    }
+
+    /// Charge the fee on the quote's output, so the swap collects it
+    pub fn apply(&self, quote_response: &mut QuoteResponse) {
+        quote_response.platform_fee = Some(PlatformFee {
+            amount: fee_amount(quote_response.out_amount, self.fee_bps),
+            fee_bps: self.fee_bps,
+        });
+    }
+
+    /// The referral account and the fee account for `mint` must exist,
+    /// otherwise jupiter rejects the swap
+    pub fn validate(&self, client: &RpcClient, mint: &Pubkey) -> Result<()> {
+        let account = client
+            .get_account(&self.account)
+            .map_err(|e| anyhow!("referral account {} not found: {}", self.account, e))?;
+        if account.owner != REFERRAL_PROGRAM_ID {
+            return Err(anyhow!(
+                "{} is not a referral account, owner: {}",
+                self.account,
+                account.owner
+            ));
+        }
+        let fee_account = self.fee_account(mint);
+        client.get_account(&fee_account).map_err(|e| {
+            anyhow!(
+                "referral fee account {} for mint {} not initialized: {}",
+                fee_account,
+                mint,
+                e
+            )
+        })?;
+        Ok(())
+    }
+}
+
+pub fn referral_token_account(referral_account: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"referral_ata", referral_account.as_ref(), mint.as_ref()],
+        &REFERRAL_PROGRAM_ID,
+    )
+    .0
+}
+
+/// Project a referral account belongs to, layout: discriminator(8),
+/// partner(32), project(32), ...
+pub fn get_project(referral_account_data: &[u8]) -> Result<Pubkey> {
+    let project = referral_account_data
+        .get(40..72)
+        .ok_or_else(|| anyhow!("referral account data too short"))?;
+    Ok(Pubkey::try_from(project)?)
+}
+
+/// Anchor `initialize_referral_token_account`, creates the fee token
+/// account for `mint` under the referral account
+pub fn initialize_referral_token_account(
+    payer: &Pubkey,
+    project: &Pubkey,
+    referral_account: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    let discriminator = &hash(b"global:initialize_referral_token_account").to_bytes()[..8];
+    Instruction::new_with_bytes(
+        REFERRAL_PROGRAM_ID,
+        discriminator,
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*project, false),
+            AccountMeta::new_readonly(*referral_account, false),
+            AccountMeta::new(referral_token_account(referral_account, mint), false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(*token_program, false),
+        ],
+    )
+}
+
+/// `partner_fee` is a fraction, e.g. 0.002 = 20 bps
+pub fn partner_fee_bps(partner_fee: f64) -> Result<u8> {
+    let fee_bps = (partner_fee * 10_000.0).round();
+    if !(0.0..=u8::MAX as f64).contains(&fee_bps) {
+        return Err(anyhow!(
+            "partner fee {} must be between 0 and {} bps",
+            partner_fee,
+            u8::MAX
+        ));
+    }
+    Ok(fee_bps as u8)
+}
+
+pub fn fee_amount(amount: u64, fee_bps: u8) -> u64 {
+    (amount as u128 * fee_bps as u128 / 10_000) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::{Referral, fee_amount, get_project, partner_fee_bps};
+
+    #[test]
+    fn test_referral_fee() {
+        assert_eq!(partner_fee_bps(0.002).unwrap(), 20);
+        assert_eq!(partner_fee_bps(0.0).unwrap(), 0);
+        assert!(partner_fee_bps(0.05).is_err());
+        assert_eq!(fee_amount(1_000_000_000, 20), 2_000_000);
+
+        let account = Pubkey::new_unique();
+        let referral = Referral::new(Some(account), 20).unwrap().unwrap();
+        assert_eq!(referral.fee_bps, 20);
+        assert!(Referral::new(Some(account), 0).unwrap().is_none());
+        assert!(Referral::new(None, 0).unwrap().is_none());
+        // a fee without a referral account is never collected
+        assert!(Referral::new(None, 20).is_err());
+    }
+
+    #[test]
+    fn test_get_project() {
+        let project = Pubkey::new_unique();
+        let mut data = vec![0u8; 40];
+        data.extend_from_slice(project.as_ref());
+        data.extend_from_slice(&50u16.to_le_bytes());
+        assert_eq!(get_project(&data).unwrap(), project);
+        assert!(get_project(&data[..50]).is_err());
+    }
+}
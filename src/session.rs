@@ -0,0 +1,291 @@
+use std::{
+    fmt,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::{
+    amount::format_sol_signed,
+    breaker, notify,
+    outcome::{ArbOutcome, ArbStatus},
+    ratelimit,
+};
+
+/// Running totals of the arb loop since startup. Amounts are in the base
+/// mint's smallest unit, tips in lamports, counted like `stats`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionStats {
+    // passes of the scan loop
+    pub iterations: u64,
+    // scans that quoted both legs or failed doing so
+    pub quotes: u64,
+    pub quote_errors: u64,
+    // scans above the profit threshold
+    pub opportunities: u64,
+    pub executions: u64,
+    pub landed: u64,
+    // quoted profit of every execution
+    pub quoted_profit: i64,
+    // quoted profit minus tip of landed trades, in base mint units
+    pub realized_profit: i64,
+    // lamports
+    pub tips_paid: u64,
+}
+
+impl SessionStats {
+    fn record(&mut self, outcome: &ArbOutcome) {
+        self.quotes += 1;
+        if matches!(outcome.status, ArbStatus::QuoteFailed { .. }) {
+            self.quote_errors += 1;
+        }
+        let Some(tip) = outcome.tip_lamports else {
+            return;
+        };
+        self.opportunities += 1;
+        let profit = outcome.profit.unwrap_or_default();
+        match outcome.status {
+            ArbStatus::Submitted { .. } | ArbStatus::Failed { .. } => {
+                self.executions += 1;
+                self.quoted_profit += profit;
+            }
+            ArbStatus::Confirmed { .. } => {
+                self.executions += 1;
+                self.landed += 1;
+                self.quoted_profit += profit;
+                self.realized_profit += profit - outcome.tip_amount.unwrap_or_default() as i64;
+                self.tips_paid += tip;
+            }
+            _ => {}
+        }
+    }
+
+    fn add(&mut self, other: &SessionStats) {
+        self.iterations += other.iterations;
+        self.quotes += other.quotes;
+        self.quote_errors += other.quote_errors;
+        self.opportunities += other.opportunities;
+        self.executions += other.executions;
+        self.landed += other.landed;
+        self.quoted_profit += other.quoted_profit;
+        self.realized_profit += other.realized_profit;
+        self.tips_paid += other.tips_paid;
+    }
+
+    pub fn quote_error_rate(&self) -> Option<f64> {
+        (self.quotes > 0).then(|| self.quote_errors as f64 / self.quotes as f64)
+    }
+
+    pub fn land_rate(&self) -> Option<f64> {
+        (self.executions > 0).then(|| self.landed as f64 / self.executions as f64)
+    }
+}
+
+/// A snapshot of the session, what the report logs and pushes
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionReport {
+    pub uptime_secs: u64,
+    #[serde(flatten)]
+    pub stats: SessionStats,
+    pub quote_error_rate: Option<f64>,
+    pub land_rate: Option<f64>,
+    // scans are paused by the circuit breaker for this long
+    pub breaker_open_secs: Option<u64>,
+    // share of the jupiter rate limit in use, when there is one
+    pub jupiter_rate_utilization: Option<f64>,
+}
+
+impl fmt::Display for SessionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rate = |rate: Option<f64>| match rate {
+            Some(rate) => format!("{:.1}%", rate * 100.0),
+            None => "-".to_string(),
+        };
+        let stats = &self.stats;
+        write!(
+            f,
+            "uptime={:?} iterations={} quotes={} quote_errors={} ({}) opportunities={} \
+             executions={} landed={} ({}) quoted_profit={} realized_profit={} tips_paid={}",
+            Duration::from_secs(self.uptime_secs),
+            stats.iterations,
+            stats.quotes,
+            stats.quote_errors,
+            rate(self.quote_error_rate),
+            stats.opportunities,
+            stats.executions,
+            stats.landed,
+            rate(self.land_rate),
+            format_sol_signed(stats.quoted_profit),
+            format_sol_signed(stats.realized_profit),
+            format_sol_signed(stats.tips_paid as i64),
+        )?;
+        if let Some(utilization) = self.jupiter_rate_utilization {
+            write!(f, " jupiter_rate={}", rate(Some(utilization)))?;
+        }
+        match self.breaker_open_secs {
+            Some(secs) => write!(f, " breaker=open({}s)", secs),
+            None => write!(f, " breaker=closed"),
+        }
+    }
+}
+
+struct Session {
+    started: Instant,
+    stats: SessionStats,
+    // totals of the runs before this one
+    previous: SessionStats,
+}
+
+static SESSION: LazyLock<Mutex<Session>> = LazyLock::new(|| {
+    Mutex::new(Session {
+        started: Instant::now(),
+        stats: SessionStats::default(),
+        previous: SessionStats::default(),
+    })
+});
+
+pub fn record_iteration() {
+    SESSION.lock().unwrap().stats.iterations += 1;
+}
+
+pub fn record(outcome: &ArbOutcome) {
+    SESSION.lock().unwrap().stats.record(outcome);
+}
+
+/// Totals across restarts, this session included
+pub fn lifetime() -> SessionStats {
+    let session = SESSION.lock().unwrap();
+    let mut lifetime = session.previous.clone();
+    lifetime.add(&session.stats);
+    lifetime
+}
+
+/// Seed the lifetime totals with those of the previous runs
+pub fn restore(lifetime: SessionStats) {
+    SESSION.lock().unwrap().previous = lifetime;
+}
+
+/// The lock is released before this returns, the report never holds it
+/// across an await
+pub fn snapshot() -> SessionReport {
+    let (uptime, stats) = {
+        let session = SESSION.lock().unwrap();
+        (session.started.elapsed(), session.stats.clone())
+    };
+    SessionReport {
+        uptime_secs: uptime.as_secs(),
+        quote_error_rate: stats.quote_error_rate(),
+        land_rate: stats.land_rate(),
+        stats,
+        breaker_open_secs: breaker::remaining().map(|remaining| remaining.as_secs().max(1)),
+        jupiter_rate_utilization: ratelimit::utilization(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReportConfig {
+    pub interval: Duration,
+    // posted as json every `notify_interval`
+    pub webhook: Option<String>,
+    pub notify_interval: Duration,
+}
+
+/// Log the session report every interval, and push it to the webhook at
+/// the longer notify interval
+pub async fn run_report(config: ReportConfig) {
+    let client = reqwest::Client::new();
+    let mut last_notify = Instant::now();
+    loop {
+        tokio::time::sleep(config.interval).await;
+        let report = snapshot();
+        info!("📊 Session report: {}", report);
+        let Some(webhook) = &config.webhook else {
+            continue;
+        };
+        if last_notify.elapsed() < config.notify_interval {
+            continue;
+        }
+        last_notify = Instant::now();
+        let text = format!("solana-arb session report: {}", report);
+        if let Err(e) = notify::post(&client, webhook, text, "report", &report).await {
+            warn!("Failed to push session report: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::pubkey::Pubkey;
+    use uuid::Uuid;
+
+    use super::{SessionReport, SessionStats};
+    use crate::outcome::{ArbOutcome, ArbStatus};
+
+    fn outcome(status: ArbStatus, profit: i64, tip: Option<u64>) -> ArbOutcome {
+        let mut outcome = ArbOutcome::new(Uuid::new_v4(), Pubkey::new_unique(), 1_000_000);
+        outcome.profit = Some(profit);
+        outcome.tip_lamports = tip;
+        // a base mint worth half a lamport per unit
+        outcome.tip_amount = tip.map(|tip| tip * 2);
+        outcome.status = status;
+        outcome
+    }
+
+    #[test]
+    fn test_session_stats() {
+        let mut stats = SessionStats::default();
+        stats.record(&outcome(
+            ArbStatus::QuoteFailed {
+                error: "timeout".to_string(),
+            },
+            0,
+            None,
+        ));
+        stats.record(&outcome(ArbStatus::Skipped, -500, None));
+        stats.record(&outcome(ArbStatus::Vetoed, 20_000, Some(1_000)));
+        stats.record(&outcome(
+            ArbStatus::Failed {
+                error: "bundle failed".to_string(),
+            },
+            10_000,
+            Some(2_000),
+        ));
+        stats.record(&outcome(
+            ArbStatus::Confirmed {
+                bundle_id: None,
+                signatures: vec![],
+            },
+            30_000,
+            Some(5_000),
+        ));
+
+        assert_eq!(stats.quotes, 5);
+        assert_eq!(stats.quote_errors, 1);
+        assert_eq!(stats.opportunities, 3);
+        assert_eq!(stats.executions, 2);
+        assert_eq!(stats.landed, 1);
+        assert_eq!(stats.quoted_profit, 40_000);
+        assert_eq!(stats.realized_profit, 20_000);
+        assert_eq!(stats.tips_paid, 5_000);
+        assert_eq!(stats.quote_error_rate(), Some(0.2));
+        assert_eq!(stats.land_rate(), Some(0.5));
+        assert_eq!(SessionStats::default().land_rate(), None);
+
+        let report = SessionReport {
+            uptime_secs: 600,
+            quote_error_rate: stats.quote_error_rate(),
+            land_rate: stats.land_rate(),
+            stats,
+            breaker_open_secs: Some(30),
+            jupiter_rate_utilization: Some(0.25),
+        };
+        let line = report.to_string();
+        assert!(line.starts_with("uptime=600s iterations=0 quotes=5 quote_errors=1 (20.0%)"));
+        assert!(line.contains("landed=1 (50.0%)"));
+        assert!(line.ends_with("jupiter_rate=25.0% breaker=open(30s)"));
+    }
+}
@@ -0,0 +1,169 @@
+use std::{fs, path::Path, time::Duration};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    amount::format_sol_signed,
+    breaker::{self, BreakerState},
+    outcome::now_millis,
+    session::{self, SessionStats},
+};
+
+// how often the state is checked for changes and written
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runtime state carried across restarts
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeState {
+    pub breaker: BreakerState,
+    // lifetime totals, every run added up
+    pub stats: SessionStats,
+}
+
+impl RuntimeState {
+    pub fn capture() -> Self {
+        Self {
+            breaker: breaker::state(),
+            stats: session::lifetime(),
+        }
+    }
+
+    /// Call after `breaker::configure`
+    pub fn restore(self) {
+        breaker::restore(&self.breaker);
+        session::restore(self.stats);
+    }
+}
+
+/// None when the file is missing, or with a warning when it can't be read
+pub fn read(path: &Path) -> Option<RuntimeState> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("Ignoring state file {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    match serde_json::from_str(&data) {
+        Ok(state) => Some(state),
+        Err(e) => {
+            warn!("Ignoring corrupt state file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+pub fn write(path: &Path, state: &RuntimeState) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // write then rename so a crash never leaves a truncated state
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(state)?)?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+/// Restore the state saved at `path`, logging what came back
+pub fn load(path: &Path) {
+    let Some(state) = read(path) else {
+        return;
+    };
+    let open_for = state
+        .breaker
+        .open_until
+        .map(|until| until.saturating_sub(now_millis()) / 1000);
+    info!(
+        "Restored state from {}: breaker trips {}, open for {:?}s, lifetime executions {}, landed {}, realized profit {}",
+        path.display(),
+        state.breaker.trips,
+        open_for,
+        state.stats.executions,
+        state.stats.landed,
+        format_sol_signed(state.stats.realized_profit)
+    );
+    state.restore();
+}
+
+/// Write the state to `path` whenever it changed
+pub async fn run_save(path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let mut saved = None;
+    loop {
+        tokio::time::sleep(SAVE_INTERVAL).await;
+        let state = RuntimeState::capture();
+        if saved.as_ref() == Some(&state) {
+            continue;
+        }
+        match write(path, &state) {
+            Ok(()) => saved = Some(state),
+            Err(e) => warn!("Failed to save state to {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Save the state once more on ctrl-c or SIGTERM, then exit
+pub async fn save_on_shutdown(path: impl AsRef<Path>) {
+    shutdown_signal().await;
+    let path = path.as_ref();
+    match write(path, &RuntimeState::capture()) {
+        Ok(()) => info!("Saved state to {}", path.display()),
+        Err(e) => warn!("Failed to save state to {}: {}", path.display(), e),
+    }
+    std::process::exit(0);
+}
+
+#[cfg(unix)]
+async fn shutdown_signal() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let Ok(mut terminate) = signal(SignalKind::terminate()) else {
+        let _ = tokio::signal::ctrl_c().await;
+        return;
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn shutdown_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::breaker::BreakerState;
+
+    use super::{RuntimeState, read, write};
+
+    #[test]
+    fn test_runtime_state_file() {
+        let path = std::env::temp_dir().join(format!("state_{}.json", uuid::Uuid::new_v4()));
+        assert!(read(&path).is_none());
+
+        let mut state = RuntimeState {
+            breaker: BreakerState {
+                trips: 2,
+                open_until: Some(1_700_000_000_000),
+            },
+            ..Default::default()
+        };
+        state.stats.executions = 3;
+        state.stats.realized_profit = -5_000;
+        write(&path, &state).unwrap();
+        assert_eq!(read(&path), Some(state));
+
+        // a corrupt file is ignored, a partial one fills in defaults
+        std::fs::write(&path, "{\"breaker\": ").unwrap();
+        assert!(read(&path).is_none());
+        std::fs::write(&path, "{\"breaker\": {\"trips\": 1}}").unwrap();
+        assert_eq!(read(&path).unwrap().breaker.trips, 1);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,214 @@
+use std::{collections::BTreeMap, io::BufRead, time::Duration};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// The fields of a trade history line stats cares about. Lines carrying an
+/// `event` (sweeps) are not trades and are ignored.
+#[derive(Debug, Deserialize)]
+struct HistoryLine {
+    timestamp: u64,
+    #[serde(default)]
+    event: Option<String>,
+    #[serde(default)]
+    mint: Option<String>,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    profit: Option<i64>,
+    #[serde(default)]
+    tip_lamports: Option<u64>,
+    #[serde(default)]
+    tip_amount: Option<u64>,
+    #[serde(default)]
+    send_mode: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StatsFilter {
+    // unix timestamp in milliseconds, older records are left out
+    pub since: Option<u64>,
+    pub mint: Option<String>,
+}
+
+/// Amounts are in the base mint's smallest unit, tips in lamports
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TradeStats {
+    pub attempts: u64,
+    // opportunities that went out to jito or the rpc
+    pub executions: u64,
+    pub landed: u64,
+    pub failed: u64,
+    pub land_rate: Option<f64>,
+    // quoted profit of every execution
+    pub gross_profit: i64,
+    // tips of landed trades
+    pub tips_paid: u64,
+    pub average_tip: Option<u64>,
+    // quoted profit minus tip of landed trades
+    pub realized_profit: i64,
+    pub best: Option<i64>,
+    pub worst: Option<i64>,
+}
+
+impl TradeStats {
+    fn add(&mut self, line: &HistoryLine) {
+        self.attempts += 1;
+        let status = line.status.as_deref().unwrap_or_default();
+        let executed =
+            line.tip_lamports.is_some() && matches!(status, "submitted" | "confirmed" | "failed");
+        if !executed {
+            return;
+        }
+        let profit = line.profit.unwrap_or_default();
+        let tip = line.tip_lamports.unwrap_or_default();
+        // older records only logged the tip in lamports
+        let tip_amount = line.tip_amount.unwrap_or(tip);
+        self.executions += 1;
+        self.gross_profit += profit;
+        if status == "confirmed" {
+            let net = profit - tip_amount as i64;
+            self.landed += 1;
+            self.tips_paid += tip;
+            self.realized_profit += net;
+            self.best = Some(self.best.map_or(net, |best| best.max(net)));
+            self.worst = Some(self.worst.map_or(net, |worst| worst.min(net)));
+            self.average_tip = Some(self.tips_paid / self.landed);
+        }
+        if status == "failed" {
+            self.failed += 1;
+        }
+        self.land_rate = Some(self.landed as f64 / self.executions as f64);
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StatsReport {
+    pub total: TradeStats,
+    pub by_mint: BTreeMap<String, TradeStats>,
+    // records from before the send mode was logged count as "unknown"
+    pub by_send_mode: BTreeMap<String, TradeStats>,
+    // lines that didn't parse as a history record
+    pub skipped: u64,
+}
+
+/// Summarize a JSONL trade history line by line, the file is never held in
+/// memory as a whole
+pub fn summarize<R: BufRead>(mut reader: R, filter: &StatsFilter) -> Result<StatsReport> {
+    let mut report = StatsReport::default();
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        if buf.trim_ascii().is_empty() {
+            continue;
+        }
+        let Ok(line) = serde_json::from_slice::<HistoryLine>(&buf) else {
+            report.skipped += 1;
+            continue;
+        };
+        if line.event.is_some() {
+            continue;
+        }
+        let Some(mint) = line.mint.clone() else {
+            report.skipped += 1;
+            continue;
+        };
+        if filter.since.is_some_and(|since| line.timestamp < since) {
+            continue;
+        }
+        if filter.mint.as_ref().is_some_and(|m| *m != mint) {
+            continue;
+        }
+        report.total.add(&line);
+        report.by_mint.entry(mint).or_default().add(&line);
+        let send_mode = line.send_mode.as_deref().unwrap_or("unknown");
+        report
+            .by_send_mode
+            .entry(send_mode.to_string())
+            .or_default()
+            .add(&line);
+    }
+    Ok(report)
+}
+
+/// Parse a window like `30m`, `24h` or `7d`
+pub fn parse_since(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("missing unit in {:?}, expected s, m, h or d", s))?;
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| anyhow!("invalid duration {:?}", s))?;
+    let secs = match unit {
+        "s" => value,
+        "m" => value * 60,
+        "h" => value * 3600,
+        "d" => value * 86400,
+        _ => return Err(anyhow!("unknown unit {:?}, expected s, m, h or d", unit)),
+    };
+    Ok(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{StatsFilter, parse_since, summarize};
+
+    const HISTORY: &str = r#"{"timestamp":1000,"execution_id":"e1","mint":"A","amount_in":1,"profit":500,"profit_usd":null,"tip_lamports":200,"status":"confirmed","bundle_id":"b","signatures":[],"latency":{}}
+{"timestamp":2000,"execution_id":"e2","mint":"A","amount_in":1,"profit":300,"profit_usd":null,"tip_lamports":100,"status":"submitted","bundle_id":"b","latency":{}}
+not json
+{"timestamp":3000,"execution_id":"e3","mint":"B","amount_in":1,"profit":-5,"profit_usd":null,"tip_lamports":null,"status":"skipped","latency":{}}
+{"timestamp":3500,"event":"sweep","from":"x","to":"y","lamports":1,"signature":"s"}
+
+{"timestamp":4000,"execution_id":"e4","mint":"B","amount_in":1,"profit":100,"profit_usd":null,"tip_lamports":150,"tip_amount":30,"send_mode":"jito","status":"confirmed","bundle_id":null,"signatures":[],"latency":{}}
+{"timestamp":5000,"execution_id":"e5","mint":"B","amount_in":1,"profit":80,"profit_usd":null,"tip_lamports":0,"send_mode":"rpc","status":"failed","error":"expired","latency":{}}
+"#;
+
+    #[test]
+    fn test_summarize() {
+        let report = summarize(HISTORY.as_bytes(), &StatsFilter::default()).unwrap();
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.total.attempts, 5);
+        assert_eq!(report.total.executions, 4);
+        assert_eq!(report.total.landed, 2);
+        assert_eq!(report.total.gross_profit, 980);
+        assert_eq!(report.total.failed, 1);
+        assert_eq!(report.total.tips_paid, 350);
+        assert_eq!(report.total.average_tip, Some(175));
+        assert_eq!(report.total.realized_profit, 370);
+        assert_eq!(report.total.best, Some(300));
+        assert_eq!(report.total.worst, Some(70));
+        assert_eq!(report.by_mint["A"].land_rate, Some(0.5));
+        assert_eq!(report.by_mint["B"].attempts, 3);
+        assert_eq!(report.by_send_mode["unknown"].attempts, 3);
+        assert_eq!(report.by_send_mode["jito"].landed, 1);
+        assert_eq!(report.by_send_mode["rpc"].executions, 1);
+        assert_eq!(report.by_send_mode["rpc"].land_rate, Some(0.0));
+
+        let filter = StatsFilter {
+            since: Some(1500),
+            mint: Some("A".to_string()),
+        };
+        let report = summarize(HISTORY.as_bytes(), &filter).unwrap();
+        assert_eq!(report.total.attempts, 1);
+        assert_eq!(report.total.landed, 0);
+        assert!(report.total.best.is_none());
+        assert_eq!(report.by_mint.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("24h").unwrap(), Duration::from_secs(86400));
+        assert_eq!(parse_since("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_since("7d").unwrap(), Duration::from_secs(604800));
+        assert!(parse_since("24").is_err());
+        assert!(parse_since("h").is_err());
+        assert!(parse_since("1w").is_err());
+    }
+}
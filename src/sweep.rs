@@ -0,0 +1,154 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    system_instruction,
+    transaction::Transaction,
+};
+use spl_token::amount_to_ui_amount;
+use tracing::{debug, info, warn};
+
+use crate::{get_rpc_client, history, inflight, outcome::now_millis};
+
+#[derive(Debug, Clone)]
+pub struct SweepConfig {
+    pub to: Pubkey,
+    // lamports, sweeping starts once the payer balance exceeds this
+    pub above: u64,
+    // lamports left in the payer after a sweep
+    pub keep: u64,
+    // lamports of native SOL the balance pre-checks rely on
+    pub reserve: u64,
+    pub interval: Duration,
+}
+
+impl SweepConfig {
+    /// Lamports to move out of a payer holding `balance`, never touching
+    /// the keep amount, the reserve or the transfer fee
+    pub fn sweep_amount(&self, balance: u64, fee: u64) -> u64 {
+        if balance <= self.above {
+            return 0;
+        }
+        let keep = self.keep.max(self.reserve);
+        balance.saturating_sub(keep).saturating_sub(fee)
+    }
+}
+
+/// A sweep in the trade history, tagged so it isn't mistaken for a trade
+#[derive(Debug, Serialize)]
+pub struct SweepRecord {
+    pub timestamp: u64,
+    pub event: &'static str,
+    pub from: String,
+    pub to: String,
+    pub lamports: u64,
+    pub signature: String,
+}
+
+/// Periodically move the payer balance above the keep amount to a cold
+/// wallet. A round is skipped while an execution is in flight.
+pub async fn run_sweep(payer: Arc<Keypair>, config: SweepConfig) {
+    loop {
+        tokio::time::sleep(config.interval).await;
+        if let Err(e) = sweep(&payer, &config) {
+            warn!("sweep: {}", e);
+        }
+    }
+}
+
+fn sweep(payer: &Keypair, config: &SweepConfig) -> Result<()> {
+    let client = get_rpc_client()?;
+    let Some((signature, lamports)) = send_sweep(&client, payer, config)? else {
+        return Ok(());
+    };
+    // confirmed outside the lock, executions aren't held off meanwhile
+    client.poll_for_signature(&signature)?;
+    info!(
+        "💸 Swept {} sol to {}, tx: {}",
+        amount_to_ui_amount(lamports, 9),
+        config.to,
+        signature
+    );
+    let record = SweepRecord {
+        timestamp: now_millis(),
+        event: "sweep",
+        from: payer.pubkey().to_string(),
+        to: config.to.to_string(),
+        lamports,
+        signature: signature.to_string(),
+    };
+    if let Err(e) = history::record(&record) {
+        warn!("sweep: failed to record trade history: {}", e);
+    }
+    Ok(())
+}
+
+// read the balance and send the transfer while no execution is in flight,
+// None when one is or there is nothing to sweep
+fn send_sweep(
+    client: &RpcClient,
+    payer: &Keypair,
+    config: &SweepConfig,
+) -> Result<Option<(Signature, u64)>> {
+    let Some(_maintenance) = inflight::try_maintenance() else {
+        debug!("sweep: execution in flight, skipping");
+        return Ok(None);
+    };
+    let owner = payer.pubkey();
+    let balance = client.get_balance(&owner)?;
+    let blockhash = client.get_latest_blockhash()?;
+    // the fee is paid from the same balance, price the tx before sizing it
+    let mut message = Transaction::new_with_payer(
+        &[system_instruction::transfer(&owner, &config.to, 0)],
+        Some(&owner),
+    )
+    .message;
+    message.recent_blockhash = blockhash;
+    let fee = client.get_fee_for_message(&message)?;
+    let lamports = config.sweep_amount(balance, fee);
+    if lamports == 0 {
+        debug!("sweep: balance {} lamports, nothing to sweep", balance);
+        return Ok(None);
+    }
+
+    let tx = Transaction::new_signed_with_payer(
+        &[system_instruction::transfer(&owner, &config.to, lamports)],
+        Some(&owner),
+        &[payer],
+        blockhash,
+    );
+    let signature = client.send_transaction(&tx)?;
+    Ok(Some((signature, lamports)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::SweepConfig;
+
+    #[test]
+    fn test_sweep_amount() {
+        let config = SweepConfig {
+            to: Pubkey::new_unique(),
+            above: 5_000_000_000,
+            keep: 1_000_000_000,
+            reserve: 10_000_000,
+            interval: Duration::from_secs(300),
+        };
+        // below the threshold, nothing to do
+        assert_eq!(config.sweep_amount(5_000_000_000, 5000), 0);
+        assert_eq!(config.sweep_amount(6_000_000_000, 5000), 4_999_995_000);
+
+        // the reserve wins over a smaller keep amount
+        let config = SweepConfig { keep: 0, ..config };
+        assert_eq!(config.sweep_amount(6_000_000_000, 5000), 5_989_995_000);
+    }
+}
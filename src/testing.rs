@@ -0,0 +1,143 @@
+use std::sync::{Arc, Mutex};
+
+use jupiter_swap_api_client::quote::QuoteResponse;
+use solana_sdk::pubkey::Pubkey;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// A request as the mock jupiter api got it
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    pub body: String,
+}
+
+/// Stand-in for the jupiter api on a local port. Every request is kept and
+/// answered with the json `respond` returns for it.
+pub struct MockJupiter {
+    pub url: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockJupiter {
+    pub async fn start(respond: impl Fn(&MockRequest) -> String + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let respond = Arc::new(respond);
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let recorded = recorded.clone();
+                let respond = respond.clone();
+                tokio::spawn(async move {
+                    let Some(request) = read_request(&mut stream).await else {
+                        return;
+                    };
+                    let body = respond(&request);
+                    recorded.lock().unwrap().push(request);
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        Self { url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Requests whose path ends with `path`, e.g. "/quote"
+    pub fn requests_to(&self, path: &str) -> Vec<MockRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request.path.ends_with(path))
+            .collect()
+    }
+}
+
+// one request per connection, the body is read up to its content length
+async fn read_request(stream: &mut TcpStream) -> Option<MockRequest> {
+    let mut buf = vec![];
+    let mut chunk = [0u8; 4096];
+    let head_len = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let head = String::from_utf8_lossy(&buf[..head_len]).to_string();
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    while buf.len() < head_len + content_length {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let mut request_line = head.lines().next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let target = request_line.next()?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    Some(MockRequest {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        body: String::from_utf8_lossy(&buf[head_len..head_len + content_length]).to_string(),
+    })
+}
+
+/// A single-hop quote as jupiter returns it
+pub fn quote_fixture(
+    input_mint: &Pubkey,
+    output_mint: &Pubkey,
+    in_amount: u64,
+    out_amount: u64,
+    context_slot: u64,
+) -> QuoteResponse {
+    serde_json::from_value(serde_json::json!({
+        "inputMint": input_mint.to_string(),
+        "inAmount": in_amount.to_string(),
+        "outputMint": output_mint.to_string(),
+        "outAmount": out_amount.to_string(),
+        "otherAmountThreshold": out_amount.to_string(),
+        "swapMode": "ExactIn",
+        "slippageBps": 50,
+        "platformFee": { "amount": "10", "feeBps": 20 },
+        "priceImpactPct": "0.01",
+        "routePlan": [{
+            "swapInfo": {
+                "ammKey": Pubkey::new_unique().to_string(),
+                "label": "Whirlpool",
+                "inputMint": input_mint.to_string(),
+                "outputMint": output_mint.to_string(),
+                "inAmount": in_amount.to_string(),
+                "outAmount": out_amount.to_string(),
+                "feeAmount": "0",
+                "feeMint": input_mint.to_string()
+            },
+            "percent": 100
+        }],
+        "contextSlot": context_slot,
+        "timeTaken": 0.01
+    }))
+    .unwrap()
+}
@@ -1,10 +1,279 @@
-use anyhow::Result;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{LazyLock, Mutex},
+};
+
+use anyhow::{Result, anyhow};
 use solana_client::rpc_client::RpcClient;
-use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
-use spl_token::state::Mint;
+use solana_sdk::{
+    account::Account, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+    signature::Keypair, signer::Signer, transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token::amount_to_ui_amount;
+use spl_token_2022::extension::{
+    BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+    transfer_fee::{TransferFee, TransferFeeConfig},
+};
+use tracing::{info, warn};
+
+// idempotent creates sent in one transaction
+const PREWARM_BATCH: usize = 10;
+// accounts per getMultipleAccounts call
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Mint state shared by the token and token-2022 programs
+#[derive(Debug, Clone)]
+pub struct MintInfo {
+    pub program_id: Pubkey,
+    pub decimals: u8,
+    pub supply: u64,
+    pub mint_authority: Option<Pubkey>,
+    pub freeze_authority: Option<Pubkey>,
+    // always empty for spl-token mints
+    pub extensions: Vec<ExtensionType>,
+    pub transfer_fee_config: Option<TransferFeeConfig>,
+}
+
+impl MintInfo {
+    pub fn is_token_2022(&self) -> bool {
+        self.program_id == spl_token_2022::id()
+    }
 
-pub fn get_mint(rpc_client: &RpcClient, address: &Pubkey) -> Result<Mint> {
+    /// Transfer fee in effect at `epoch`, if the mint has the extension
+    pub fn transfer_fee(&self, epoch: u64) -> Option<TransferFee> {
+        self.transfer_fee_config
+            .map(|config| *config.get_epoch_fee(epoch))
+    }
+}
+
+pub fn get_mint(rpc_client: &RpcClient, address: &Pubkey) -> Result<MintInfo> {
     let mint_account = rpc_client.get_account(address)?;
-    let mint_data = Mint::unpack(&mint_account.data)?;
-    Ok(mint_data)
+    unpack_mint(&mint_account.owner, &mint_account.data)
+}
+
+pub fn unpack_mint(owner: &Pubkey, data: &[u8]) -> Result<MintInfo> {
+    if owner == &spl_token::id() {
+        let mint = spl_token::state::Mint::unpack(data)?;
+        Ok(MintInfo {
+            program_id: *owner,
+            decimals: mint.decimals,
+            supply: mint.supply,
+            mint_authority: to_option(mint.mint_authority),
+            freeze_authority: to_option(mint.freeze_authority),
+            extensions: vec![],
+            transfer_fee_config: None,
+        })
+    } else if owner == &spl_token_2022::id() {
+        let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(data)?;
+        Ok(MintInfo {
+            program_id: *owner,
+            decimals: state.base.decimals,
+            supply: state.base.supply,
+            mint_authority: to_option(state.base.mint_authority),
+            freeze_authority: to_option(state.base.freeze_authority),
+            extensions: state.get_extension_types()?,
+            transfer_fee_config: state.get_extension::<TransferFeeConfig>().ok().copied(),
+        })
+    } else {
+        Err(anyhow!("account is not a mint, owner: {}", owner))
+    }
+}
+
+fn to_option(value: COption<Pubkey>) -> Option<Pubkey> {
+    match value {
+        COption::Some(key) => Some(key),
+        COption::None => None,
+    }
+}
+
+pub fn get_ata(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    get_associated_token_address(owner, mint)
+}
+
+// (owner, mint) pairs whose associated token account is known to exist
+static KNOWN_ATAS: LazyLock<Mutex<HashSet<(Pubkey, Pubkey)>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+// token program and token account length of each mint, neither changes
+// once the mint is initialized
+static ACCOUNT_LAYOUTS: LazyLock<Mutex<HashMap<Pubkey, (Pubkey, usize)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// rent exemption by account length, fixed as long as the rent sysvar is
+static RENT_EXEMPTIONS: LazyLock<Mutex<HashMap<usize, u64>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Lamports of rent a swap into `mint` pays to create the owner's token
+/// account, 0 once the account exists. Only the account lookup goes to
+/// the rpc on every call, the mint's layout and the rent are cached.
+pub fn ata_rent(rpc_client: &RpcClient, owner: &Pubkey, mint: &Pubkey) -> Result<u64> {
+    if KNOWN_ATAS.lock().unwrap().contains(&(*owner, *mint)) {
+        return Ok(0);
+    }
+    let (program_id, account_len) = account_layout(rpc_client, mint)?;
+    let ata = get_associated_token_address_with_program_id(owner, mint, &program_id);
+    let ata_account = rpc_client
+        .get_account_with_commitment(&ata, rpc_client.commitment())?
+        .value;
+    if ata_account.is_some() {
+        KNOWN_ATAS.lock().unwrap().insert((*owner, *mint));
+        return Ok(0);
+    }
+    rent_exemption(rpc_client, account_len)
+}
+
+fn account_layout(rpc_client: &RpcClient, mint: &Pubkey) -> Result<(Pubkey, usize)> {
+    if let Some(layout) = ACCOUNT_LAYOUTS.lock().unwrap().get(mint) {
+        return Ok(*layout);
+    }
+    let mint_account = rpc_client.get_account(mint)?;
+    let mint_info = unpack_mint(&mint_account.owner, &mint_account.data)?;
+    let account_len = if mint_info.is_token_2022() {
+        // e.g. a transfer fee mint needs room for the withheld amount
+        let extensions = ExtensionType::get_required_init_account_extensions(&mint_info.extensions);
+        ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&extensions)?
+    } else {
+        spl_token::state::Account::LEN
+    };
+    let layout = (mint_account.owner, account_len);
+    ACCOUNT_LAYOUTS.lock().unwrap().insert(*mint, layout);
+    Ok(layout)
+}
+
+fn rent_exemption(rpc_client: &RpcClient, account_len: usize) -> Result<u64> {
+    if let Some(rent) = RENT_EXEMPTIONS.lock().unwrap().get(&account_len) {
+        return Ok(*rent);
+    }
+    let rent = rpc_client.get_minimum_balance_for_rent_exemption(account_len)?;
+    RENT_EXEMPTIONS.lock().unwrap().insert(account_len, rent);
+    Ok(rent)
+}
+
+/// What `prewarm_atas` did for an owner
+#[derive(Debug, Default)]
+pub struct Prewarm {
+    pub created: Vec<Pubkey>,
+    // lamports of rent locked in the created accounts
+    pub rent: u64,
+    // mints without a usable token account, and why
+    pub failed: Vec<(Pubkey, String)>,
+}
+
+/// Create the payer's missing token accounts for `mints` before trading,
+/// so no first trade pays the rent and carries the setup instructions.
+/// A batch that fails is retried one mint at a time to single out the bad
+/// ones, a frozen account counts as failed too.
+pub fn prewarm_atas(client: &RpcClient, payer: &Keypair, mints: &[Pubkey]) -> Result<Prewarm> {
+    let owner = payer.pubkey();
+    let mut prewarm = Prewarm::default();
+    let mut atas = vec![];
+    for (mint, mint_account) in mints.iter().zip(get_accounts(client, mints)?) {
+        match mint_account {
+            Some(mint_account) => atas.push((
+                *mint,
+                mint_account.owner,
+                get_associated_token_address_with_program_id(&owner, mint, &mint_account.owner),
+            )),
+            None => prewarm
+                .failed
+                .push((*mint, "mint account not found".to_string())),
+        }
+    }
+    let addresses: Vec<Pubkey> = atas.iter().map(|(_, _, ata)| *ata).collect();
+    let existing = get_accounts(client, &addresses)?;
+    let missing: Vec<(Pubkey, Pubkey)> = atas
+        .iter()
+        .zip(&existing)
+        .filter(|(_, account)| account.is_none())
+        .map(|((mint, program_id, _), _)| (*mint, *program_id))
+        .collect();
+    info!(
+        "{} of {} mints need a token account for {}",
+        missing.len(),
+        atas.len(),
+        owner
+    );
+
+    for batch in missing.chunks(PREWARM_BATCH) {
+        if let Err(e) = create_atas(client, payer, batch) {
+            if batch.len() == 1 {
+                prewarm.failed.push((batch[0].0, e.to_string()));
+                continue;
+            }
+            warn!(
+                "Failed to create a batch of {} token accounts, retrying one by one: {}",
+                batch.len(),
+                e
+            );
+            for single in batch.chunks(1) {
+                if let Err(e) = create_atas(client, payer, single) {
+                    prewarm.failed.push((single[0].0, e.to_string()));
+                }
+            }
+        }
+    }
+
+    // rent comes from what the created accounts hold, frozen ones are unusable
+    let accounts = get_accounts(client, &addresses)?;
+    for ((mint, _, _), (before, after)) in atas.iter().zip(existing.iter().zip(accounts)) {
+        let Some(account) = after else {
+            continue;
+        };
+        if is_frozen(&account) {
+            prewarm
+                .failed
+                .push((*mint, "token account is frozen".to_string()));
+            continue;
+        }
+        if before.is_none() {
+            prewarm.created.push(*mint);
+            prewarm.rent += account.lamports;
+        }
+        KNOWN_ATAS.lock().unwrap().insert((owner, *mint));
+    }
+    info!(
+        "Created {} token accounts for {}, rent: {} sol",
+        prewarm.created.len(),
+        owner,
+        amount_to_ui_amount(prewarm.rent, 9)
+    );
+    Ok(prewarm)
+}
+
+fn get_accounts(client: &RpcClient, keys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+    let mut accounts = Vec::with_capacity(keys.len());
+    for chunk in keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        accounts.extend(client.get_multiple_accounts(chunk)?);
+    }
+    Ok(accounts)
+}
+
+// (mint, token program) pairs
+fn create_atas(client: &RpcClient, payer: &Keypair, mints: &[(Pubkey, Pubkey)]) -> Result<()> {
+    let owner = payer.pubkey();
+    let ixs: Vec<_> = mints
+        .iter()
+        .map(|(mint, program_id)| {
+            create_associated_token_account_idempotent(&owner, &owner, mint, program_id)
+        })
+        .collect();
+    let tx = Transaction::new_signed_with_payer(
+        &ixs,
+        Some(&owner),
+        &[payer],
+        client.get_latest_blockhash()?,
+    );
+    let signature = client.send_and_confirm_transaction(&tx)?;
+    info!("Created {} token accounts: {}", ixs.len(), signature);
+    Ok(())
+}
+
+// the base layout is the same for both token programs
+fn is_frozen(account: &Account) -> bool {
+    StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+        .is_ok_and(|state| state.base.is_frozen())
 }
@@ -0,0 +1,170 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    sync::{
+        LazyLock, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use futures_util::{StreamExt, stream::select_all};
+use jupiter_swap_api_client::quote::QuoteResponse;
+use solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use tokio::{sync::mpsc, time::Instant};
+use tracing::{debug, info, warn};
+
+// mint -> pool accounts seen in its latest route plans
+static POOL_ACCOUNTS: LazyLock<RwLock<HashMap<Pubkey, HashSet<Pubkey>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+// false while the websocket is down, the scan loop polls instead
+static CONNECTED: AtomicBool = AtomicBool::new(false);
+
+pub fn get_ws_endpoints() -> Result<Vec<String>> {
+    let ws_urls = env::var("RPC_WS_ENDPOINTS")?
+        .split(",")
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<String>>();
+    if ws_urls.is_empty() {
+        return Err(anyhow!("RPC_WS_ENDPOINTS is empty"));
+    }
+    Ok(ws_urls)
+}
+
+pub fn is_connected() -> bool {
+    CONNECTED.load(Ordering::Relaxed)
+}
+
+/// Remember the pool accounts of the latest quotes for a mint, they are
+/// picked up on the next resubscription
+pub fn record_route(mint: &Pubkey, quotes: &[&QuoteResponse]) {
+    let pools: HashSet<Pubkey> = quotes
+        .iter()
+        .flat_map(|quote| quote.route_plan.iter())
+        .map(|step| step.swap_info.amm_key)
+        .collect();
+    POOL_ACCOUNTS.write().unwrap().insert(*mint, pools);
+}
+
+// pool account -> mints routed through it
+fn mints_by_pool() -> HashMap<Pubkey, Vec<Pubkey>> {
+    let mut mints_by_pool: HashMap<Pubkey, Vec<Pubkey>> = HashMap::new();
+    for (mint, pools) in POOL_ACCOUNTS.read().unwrap().iter() {
+        for pool in pools {
+            mints_by_pool.entry(*pool).or_default().push(*mint);
+        }
+    }
+    for mints in mints_by_pool.values_mut() {
+        mints.sort();
+    }
+    mints_by_pool
+}
+
+#[derive(Debug, Clone)]
+pub struct TriggerConfig {
+    pub ws_urls: Vec<String>,
+    // at most one trigger per mint within this window
+    pub debounce: Duration,
+    // how often the pool set is checked for changes
+    pub resubscribe_interval: Duration,
+}
+
+/// Subscribe to the pool accounts of every tracked mint and send a mint
+/// whenever one of its pools changes. Reconnects with backoff, rotating
+/// through the ws endpoints, and resubscribes when the pool set changes.
+pub async fn run_triggers(config: TriggerConfig, sender: mpsc::Sender<Pubkey>) {
+    let mut last_triggered: HashMap<Pubkey, Instant> = HashMap::new();
+    let mut failures = 0u32;
+    loop {
+        let ws_url = &config.ws_urls[failures as usize % config.ws_urls.len()];
+        match subscribe(ws_url, &config, &sender, &mut last_triggered).await {
+            Ok(()) => failures = 0,
+            Err(e) => {
+                CONNECTED.store(false, Ordering::Relaxed);
+                failures += 1;
+                let backoff = Duration::from_millis(500 * 2u64.pow(failures.min(6)));
+                warn!(
+                    "ws trigger {}: {}, polling until reconnected in {:?}",
+                    ws_url, e, backoff
+                );
+                tokio::time::sleep(backoff).await;
+            }
+        }
+        if sender.is_closed() {
+            return;
+        }
+    }
+}
+
+// returns Ok once the pools changed and a resubscription is due
+async fn subscribe(
+    ws_url: &str,
+    config: &TriggerConfig,
+    sender: &mpsc::Sender<Pubkey>,
+    last_triggered: &mut HashMap<Pubkey, Instant>,
+) -> Result<()> {
+    let subscribed = mints_by_pool();
+    if subscribed.is_empty() {
+        // nothing quoted yet, polling discovers the routes
+        CONNECTED.store(false, Ordering::Relaxed);
+        tokio::time::sleep(config.resubscribe_interval).await;
+        return Ok(());
+    }
+
+    let client = PubsubClient::new(ws_url).await?;
+    let account_config = RpcAccountInfoConfig {
+        commitment: Some(CommitmentConfig::processed()),
+        ..Default::default()
+    };
+    let mut streams = Vec::with_capacity(subscribed.len());
+    for pool in subscribed.keys() {
+        let (stream, _unsubscribe) = client
+            .account_subscribe(pool, Some(account_config.clone()))
+            .await?;
+        let pool = *pool;
+        streams.push(stream.map(move |_| pool));
+    }
+    let mut updates = select_all(streams);
+    CONNECTED.store(true, Ordering::Relaxed);
+    info!(
+        "ws trigger: subscribed to {} pool accounts on {}",
+        subscribed.len(),
+        ws_url
+    );
+
+    let resubscribe = tokio::time::sleep(config.resubscribe_interval);
+    tokio::pin!(resubscribe);
+    loop {
+        tokio::select! {
+            update = updates.next() => {
+                let Some(pool) = update else {
+                    return Err(anyhow!("subscription closed"));
+                };
+                for mint in &subscribed[&pool] {
+                    let now = Instant::now();
+                    if last_triggered
+                        .get(mint)
+                        .is_some_and(|last| now.duration_since(*last) < config.debounce)
+                    {
+                        continue;
+                    }
+                    last_triggered.insert(*mint, now);
+                    debug!("ws trigger: pool {} changed, scheduling {}", pool, mint);
+                    if sender.try_send(*mint).is_err() {
+                        debug!("ws trigger: queue full, dropping {}", mint);
+                    }
+                }
+            }
+            _ = &mut resubscribe => {
+                if mints_by_pool() != subscribed {
+                    return Ok(());
+                }
+                resubscribe.as_mut().reset(Instant::now() + config.resubscribe_interval);
+            }
+        }
+    }
+}
@@ -1,29 +1,64 @@
-use std::{env, sync::Arc, time::Duration};
+use std::{
+    collections::HashSet,
+    env,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use anyhow::{Result, anyhow};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use clap::ValueEnum;
+use futures_util::{
+    StreamExt,
+    future::{BoxFuture, select_ok},
+};
 use jito_json_rpc_client::jsonrpc_client::rpc_client::RpcClient as JitoRpcClient;
-use solana_client::rpc_client::RpcClient;
+use serde::{Deserialize, Serialize};
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient,
+    rpc_client::RpcClient,
+    rpc_config::{RpcSendTransactionConfig, RpcSignatureSubscribeConfig},
+    rpc_response::{ProcessedSignatureResult, RpcSignatureResult},
+};
 use solana_sdk::{
+    account::Account,
     address_lookup_table::{AddressLookupTableAccount, state::AddressLookupTable},
+    commitment_config::CommitmentConfig,
+    compute_budget,
     instruction::Instruction,
     message::{VersionedMessage, v0},
+    packet::PACKET_DATA_SIZE,
     pubkey::Pubkey,
-    signature::Keypair,
+    rent::Rent,
+    signature::{Keypair, Signature},
     signer::Signer,
     system_instruction, system_transaction,
     transaction::{Transaction, VersionedTransaction},
 };
 use spl_token::{amount_to_ui_amount, ui_amount_to_amount};
 
-use tokio::time::Instant;
-use tracing::{error, info};
+use tokio::{task::JoinSet, time::Instant};
+use tracing::{debug, error, info, warn};
 
-use crate::jito::{self, get_tip_account, get_tip_value, wait_for_bundle_confirmation};
+use crate::{
+    alt, arb,
+    batch::MAX_BUNDLE_TXS,
+    error::TxError,
+    failover::{self, with_failover},
+    get_rpc_endpoints,
+    jito::{
+        self, get_tip_account, get_tip_value, wait_for_bundle_confirmation, wait_for_bundle_status,
+    },
+    latency::LatencyBreakdown,
+    metrics, program_error,
+    trigger::get_ws_endpoints,
+};
 
 pub async fn new_signed_and_send(
     client: &RpcClient,
     keypair: &Keypair,
     instructions: Vec<Instruction>,
+    confirmation: &ConfirmationConfig,
 ) -> Result<Vec<String>> {
     // send init tx
     let recent_blockhash = client.get_latest_blockhash()?;
@@ -34,7 +69,7 @@ pub async fn new_signed_and_send(
         recent_blockhash,
     );
 
-    if env::var("TX_SIMULATE").ok() == Some("true".to_string()) {
+    if simulate_only() {
         let simulate_result = client.simulate_transaction(&txn)?;
         if let Some(logs) = simulate_result.value.logs {
             for log in logs {
@@ -50,10 +85,9 @@ pub async fn new_signed_and_send(
     let start_time = Instant::now();
     // jito
     let tip_account = get_tip_account().await?;
-    // jito tip, the upper limit is 0.1
-    let mut tip = get_tip_value().await?;
-    tip = tip.min(0.1);
-    let tip_lamports = ui_amount_to_amount(tip, spl_token::native_mint::DECIMALS);
+    let tip = get_tip_value().await?;
+    let tip_lamports =
+        jito::TIP_POLICY.clamp(ui_amount_to_amount(tip, spl_token::native_mint::DECIMALS));
     info!(
         "tip account: {}, tip(sol): {}, lamports: {}",
         tip_account, tip, tip_lamports
@@ -87,8 +121,8 @@ pub async fn new_signed_and_send(
             }
         },
         bundle_id,
-        Duration::from_millis(1000),
-        Duration::from_secs(10),
+        confirmation.poll_interval,
+        confirmation.timeout,
         true,
     )
     .await?;
@@ -110,45 +144,579 @@ pub fn get_tip_instruction(
     system_instruction::transfer(from_pubkey, &tip_account, tip_lamports)
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SendMode {
+    /// Bundle through the jito block engine
+    #[default]
+    Jito,
+    /// Broadcast the signed transaction to every RPC_ENDPOINTS endpoint
+    Rpc,
+    /// Both of the above with the same signed transaction, the first to
+    /// confirm wins
+    Both,
+}
+
+/// Commitment an rpc sent transaction is preflighted and confirmed at
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Commitment {
+    Processed,
+    #[default]
+    Confirmed,
+    Finalized,
+}
+
+impl Commitment {
+    pub fn config(self) -> CommitmentConfig {
+        match self {
+            Commitment::Processed => CommitmentConfig::processed(),
+            Commitment::Confirmed => CommitmentConfig::confirmed(),
+            Commitment::Finalized => CommitmentConfig::finalized(),
+        }
+    }
+}
+
+/// How submitted bundles/transactions are polled for confirmation
+#[derive(Debug, Clone, Copy)]
+pub struct ConfirmationConfig {
+    pub poll_interval: Duration,
+    pub timeout: Duration,
+    // rpc sent transactions only, bundles are confirmed at `confirmed`
+    pub commitment: CommitmentConfig,
+    // a timeout fails the execution, otherwise it counts as submitted and
+    // the engine checks on it again later
+    pub fail_fast: bool,
+}
+
+impl Default for ConfirmationConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(1000),
+            timeout: Duration::from_secs(5),
+            commitment: CommitmentConfig::confirmed(),
+            fail_fast: true,
+        }
+    }
+}
+
+impl ConfirmationConfig {
+    pub fn new(
+        poll_interval: Duration,
+        timeout: Duration,
+        commitment: CommitmentConfig,
+    ) -> Result<Self> {
+        if poll_interval.is_zero() || poll_interval >= timeout {
+            return Err(anyhow!(
+                "confirmation poll interval {:?} must be non-zero and below the timeout {:?}",
+                poll_interval,
+                timeout
+            ));
+        }
+        Ok(Self {
+            poll_interval,
+            timeout,
+            commitment,
+            ..Default::default()
+        })
+    }
+}
+
+/// Compute unit price paid on the rpc send path, where the jito tip buys nothing
+#[derive(Debug, Clone, Copy)]
+pub struct PriorityFeeConfig {
+    // percentile of the recent prioritization fees on the written accounts
+    pub percentile: u8,
+    // micro-lamports per compute unit
+    pub max_compute_unit_price: u64,
+}
+
+impl Default for PriorityFeeConfig {
+    fn default() -> Self {
+        Self {
+            percentile: 75,
+            max_compute_unit_price: 1_000_000,
+        }
+    }
+}
+
+/// Estimate the compute unit price from the recent prioritization fees paid
+/// by transactions locking `accounts`, capped by the config
+pub fn estimate_compute_unit_price(
+    client: &RpcClient,
+    accounts: &[Pubkey],
+    config: &PriorityFeeConfig,
+) -> Result<u64, TxError> {
+    let mut fees: Vec<u64> = client
+        .get_recent_prioritization_fees(accounts)?
+        .into_iter()
+        .map(|fee| fee.prioritization_fee)
+        .collect();
+    let price = fee_percentile(&mut fees, config.percentile).min(config.max_compute_unit_price);
+    debug!(
+        "compute unit price: {} micro-lamports (p{} of {} slots)",
+        price,
+        config.percentile,
+        fees.len()
+    );
+    Ok(price)
+}
+
+fn fee_percentile(fees: &mut [u64], percentile: u8) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    fees.sort_unstable();
+    let index = (fees.len() - 1) * percentile.min(100) as usize / 100;
+    fees[index]
+}
+
+#[derive(Debug, Clone)]
+pub struct BroadcastConfig {
+    // per endpoint request timeout
+    pub timeout: Duration,
+    // preflight costs a simulation round trip on every endpoint
+    pub skip_preflight: bool,
+    pub preflight_commitment: CommitmentConfig,
+}
+
+impl Default for BroadcastConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(2),
+            skip_preflight: true,
+            preflight_commitment: CommitmentConfig::confirmed(),
+        }
+    }
+}
+
+/// How `send_versioned_transaction` submits and whether it waits for the
+/// transactions to land
+#[derive(Debug, Clone)]
+pub struct SendOptions {
+    pub send_mode: SendMode,
+    // the rpc send path, SendMode::Rpc and Both
+    pub broadcast: BroadcastConfig,
+    pub wait_for_confirmation: bool,
+    pub confirmation: ConfirmationConfig,
+}
+
+/// Send the same signed transaction to every endpoint at once, returns the
+/// signature and the endpoint that accepted it first
+pub async fn broadcast_transaction(
+    endpoints: &[String],
+    transaction: &VersionedTransaction,
+    config: &BroadcastConfig,
+) -> Result<(Signature, String), TxError> {
+    let mut tasks = JoinSet::new();
+    for endpoint in endpoints {
+        let endpoint = endpoint.clone();
+        let transaction = transaction.clone();
+        let send_config = RpcSendTransactionConfig {
+            skip_preflight: config.skip_preflight,
+            preflight_commitment: Some(config.preflight_commitment.commitment),
+            max_retries: Some(0),
+            ..Default::default()
+        };
+        let timeout = config.timeout;
+        tasks.spawn_blocking(move || {
+            let client = RpcClient::new_with_timeout(endpoint.clone(), timeout);
+            let result = client.send_transaction_with_config(&transaction, send_config);
+            (endpoint, result)
+        });
+    }
+
+    let mut errors = vec![];
+    while let Some(joined) = tasks.join_next().await {
+        let (endpoint, result) = joined.map_err(|e| TxError::Other(e.into()))?;
+        match result {
+            Ok(signature) => {
+                debug!("broadcast: {} accepted {}", endpoint, signature);
+                // the rest keep running in the background and are dropped
+                tasks.detach_all();
+                return Ok((signature, endpoint));
+            }
+            // another endpoint (or a previous attempt) got there first
+            Err(e) if is_already_processed(&e.to_string()) => {
+                tasks.detach_all();
+                return Ok((transaction.signatures[0], endpoint));
+            }
+            Err(e) => {
+                debug!("broadcast: {} rejected: {}", endpoint, e);
+                errors.push(format!("{}: {}", endpoint, e));
+            }
+        }
+    }
+    Err(TxError::BroadcastFailed(errors.join("; ")))
+}
+
+fn is_already_processed(err: &str) -> bool {
+    err.contains("AlreadyProcessed") || err.contains("already been processed")
+}
+
+/// Poll the signature status until it reaches `commitment` or `timeout`
+/// passes, returns the slot it landed in
+pub async fn confirm_signature(
+    client: &RpcClient,
+    signature: &Signature,
+    commitment: CommitmentConfig,
+    poll_interval: Duration,
+    timeout: Duration,
+) -> Result<u64, TxError> {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        let statuses = with_failover(client, |client| {
+            client.get_signature_statuses(&[*signature])
+        })?
+        .value;
+        if let Some(Some(status)) = statuses.first() {
+            if let Some(err) = &status.err {
+                return Err(TxError::TransactionFailed(err.to_string()));
+            }
+            if status.satisfies_commitment(commitment) {
+                return Ok(status.slot);
+            }
+        }
+        tokio::time::sleep(poll_interval).await;
+    }
+    Err(TxError::SignatureTimeout {
+        signature: signature.to_string(),
+    })
+}
+
+/// Wait for the signature notification over the websocket, it arrives
+/// within a slot of landing where polling lags by up to a poll interval.
+/// Returns the slot it landed in.
+pub async fn confirm_signature_ws(
+    ws_url: &str,
+    signature: &Signature,
+    commitment: CommitmentConfig,
+    timeout: Duration,
+) -> Result<u64, TxError> {
+    let notified = async {
+        let client = PubsubClient::new(ws_url)
+            .await
+            .map_err(|e| TxError::Other(anyhow!("{}: {}", ws_url, e)))?;
+        let config = RpcSignatureSubscribeConfig {
+            commitment: Some(commitment),
+            enable_received_notification: Some(false),
+        };
+        let (mut notifications, _unsubscribe) = client
+            .signature_subscribe(signature, Some(config))
+            .await
+            .map_err(|e| TxError::Other(anyhow!("{}: {}", ws_url, e)))?;
+        let Some(response) = notifications.next().await else {
+            return Err(TxError::Other(anyhow!("{}: websocket closed", ws_url)));
+        };
+        match response.value {
+            RpcSignatureResult::ProcessedSignature(ProcessedSignatureResult { err: Some(err) }) => {
+                Err(TxError::TransactionFailed(err.to_string()))
+            }
+            RpcSignatureResult::ProcessedSignature(_) => Ok(response.context.slot),
+            RpcSignatureResult::ReceivedSignature(_) => Err(TxError::Other(anyhow!(
+                "{}: unexpected received notification",
+                ws_url
+            ))),
+        }
+    };
+    tokio::time::timeout(timeout, notified)
+        .await
+        .unwrap_or_else(|_| {
+            Err(TxError::SignatureTimeout {
+                signature: signature.to_string(),
+            })
+        })
+}
+
+/// Ok(Some(slot)) once the websocket reports the signature landed, Ok(None)
+/// when RPC_WS_ENDPOINTS isn't set or the websocket gave no answer and the
+/// caller should poll instead
+async fn confirm_via_ws(
+    signature: &Signature,
+    commitment: CommitmentConfig,
+    timeout: Duration,
+) -> Result<Option<u64>, TxError> {
+    let Some(ws_url) = get_ws_endpoints()
+        .ok()
+        .and_then(|urls| urls.into_iter().next())
+    else {
+        return Ok(None);
+    };
+    match confirm_signature_ws(&ws_url, signature, commitment, timeout).await {
+        Ok(slot) => Ok(Some(slot)),
+        Err(e @ TxError::TransactionFailed(_)) => Err(e),
+        Err(e) => {
+            debug!("websocket confirmation failed, polling instead: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+// time left for the polling fallback, at least one poll
+fn remaining_timeout(start: Instant, confirmation: &ConfirmationConfig) -> Duration {
+    confirmation
+        .timeout
+        .saturating_sub(start.elapsed())
+        .max(confirmation.poll_interval)
+}
+
+/// What a send produced. The slots are known once the transactions are
+/// submitted and confirmed respectively.
+#[derive(Debug, Clone, Default)]
+pub struct SendResult {
+    pub signatures: Vec<String>,
+    // set by the caller from the submission callback
+    pub bundle_id: Option<String>,
+    pub submit_slot: Option<u64>,
+    pub landed_slot: Option<u64>,
+}
+
+impl SendResult {
+    /// Slots between submission and landing
+    pub fn slots_to_land(&self) -> Option<u64> {
+        Some(self.landed_slot?.saturating_sub(self.submit_slot?))
+    }
+
+    fn log(&self) {
+        if let (Some(submit_slot), Some(landed_slot)) = (self.submit_slot, self.landed_slot) {
+            info!(
+                "🎯 landed in slot {}, submitted at {} ({} slots)",
+                landed_slot,
+                submit_slot,
+                landed_slot.saturating_sub(submit_slot)
+            );
+        }
+    }
+}
+
+// most recent slot seen in an rpc response context, and when
+static OBSERVED_SLOT: Mutex<Option<(u64, Instant)>> = Mutex::new(None);
+// a couple of slots, older than this the rpc is asked again
+const OBSERVED_SLOT_MAX_AGE: Duration = Duration::from_secs(1);
+
+fn observe_slot(slot: u64) {
+    let mut observed = OBSERVED_SLOT.lock().unwrap();
+    if observed.is_none_or(|(observed_slot, _)| observed_slot <= slot) {
+        *observed = Some((slot, Instant::now()));
+    }
+}
+
+// the slot right before submission, from the last response context when
+// it's fresh enough
+fn current_slot(client: &RpcClient) -> Option<u64> {
+    let observed = *OBSERVED_SLOT.lock().unwrap();
+    if let Some((slot, at)) = observed.filter(|(_, at)| at.elapsed() < OBSERVED_SLOT_MAX_AGE) {
+        debug!("submit slot {} observed {:?} ago", slot, at.elapsed());
+        return Some(slot);
+    }
+    match with_failover(client, |client| {
+        client.get_slot_with_commitment(CommitmentConfig::processed())
+    }) {
+        Ok(slot) => {
+            observe_slot(slot);
+            Some(slot)
+        }
+        Err(e) => {
+            debug!("failed to get the submit slot: {}", e);
+            None
+        }
+    }
+}
+
+/// TX_SIMULATE=true: transactions are simulated and never sent
+pub fn simulate_only() -> bool {
+    env::var("TX_SIMULATE").ok() == Some("true".to_string())
+}
+
 pub async fn send_versioned_transaction(
     client: &RpcClient,
     keypair: &Keypair,
-    versioned_transaction: VersionedTransaction,
+    versioned_transactions: Vec<VersionedTransaction>,
     tip: Option<(Pubkey, u64)>,
-    wait_for_confirmation: bool,
-) -> Result<Vec<String>> {
+    options: &SendOptions,
+    on_submitted: impl FnOnce(&str),
+    latency: &mut LatencyBreakdown,
+) -> Result<SendResult, TxError> {
+    let send_mode = options.send_mode;
+    let Some(first) = versioned_transactions.first() else {
+        return Err(TxError::CompileFailed("no transaction to send".to_string()));
+    };
     // TX_SIMULATE
-    if env::var("TX_SIMULATE").ok() == Some("true".to_string()) {
+    if simulate_only() {
+        // later txs depend on the state the first one leaves behind
+        if versioned_transactions.len() > 1 {
+            debug!(
+                "simulating the first of {} transactions only",
+                versioned_transactions.len()
+            );
+        }
         let signed_versioned_transaction =
-            VersionedTransaction::try_new(versioned_transaction.message, &[&keypair])?;
+            VersionedTransaction::try_new(first.message.clone(), &[&keypair])
+                .map_err(|e| TxError::CompileFailed(e.to_string()))?;
         let simulate_result = client
             .simulate_transaction(&signed_versioned_transaction)
             .inspect_err(|err| {
                 println!("err: {}", err);
             })?;
-        if let Some(logs) = simulate_result.value.logs {
-            for log in logs {
-                info!("{}", log);
-            }
+        let logs = simulate_result.value.logs.unwrap_or_default();
+        for log in &logs {
+            info!("{}", log);
         }
         return match simulate_result.value.err {
-            Some(err) => Err(anyhow!("{}", err)),
-            None => Ok(vec![]),
+            Some(err) => Err(TxError::SimulationFailed(program_error::decode(
+                &err,
+                Some(&first.message),
+                &logs,
+            ))),
+            None => Ok(SendResult::default()),
         };
     }
 
-    let jito_client = Arc::new(JitoRpcClient::new(format!(
+    let submit_slot = current_slot(client);
+    // split legs are only safe when they land or fail together
+    if send_mode != SendMode::Jito && versioned_transactions.len() > 1 {
+        return Err(TxError::Other(anyhow!(
+            "{} transactions can only be sent as a jito bundle",
+            versioned_transactions.len()
+        )));
+    }
+
+    if send_mode == SendMode::Rpc {
+        let signed_versioned_transaction =
+            VersionedTransaction::try_new(first.message.clone(), &[&keypair])
+                .map_err(|e| TxError::CompileFailed(e.to_string()))?;
+        // a separate tip tx only makes sense inside a bundle
+        if tip.is_some() {
+            debug!("send mode rpc: dropping the jito tip transaction");
+        }
+        let endpoints = get_rpc_endpoints()?;
+        let start_submit = Instant::now();
+        let (signature, endpoint) = broadcast_transaction(
+            &endpoints,
+            &signed_versioned_transaction,
+            &options.broadcast,
+        )
+        .await?;
+        latency.bundle_submit = Some(start_submit.elapsed());
+        info!(
+            "📡 signature: {} (first accepted by {})",
+            signature, endpoint
+        );
+        on_submitted(&signature.to_string());
+
+        let mut sent = SendResult {
+            submit_slot,
+            ..Default::default()
+        };
+        if options.wait_for_confirmation {
+            let start_confirm = Instant::now();
+            let confirmed = confirm_transaction(client, &signature, &options.confirmation).await;
+            latency.confirmation = Some(start_confirm.elapsed());
+            sent.landed_slot = Some(confirmed?);
+            sent.signatures = vec![signature.to_string()];
+            sent.log();
+        }
+        return Ok(sent);
+    }
+
+    let bundle = sign_bundle(keypair, versioned_transactions, tip)?;
+    if send_mode == SendMode::Jito {
+        return send_bundle(
+            client,
+            bundle,
+            options.wait_for_confirmation,
+            &options.confirmation,
+            on_submitted,
+            latency,
+        )
+        .await;
+    }
+
+    let mut sent = race_jito_and_rpc(
+        client,
+        jito_client(),
+        bundle,
+        options,
+        on_submitted,
+        latency,
+    )
+    .await?;
+    sent.submit_slot = submit_slot;
+    sent.log();
+    Ok(sent)
+}
+
+fn jito_client() -> Arc<JitoRpcClient> {
+    Arc::new(JitoRpcClient::new(format!(
         "{}/api/v1/bundles",
         jito::BLOCK_ENGINE_URL.to_string()
-    )));
+    )))
+}
+
+/// Submit a signed bundle to the jito block engine, every transaction of
+/// it lands or none does. `on_submitted` gets the bundle id.
+pub async fn send_bundle(
+    client: &RpcClient,
+    bundle: Vec<VersionedTransaction>,
+    wait_for_confirmation: bool,
+    confirmation: &ConfirmationConfig,
+    on_submitted: impl FnOnce(&str),
+    latency: &mut LatencyBreakdown,
+) -> Result<SendResult, TxError> {
+    let submit_slot = current_slot(client);
+    let jito_client = jito_client();
+    let signatures: Vec<Signature> = bundle.iter().map(|tx| tx.signatures[0]).collect();
+    let start_submit = Instant::now();
+    let bundle_id =
+        jito_client
+            .send_bundle(&bundle)
+            .await
+            .map_err(|e| TxError::BundleRejected {
+                reason: e.to_string(),
+            })?;
+    latency.bundle_submit = Some(start_submit.elapsed());
+    info!("📦 bundle_id: {}", bundle_id);
+    on_submitted(&bundle_id);
+
+    let mut sent = SendResult {
+        submit_slot,
+        ..Default::default()
+    };
+    if wait_for_confirmation {
+        let start_confirm = Instant::now();
+        let confirmed = confirm_bundle(
+            jito_client,
+            bundle_id,
+            &signatures,
+            bundle.first().map(|tx| &tx.message),
+            confirmation,
+        )
+        .await;
+        latency.confirmation = Some(start_confirm.elapsed());
+        let (txs, landed_slot) = confirmed?;
+        sent.signatures = txs;
+        sent.landed_slot = Some(landed_slot);
+        sent.log();
+    }
+    Ok(sent)
+}
+
+/// Sign txs in order, the tip tx goes last
+pub fn sign_bundle(
+    keypair: &Keypair,
+    versioned_transactions: Vec<VersionedTransaction>,
+    tip: Option<(Pubkey, u64)>,
+) -> Result<Vec<VersionedTransaction>, TxError> {
     let mut bundle: Vec<VersionedTransaction> = vec![];
-    // sign tx
-    let recent_blockhash = *versioned_transaction.message.recent_blockhash();
-    let signed_versioned_transaction =
-        VersionedTransaction::try_new(versioned_transaction.message, &[&keypair])?;
-    bundle.push(signed_versioned_transaction);
+    let mut recent_blockhash = None;
+    for versioned_transaction in versioned_transactions {
+        recent_blockhash.get_or_insert(*versioned_transaction.message.recent_blockhash());
+        let signed_versioned_transaction =
+            VersionedTransaction::try_new(versioned_transaction.message, &[&keypair])
+                .map_err(|e| TxError::CompileFailed(e.to_string()))?;
+        bundle.push(signed_versioned_transaction);
+    }
 
-    if let Some((tip_account, tip_lamports)) = tip {
+    if let (Some((tip_account, tip_lamports)), Some(recent_blockhash)) = (tip, recent_blockhash) {
         bundle.push(VersionedTransaction::from(system_transaction::transfer(
             &keypair,
             &tip_account,
@@ -156,33 +724,308 @@ pub async fn send_versioned_transaction(
             recent_blockhash,
         )));
     }
+    Ok(bundle)
+}
 
-    let bundle_id = jito_client.send_bundle(&bundle).await?;
-    info!("📦 bundle_id: {}", bundle_id);
+/// Submit the bundle to jito and its first transaction to every rpc
+/// endpoint at once. Both carry the same signed transaction, so at most one
+/// copy lands. Confirmation returns with the first path to report it,
+/// which is counted in `arb_send_path_total`.
+async fn race_jito_and_rpc(
+    client: &RpcClient,
+    jito_client: Arc<JitoRpcClient>,
+    bundle: Vec<VersionedTransaction>,
+    options: &SendOptions,
+    on_submitted: impl FnOnce(&str),
+    latency: &mut LatencyBreakdown,
+) -> Result<SendResult, TxError> {
+    let confirmation = &options.confirmation;
+    let signatures: Vec<Signature> = bundle.iter().map(|tx| tx.signatures[0]).collect();
+    let endpoints = get_rpc_endpoints()?;
+    let start_submit = Instant::now();
+    let (bundle_id, broadcast) = tokio::join!(
+        jito_client.send_bundle(&bundle),
+        broadcast_transaction(&endpoints, &bundle[0], &options.broadcast),
+    );
+    latency.bundle_submit = Some(start_submit.elapsed());
+    let bundle_id = bundle_id
+        .inspect_err(|e| debug!("send mode both: bundle rejected: {}", e))
+        .ok();
+    let broadcast = broadcast
+        .inspect_err(|e| debug!("send mode both: broadcast failed: {}", e))
+        .ok();
+    match (&bundle_id, &broadcast) {
+        (None, None) => {
+            return Err(TxError::BroadcastFailed(
+                "both jito and every rpc endpoint rejected the transaction".to_string(),
+            ));
+        }
+        (Some(bundle_id), _) => {
+            info!("📦 bundle_id: {}", bundle_id);
+            on_submitted(bundle_id);
+        }
+        (None, Some((signature, _))) => on_submitted(&signature.to_string()),
+    }
+    if let Some((signature, endpoint)) = &broadcast {
+        info!(
+            "📡 signature: {} (first accepted by {})",
+            signature, endpoint
+        );
+    }
+    if !options.wait_for_confirmation {
+        return Ok(SendResult::default());
+    }
 
-    let txs = if wait_for_confirmation {
-        wait_for_bundle_confirmation(
-            move |id: String| {
-                let client = Arc::clone(&jito_client);
-                async move {
-                    let response = client.get_bundle_statuses(&[id]).await;
-                    let statuses = response.inspect_err(|err| {
-                        error!("Error fetching bundle status: {:?}", err);
-                    })?;
-                    Ok(statuses.value)
-                }
-            },
-            bundle_id,
-            Duration::from_millis(1000),
-            Duration::from_secs(5),
-            false,
-        )
-        .await?
-    } else {
-        vec![]
+    let start_confirm = Instant::now();
+    let mut paths: Vec<BoxFuture<'_, Result<(&'static str, Vec<String>, u64), TxError>>> = vec![];
+    if let Some(bundle_id) = bundle_id {
+        let signatures = &signatures;
+        let message = bundle.first().map(|tx| &tx.message);
+        paths.push(Box::pin(async move {
+            let (txs, slot) =
+                confirm_bundle(jito_client, bundle_id, signatures, message, confirmation).await?;
+            Ok(("jito", txs, slot))
+        }));
+    }
+    if let Some((signature, _)) = broadcast {
+        paths.push(Box::pin(async move {
+            let slot = confirm_transaction(client, &signature, confirmation).await?;
+            Ok(("rpc", vec![signature.to_string()], slot))
+        }));
+    }
+    // the losing wait is dropped, an error only counts once both failed
+    let confirmed = select_ok(paths).await;
+    latency.confirmation = Some(start_confirm.elapsed());
+    let ((path, txs, slot), _) = confirmed?;
+    info!("send mode both: {} confirmed first", path);
+    metrics::inc_counter("arb_send_path_total", &[("path", path)]);
+    Ok(SendResult {
+        signatures: txs,
+        landed_slot: Some(slot),
+        ..Default::default()
+    })
+}
+
+/// Wait for a signature over the websocket, polling its status when that
+/// isn't available. Returns the landing slot.
+async fn confirm_transaction(
+    client: &RpcClient,
+    signature: &Signature,
+    confirmation: &ConfirmationConfig,
+) -> Result<u64, TxError> {
+    let start = Instant::now();
+    match confirm_via_ws(signature, confirmation.commitment, confirmation.timeout).await? {
+        Some(slot) => Ok(slot),
+        None => {
+            confirm_signature(
+                client,
+                signature,
+                confirmation.commitment,
+                confirmation.poll_interval,
+                remaining_timeout(start, confirmation),
+            )
+            .await
+        }
+    }
+}
+
+/// Wait for the bundle's first signature over the websocket, polling the
+/// bundle status when that isn't available. Returns the landed signatures
+/// and slot. `message` is the first transaction's, used to name the
+/// program of a failed bundle.
+async fn confirm_bundle(
+    jito_client: Arc<JitoRpcClient>,
+    bundle_id: String,
+    signatures: &[Signature],
+    message: Option<&VersionedMessage>,
+    confirmation: &ConfirmationConfig,
+) -> Result<(Vec<String>, u64), TxError> {
+    let start = Instant::now();
+    if let Some(slot) = confirm_via_ws(
+        &signatures[0],
+        CommitmentConfig::confirmed(),
+        confirmation.timeout,
+    )
+    .await?
+    {
+        return Ok((signatures.iter().map(Signature::to_string).collect(), slot));
+    }
+    let bundle_status = wait_for_bundle_status(
+        move |id: String| {
+            let client = Arc::clone(&jito_client);
+            async move {
+                let response = client.get_bundle_statuses(&[id]).await;
+                let statuses = response.inspect_err(|err| {
+                    error!("Error fetching bundle status: {:?}", err);
+                })?;
+                Ok(statuses.value)
+            }
+        },
+        bundle_id,
+        confirmation.poll_interval,
+        remaining_timeout(start, confirmation),
+        false,
+    )
+    .await?;
+    if let Some(err) = &bundle_status.err.err {
+        return Err(TxError::TransactionFailed(program_error::decode_value(
+            err, message,
+        )));
+    }
+    Ok((bundle_status.transactions, bundle_status.slot))
+}
+
+/// Decode one base64 encoded bincode `VersionedTransaction` per line,
+/// blank lines and `#` comments are ignored
+pub fn decode_transactions(content: &str) -> Result<Vec<VersionedTransaction>> {
+    content
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let bytes = BASE64_STANDARD
+                .decode(line)
+                .map_err(|e| anyhow!("line {}: invalid base64: {}", i + 1, e))?;
+            bincode::deserialize::<VersionedTransaction>(&bytes)
+                .map_err(|e| anyhow!("line {}: invalid transaction: {}", i + 1, e))
+        })
+        .collect()
+}
+
+/// Check prepared transactions before they go out as one bundle: at most
+/// `MAX_BUNDLE_TXS`, all on the same blockhash, each fitting a packet and
+/// validly signed. Unsigned ones are refused, or signed by `payer` when
+/// given.
+pub fn prepare_bundle(bundle: &mut [VersionedTransaction], payer: Option<&Keypair>) -> Result<()> {
+    let Some(first) = bundle.first() else {
+        return Err(anyhow!("no transactions to bundle"));
     };
+    if bundle.len() > MAX_BUNDLE_TXS {
+        return Err(anyhow!(
+            "{} transactions, a bundle takes at most {}",
+            bundle.len(),
+            MAX_BUNDLE_TXS
+        ));
+    }
+    let blockhash = *first.message.recent_blockhash();
+    for (i, tx) in bundle.iter_mut().enumerate() {
+        let n = i + 1;
+        if *tx.message.recent_blockhash() != blockhash {
+            return Err(anyhow!(
+                "transaction {} uses blockhash {}, the first one {}",
+                n,
+                tx.message.recent_blockhash(),
+                blockhash
+            ));
+        }
+        if tx
+            .signatures
+            .iter()
+            .all(|signature| *signature == Signature::default())
+        {
+            let Some(payer) = payer else {
+                return Err(anyhow!(
+                    "transaction {} is unsigned, pass --sign to sign it with the payer",
+                    n
+                ));
+            };
+            *tx = VersionedTransaction::try_new(tx.message.clone(), &[payer])
+                .map_err(|e| anyhow!("transaction {}: failed to sign: {}", n, e))?;
+        }
+        if tx.verify_with_results().contains(&false) {
+            return Err(anyhow!(
+                "transaction {} has a missing or invalid signature",
+                n
+            ));
+        }
+        let size = bincode::serialized_size(tx)? as usize;
+        if size > PACKET_DATA_SIZE {
+            return Err(anyhow!(
+                "transaction {} is {} bytes, above the {} byte limit",
+                n,
+                size,
+                PACKET_DATA_SIZE
+            ));
+        }
+    }
+    Ok(())
+}
 
-    Ok(txs)
+// lookup table accounts and the slot they were read at. Tables a lagging
+// node has no account for are asked for once more on another endpoint, a
+// transaction compiled without them would fail to fit or resolve.
+fn fetch_lookup_tables(
+    client: &RpcClient,
+    keys: &[Pubkey],
+) -> Result<(u64, Vec<(Pubkey, Account)>), TxError> {
+    let response = with_failover(client, |client| {
+        client.get_multiple_accounts_with_commitment(keys, client.commitment())
+    })?;
+    let mut slot = response.context.slot;
+    let mut accounts = response.value;
+    let missing = missing_keys(keys, &accounts);
+    if !missing.is_empty() {
+        let Some(other) = failover::other_client(client) else {
+            return Err(TxError::MissingLookupTables(join_keys(&missing)));
+        };
+        warn!(
+            "rpc {} has no lookup tables {}, retrying on {}",
+            client.url(),
+            join_keys(&missing),
+            other.url()
+        );
+        let retry = other.get_multiple_accounts_with_commitment(&missing, other.commitment())?;
+        // the older slot keeps entries extended in between unused
+        slot = slot.min(retry.context.slot);
+        let mut found = retry.value.into_iter();
+        for account in accounts.iter_mut().filter(|account| account.is_none()) {
+            *account = found.next().flatten();
+        }
+        let missing = missing_keys(keys, &accounts);
+        if !missing.is_empty() {
+            return Err(TxError::MissingLookupTables(join_keys(&missing)));
+        }
+    }
+    let accounts = keys
+        .iter()
+        .zip(accounts)
+        .filter_map(|(key, account)| Some((*key, account?)))
+        .collect();
+    Ok((slot, accounts))
+}
+
+fn missing_keys(keys: &[Pubkey], accounts: &[Option<Account>]) -> Vec<Pubkey> {
+    keys.iter()
+        .zip(accounts)
+        .filter(|(_, account)| account.is_none())
+        .map(|(key, _)| *key)
+        .collect()
+}
+
+fn join_keys(keys: &[Pubkey]) -> String {
+    keys.iter()
+        .map(Pubkey::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Move unsent transactions to the latest blockhash, they are signed again
+/// when sent
+pub fn refresh_blockhash(
+    client: &RpcClient,
+    versioned_transactions: &mut [VersionedTransaction],
+    latency: &mut LatencyBreakdown,
+) -> Result<(), TxError> {
+    let start_blockhash = Instant::now();
+    let blockhash = with_failover(client, RpcClient::get_latest_blockhash)?;
+    latency.blockhash = Some(start_blockhash.elapsed());
+    for versioned_transaction in versioned_transactions {
+        versioned_transaction
+            .message
+            .set_recent_blockhash(blockhash);
+    }
+    Ok(())
 }
 
 pub fn create_tx_with_address_table_lookup(
@@ -190,34 +1033,551 @@ pub fn create_tx_with_address_table_lookup(
     instructions: &mut Vec<Instruction>,
     address_lookup_table_keys: &Vec<Pubkey>,
     payer: &Keypair,
-) -> Result<VersionedTransaction> {
-    let raw_accounts = client.get_multiple_accounts(&address_lookup_table_keys)?;
+    latency: &mut LatencyBreakdown,
+) -> Result<VersionedTransaction, TxError> {
+    let mut txs = create_txs_with_address_table_lookup(
+        client,
+        std::slice::from_ref(&*instructions),
+        address_lookup_table_keys,
+        payer,
+        latency,
+    )?;
+    Ok(txs.remove(0))
+}
 
-    let address_lookup_table_accounts = address_lookup_table_keys
+/// Compile one transaction per instruction list against the same lookup
+/// tables and blockhash, in order, ready to go out as a bundle
+pub fn create_txs_with_address_table_lookup(
+    client: &RpcClient,
+    instructions: &[Vec<Instruction>],
+    address_lookup_table_keys: &[Pubkey],
+    payer: &Keypair,
+    latency: &mut LatencyBreakdown,
+) -> Result<Vec<VersionedTransaction>, TxError> {
+    // jupiter may list a table twice, and ours may be among them
+    let mut seen = HashSet::new();
+    let address_lookup_table_keys: Vec<Pubkey> = address_lookup_table_keys
         .iter()
-        .zip(raw_accounts.iter())
-        .filter_map(|(key, account_opt)| {
-            account_opt.as_ref().and_then(|account| {
-                AddressLookupTable::deserialize(&account.data)
-                    .map(|lookup_table| AddressLookupTableAccount {
-                        key: *key,
-                        addresses: lookup_table.addresses.to_vec(),
-                    })
-                    .ok()
-            })
+        .chain(alt::OWN_ALT.iter())
+        .filter(|key| seen.insert(**key))
+        .copied()
+        .collect();
+
+    let start_fetch = Instant::now();
+    let (current_slot, accounts) = fetch_lookup_tables(client, &address_lookup_table_keys)?;
+    latency.alt_fetch = Some(start_fetch.elapsed());
+
+    // the response slot tells which entries of a freshly extended table are active
+    observe_slot(current_slot);
+    let address_lookup_table_accounts = accounts
+        .iter()
+        .filter_map(|(key, account)| {
+            AddressLookupTable::deserialize(&account.data)
+                .ok()
+                .and_then(|lookup_table| alt::active_account(*key, &lookup_table, current_slot))
         })
         .collect::<Vec<AddressLookupTableAccount>>();
+    let address_lookup_table_accounts =
+        prune_lookup_tables(address_lookup_table_accounts, instructions);
+
+    let start_blockhash = Instant::now();
+    let blockhash = with_failover(client, RpcClient::get_latest_blockhash)?;
+    latency.blockhash = Some(start_blockhash.elapsed());
+
+    let start_compile = Instant::now();
+    let messages = instructions
+        .iter()
+        .map(|instructions| {
+            v0::Message::try_compile(
+                &payer.pubkey(),
+                instructions,
+                &address_lookup_table_accounts,
+                blockhash,
+            )
+            .map_err(|e| TxError::CompileFailed(e.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    latency.compile = Some(start_compile.elapsed());
+
+    let start_sign = Instant::now();
+    let txs = messages
+        .into_iter()
+        .map(|message| {
+            VersionedTransaction::try_new(VersionedMessage::V0(message), &[payer])
+                .map_err(|e| TxError::CompileFailed(e.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    latency.sign = Some(start_sign.elapsed());
+
+    Ok(txs)
+}
+
+// compute budget instruction tags
+const SET_COMPUTE_UNIT_LIMIT_TAG: u8 = 2;
+const SET_COMPUTE_UNIT_PRICE_TAG: u8 = 3;
+// units per instruction when no limit is set, and the cap
+const DEFAULT_COMPUTE_UNIT_LIMIT: u64 = 200_000;
+const MAX_COMPUTE_UNIT_LIMIT: u64 = 1_400_000;
+
+// (compute unit limit, compute unit price) a message pays for
+fn compute_budget_of(message: &VersionedMessage) -> (u64, u64) {
+    let keys = message.static_account_keys();
+    let mut compute_unit_limit = None;
+    let mut compute_unit_price = 0u64;
+    let mut other_instructions = 0u64;
+    for ix in message.instructions() {
+        if keys.get(ix.program_id_index as usize) != Some(&compute_budget::id()) {
+            other_instructions += 1;
+            continue;
+        }
+        match ix.data.split_first() {
+            Some((&SET_COMPUTE_UNIT_LIMIT_TAG, data)) => {
+                compute_unit_limit = data
+                    .try_into()
+                    .ok()
+                    .map(|bytes| u32::from_le_bytes(bytes) as u64);
+            }
+            Some((&SET_COMPUTE_UNIT_PRICE_TAG, data)) => {
+                compute_unit_price = data.try_into().map(u64::from_le_bytes).unwrap_or(0);
+            }
+            _ => {}
+        }
+    }
+    let compute_unit_limit = compute_unit_limit
+        .unwrap_or(other_instructions * DEFAULT_COMPUTE_UNIT_LIMIT)
+        .min(MAX_COMPUTE_UNIT_LIMIT);
+    (compute_unit_limit, compute_unit_price)
+}
+
+/// Lamports paid for `compute_unit_limit` units at `compute_unit_price`
+/// micro-lamports each, rounded up like the runtime does
+pub fn priority_fee(compute_unit_limit: u64, compute_unit_price: u64) -> u64 {
+    (compute_unit_price as u128 * compute_unit_limit as u128).div_ceil(1_000_000) as u64
+}
+
+/// Lamports the transactions pay in fees: the base fee per signature plus
+/// the priority fee set by their compute budget instructions
+pub fn estimate_fee(versioned_transactions: &[VersionedTransaction]) -> u64 {
+    versioned_transactions
+        .iter()
+        .map(|tx| {
+            let signatures = tx.message.header().num_required_signatures as u64;
+            let (compute_unit_limit, compute_unit_price) = compute_budget_of(&tx.message);
+            signatures * arb::BASE_FEE_LAMPORTS
+                + priority_fee(compute_unit_limit, compute_unit_price)
+        })
+        .sum()
+}
+
+/// Compute unit limit for a simulation that consumed `units_consumed`, plus
+/// a margin and capped at the per-transaction maximum. None when the
+/// simulation reported nothing to go by.
+pub fn adjusted_compute_unit_limit(units_consumed: Option<u64>, margin_pct: u16) -> Option<u32> {
+    let units = units_consumed.filter(|units| *units > 0)?;
+    let limit = (units as u128 * (100 + margin_pct as u128)).div_ceil(100);
+    Some(limit.min(MAX_COMPUTE_UNIT_LIMIT as u128) as u32)
+}
+
+// rewrite the SetComputeUnitLimit of a compiled message in place, false when
+// it has none
+fn set_compute_unit_limit(message: &mut VersionedMessage, limit: u32) -> bool {
+    let Some(program_index) = message
+        .static_account_keys()
+        .iter()
+        .position(|key| *key == compute_budget::id())
+    else {
+        return false;
+    };
+    let instructions = match message {
+        VersionedMessage::Legacy(message) => &mut message.instructions,
+        VersionedMessage::V0(message) => &mut message.instructions,
+    };
+    let Some(ix) = instructions.iter_mut().find(|ix| {
+        ix.program_id_index as usize == program_index
+            && ix.data.first() == Some(&SET_COMPUTE_UNIT_LIMIT_TAG)
+    }) else {
+        return false;
+    };
+    ix.data = [&[SET_COMPUTE_UNIT_LIMIT_TAG][..], &limit.to_le_bytes()].concat();
+    true
+}
+
+/// Simulate the transaction, a failing simulation fails here before anything
+/// is submitted. With `margin_pct` its compute unit limit shrinks to the
+/// units it consumed plus the margin, re-signing it once with the new limit
+/// and the same blockhash.
+pub fn presimulate(
+    client: &RpcClient,
+    payer: &Keypair,
+    versioned_transactions: &mut [VersionedTransaction],
+    margin_pct: Option<u16>,
+) -> Result<(), TxError> {
+    // later txs depend on the state the first one leaves behind
+    let [tx] = versioned_transactions else {
+        debug!(
+            "presimulate: skipping a bundle of {} transactions",
+            versioned_transactions.len()
+        );
+        return Ok(());
+    };
+    let signed = VersionedTransaction::try_new(tx.message.clone(), &[payer])
+        .map_err(|e| TxError::CompileFailed(e.to_string()))?;
+    let simulation = with_failover(client, |client| client.simulate_transaction(&signed))?.value;
+    if let Some(err) = simulation.err {
+        let logs = simulation.logs.unwrap_or_default();
+        for log in &logs {
+            debug!("{}", log);
+        }
+        return Err(TxError::SimulationFailed(program_error::decode(
+            &err,
+            Some(&tx.message),
+            &logs,
+        )));
+    }
+    let Some(margin_pct) = margin_pct else {
+        debug!(
+            "presimulate: simulation passed, {} units consumed",
+            simulation.units_consumed.unwrap_or_default()
+        );
+        return Ok(());
+    };
+    let Some(limit) = adjusted_compute_unit_limit(simulation.units_consumed, margin_pct) else {
+        warn!("presimulate: no units consumed reported, keeping the compute unit limit");
+        return Ok(());
+    };
+
+    let (original, compute_unit_price) = compute_budget_of(&tx.message);
+    let mut message = tx.message.clone();
+    if !set_compute_unit_limit(&mut message, limit) {
+        debug!("presimulate: no compute unit limit instruction to rewrite");
+        return Ok(());
+    }
+    *tx = VersionedTransaction::try_new(message, &[payer])
+        .map_err(|e| TxError::CompileFailed(e.to_string()))?;
+    let saved = priority_fee(original, compute_unit_price)
+        .saturating_sub(priority_fee(limit as u64, compute_unit_price));
+    info!(
+        "presimulate: compute unit limit {} -> {} ({} consumed), priority fee saved: {} lamports",
+        original,
+        limit,
+        simulation.units_consumed.unwrap_or_default(),
+        saved
+    );
+    Ok(())
+}
+
+/// Fail before submitting when the payer can't cover `spend` lamports plus
+/// the fees of the transactions. The payer must also stay rent exempt, the
+/// runtime rejects a transfer that leaves it with less.
+pub fn check_headroom(
+    client: &RpcClient,
+    payer: &Pubkey,
+    versioned_transactions: &[VersionedTransaction],
+    spend: u64,
+) -> Result<(), TxError> {
+    let required =
+        spend + estimate_fee(versioned_transactions) + Rent::default().minimum_balance(0);
+    let available = with_failover(client, |client| client.get_balance(payer))?;
+    if available < required {
+        return Err(TxError::InsufficientBalance {
+            required,
+            available,
+        });
+    }
+    Ok(())
+}
+
+/// Drop the tables that hold none of the accounts the instructions could
+/// load through them. Signers and program ids always stay in the static
+/// keys, a match on those alone doesn't count.
+pub fn prune_lookup_tables(
+    tables: Vec<AddressLookupTableAccount>,
+    instructions: &[Vec<Instruction>],
+) -> Vec<AddressLookupTableAccount> {
+    let program_ids: HashSet<Pubkey> = instructions
+        .iter()
+        .flatten()
+        .map(|ix| ix.program_id)
+        .collect();
+    let loadable: HashSet<Pubkey> = instructions
+        .iter()
+        .flatten()
+        .flat_map(|ix| ix.accounts.iter())
+        .filter(|meta| !meta.is_signer && !program_ids.contains(&meta.pubkey))
+        .map(|meta| meta.pubkey)
+        .collect();
+
+    let total = tables.len();
+    let (kept, pruned): (Vec<_>, Vec<_>) = tables.into_iter().partition(|table| {
+        table
+            .addresses
+            .iter()
+            .any(|address| loadable.contains(address))
+    });
+    if !pruned.is_empty() {
+        debug!(
+            "pruned {} of {} lookup tables ({} addresses)",
+            pruned.len(),
+            total,
+            pruned
+                .iter()
+                .map(|table| table.addresses.len())
+                .sum::<usize>()
+        );
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
 
-    let blockhash = client.get_latest_blockhash()?;
-    let tx = VersionedTransaction::try_new(
-        VersionedMessage::V0(v0::Message::try_compile(
+    use base64::{Engine, prelude::BASE64_STANDARD};
+    use solana_sdk::{
+        account::Account,
+        address_lookup_table::AddressLookupTableAccount,
+        commitment_config::CommitmentConfig,
+        compute_budget::ComputeBudgetInstruction,
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        message::{VersionedMessage, v0},
+        pubkey::Pubkey,
+        signature::Keypair,
+        signer::Signer,
+        system_transaction,
+        transaction::VersionedTransaction,
+    };
+
+    use super::{
+        ConfirmationConfig, SendResult, adjusted_compute_unit_limit, decode_transactions,
+        estimate_fee, fee_percentile, missing_keys, prepare_bundle, prune_lookup_tables,
+        set_compute_unit_limit,
+    };
+
+    #[test]
+    fn test_decode_transactions() {
+        let payer = Keypair::new();
+        let tx = VersionedTransaction::from(system_transaction::transfer(
+            &payer,
             &payer.pubkey(),
-            &instructions,
-            &address_lookup_table_accounts,
-            blockhash,
-        )?),
-        &[payer],
-    )?;
+            1,
+            Hash::default(),
+        ));
+        let line = BASE64_STANDARD.encode(bincode::serialize(&tx).unwrap());
+        let content = format!("# captured bundle\n{}\n\n{}\n", line, line);
+
+        let txs = decode_transactions(&content).unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0].signatures, tx.signatures);
+
+        let err = decode_transactions(&format!("{}\nnot base64!", line)).unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn test_prepare_bundle() {
+        let payer = Keypair::new();
+        let transfer = |blockhash| {
+            VersionedTransaction::from(system_transaction::transfer(
+                &payer,
+                &Pubkey::new_unique(),
+                1,
+                blockhash,
+            ))
+        };
+        let mut bundle = [transfer(Hash::default()), transfer(Hash::default())];
+        prepare_bundle(&mut bundle, None).unwrap();
+
+        let mut mixed = [transfer(Hash::default()), transfer(Hash::new_unique())];
+        let err = prepare_bundle(&mut mixed, None).unwrap_err();
+        assert!(err.to_string().contains("transaction 2 uses blockhash"));
+        assert!(prepare_bundle(&mut [], None).is_err());
+        let mut oversized = [(); 6].map(|_| transfer(Hash::default()));
+        assert!(prepare_bundle(&mut oversized, None).is_err());
+
+        // unsigned only goes out signed by the payer
+        let mut unsigned = [bundle[0].clone()];
+        unsigned[0].signatures = vec![Default::default()];
+        let err = prepare_bundle(&mut unsigned, None).unwrap_err();
+        assert!(err.to_string().contains("unsigned, pass --sign"));
+        prepare_bundle(&mut unsigned, Some(&payer)).unwrap();
+        assert_eq!(unsigned[0].signatures, bundle[0].signatures);
+
+        // a signature by someone else is caught, not re-signed
+        let mut forged = [transfer(Hash::default())];
+        forged[0].signatures = vec![Keypair::new().sign_message(&[1])];
+        assert!(prepare_bundle(&mut forged, Some(&payer)).is_err());
+    }
+
+    #[test]
+    fn test_confirmation_config() {
+        let poll = Duration::from_millis(500);
+        let commitment = CommitmentConfig::confirmed();
+        assert!(ConfirmationConfig::new(poll, Duration::from_secs(5), commitment).is_ok());
+        assert!(ConfirmationConfig::new(poll, poll, commitment).is_err());
+        assert!(
+            ConfirmationConfig::new(Duration::ZERO, Duration::from_secs(5), commitment).is_err()
+        );
+    }
+
+    #[test]
+    fn test_fee_percentile() {
+        assert_eq!(fee_percentile(&mut [], 75), 0);
+        let mut fees = [500, 0, 100, 10_000, 0];
+        assert_eq!(fee_percentile(&mut fees, 0), 0);
+        assert_eq!(fee_percentile(&mut fees, 50), 100);
+        assert_eq!(fee_percentile(&mut fees, 75), 500);
+        assert_eq!(fee_percentile(&mut fees, 100), 10_000);
+    }
+
+    #[test]
+    fn test_missing_keys() {
+        let keys = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        let accounts = [Some(Account::default()), None, Some(Account::default())];
+        assert_eq!(missing_keys(&keys, &accounts), vec![keys[1]]);
+        assert!(missing_keys(&keys, &[(); 3].map(|_| Some(Account::default()))).is_empty());
+    }
 
-    Ok(tx)
+    #[test]
+    fn test_prune_lookup_tables() {
+        let payer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        let accounts: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let instructions = vec![vec![Instruction::new_with_bytes(
+            program_id,
+            &[],
+            vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(accounts[0], false),
+                AccountMeta::new_readonly(accounts[1], false),
+                AccountMeta::new_readonly(accounts[2], false),
+            ],
+        )]];
+        let table = |addresses: Vec<Pubkey>| AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses,
+        };
+        let junk = || (0..20).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        let covering = table([junk(), vec![accounts[0]]].concat());
+        let tables = vec![
+            table(junk()),
+            covering.clone(),
+            // the payer and the program can't be loaded from a table
+            table(vec![payer, program_id]),
+            table([vec![accounts[1]], junk()].concat()),
+        ];
+
+        let pruned = prune_lookup_tables(tables.clone(), &instructions);
+        let keys: Vec<Pubkey> = pruned.iter().map(|table| table.key).collect();
+        assert_eq!(keys, vec![tables[1].key, tables[3].key]);
+        assert_eq!(pruned[0].addresses, covering.addresses);
+        assert!(prune_lookup_tables(tables[..1].to_vec(), &instructions).is_empty());
+
+        // the compiled message only references the kept tables
+        let size = |tables: &[AddressLookupTableAccount]| {
+            let message =
+                v0::Message::try_compile(&payer, &instructions[0], tables, Hash::default())
+                    .unwrap();
+            assert_eq!(message.address_table_lookups.len(), 2);
+            bincode::serialize(&VersionedMessage::V0(message))
+                .unwrap()
+                .len()
+        };
+        assert!(size(&pruned) <= size(&tables));
+    }
+
+    #[test]
+    fn test_estimate_fee() {
+        let payer = Keypair::new();
+        let transfer = system_transaction::transfer(&payer, &payer.pubkey(), 1, Hash::default());
+        let tx = |compute_budget: Vec<Instruction>| {
+            let mut instructions = compute_budget;
+            instructions.push(Instruction::new_with_bytes(
+                Pubkey::new_unique(),
+                &[],
+                vec![AccountMeta::new(payer.pubkey(), true)],
+            ));
+            let message =
+                v0::Message::try_compile(&payer.pubkey(), &instructions, &[], Hash::default())
+                    .unwrap();
+            VersionedTransaction::try_new(VersionedMessage::V0(message), &[&payer]).unwrap()
+        };
+
+        assert_eq!(estimate_fee(&[VersionedTransaction::from(transfer)]), 5000);
+        // no price, no priority fee
+        assert_eq!(estimate_fee(&[tx(vec![])]), 5000);
+        let priced = tx(vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(300_000),
+            ComputeBudgetInstruction::set_compute_unit_price(10_001),
+        ]);
+        // 300_000 * 10_001 micro-lamports, rounded up
+        assert_eq!(estimate_fee(&[priced.clone()]), 5000 + 3001);
+        // without a limit every instruction gets the default
+        let unlimited = tx(vec![ComputeBudgetInstruction::set_compute_unit_price(
+            1_000_000,
+        )]);
+        assert_eq!(estimate_fee(&[unlimited]), 5000 + 200_000);
+        assert_eq!(estimate_fee(&[priced.clone(), priced]), 2 * (5000 + 3001));
+    }
+
+    #[test]
+    fn test_slots_to_land() {
+        let sent = SendResult {
+            submit_slot: Some(100),
+            landed_slot: Some(102),
+            ..Default::default()
+        };
+        assert_eq!(sent.slots_to_land(), Some(2));
+        let unconfirmed = SendResult {
+            landed_slot: None,
+            ..sent
+        };
+        assert_eq!(unconfirmed.slots_to_land(), None);
+    }
+
+    #[test]
+    fn test_adjusted_compute_unit_limit() {
+        assert_eq!(
+            adjusted_compute_unit_limit(Some(100_000), 10),
+            Some(110_000)
+        );
+        // rounded up
+        assert_eq!(adjusted_compute_unit_limit(Some(101), 10), Some(112));
+        assert_eq!(
+            adjusted_compute_unit_limit(Some(1_300_000), 10),
+            Some(1_400_000)
+        );
+        assert_eq!(adjusted_compute_unit_limit(Some(0), 10), None);
+        assert_eq!(adjusted_compute_unit_limit(None, 10), None);
+    }
+
+    #[test]
+    fn test_set_compute_unit_limit() {
+        let payer = Keypair::new();
+        let compile = |instructions: &[Instruction]| {
+            VersionedMessage::V0(
+                v0::Message::try_compile(&payer.pubkey(), instructions, &[], Hash::default())
+                    .unwrap(),
+            )
+        };
+        let mut message = compile(&[
+            ComputeBudgetInstruction::set_compute_unit_limit(400_000),
+            ComputeBudgetInstruction::set_compute_unit_price(1_000),
+        ]);
+        assert!(set_compute_unit_limit(&mut message, 120_000));
+        assert_eq!(
+            message,
+            compile(&[
+                ComputeBudgetInstruction::set_compute_unit_limit(120_000),
+                ComputeBudgetInstruction::set_compute_unit_price(1_000),
+            ])
+        );
+
+        let mut message = compile(&[ComputeBudgetInstruction::set_compute_unit_price(1_000)]);
+        assert!(!set_compute_unit_limit(&mut message, 120_000));
+    }
 }
@@ -0,0 +1,192 @@
+use std::{collections::HashSet, fs, path::Path, str::FromStr};
+
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use tokio::sync::RwLock;
+
+use crate::dex::DexSelection;
+
+/// Mints scheduled for scanning: statically configured ones plus
+/// whatever discovery currently reports
+pub struct Watchlist {
+    static_mints: Vec<Pubkey>,
+    discovered: RwLock<Vec<Pubkey>>,
+}
+
+impl Watchlist {
+    pub fn new(static_mints: Vec<Pubkey>) -> Self {
+        Self {
+            static_mints,
+            discovered: RwLock::new(vec![]),
+        }
+    }
+
+    /// Static mints first, then discovered ones, without duplicates
+    pub async fn mints(&self) -> Vec<Pubkey> {
+        let discovered = self.discovered.read().await;
+        let mut seen = HashSet::new();
+        self.static_mints
+            .iter()
+            .chain(discovered.iter())
+            .filter(|mint| seen.insert(**mint))
+            .copied()
+            .collect()
+    }
+
+    pub async fn set_discovered(&self, mints: Vec<Pubkey>) {
+        *self.discovered.write().await = mints;
+    }
+}
+
+/// One mint per line, blank lines and `#` comments are ignored
+pub fn read_mints_file(path: &Path) -> Result<Vec<Pubkey>> {
+    fs::read_to_string(path)?
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| Pubkey::from_str(line).map_err(|e| anyhow!("invalid mint {}: {}", line, e)))
+        .collect()
+}
+
+/// Per-mint settings from a watchlist file, unset ones fall back to the
+/// command line. Amounts are ui amounts of the base mint.
+#[derive(Debug, Clone, Default)]
+pub struct MintOverrides {
+    pub amount_in: Option<f64>,
+    pub min_profit: Option<f64>,
+    pub tip_percentage: Option<f64>,
+    pub dexes: Option<DexSelection>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WatchlistEntry {
+    mint: String,
+    amount_in: Option<f64>,
+    min_profit: Option<f64>,
+    tip_percentage: Option<f64>,
+    // comma-separated jupiter labels, as --dexes
+    dexes: Option<String>,
+}
+
+impl WatchlistEntry {
+    fn resolve(self, allow_unknown_dexes: bool) -> Result<(Pubkey, MintOverrides)> {
+        let mint = Pubkey::from_str(&self.mint)
+            .map_err(|e| anyhow!("invalid mint {}: {}", self.mint, e))?;
+        if self.amount_in.is_some_and(|amount| amount <= 0.0) {
+            return Err(anyhow!("amount_in must be positive"));
+        }
+        if self.min_profit.is_some_and(|profit| profit < 0.0) {
+            return Err(anyhow!("min_profit must not be negative"));
+        }
+        if self
+            .tip_percentage
+            .is_some_and(|pct| !(0.0..=1.0).contains(&pct))
+        {
+            return Err(anyhow!("tip_percentage must be in [0.0, 1.0]"));
+        }
+        let dexes = self
+            .dexes
+            .map(|dexes| DexSelection::parse(&dexes, allow_unknown_dexes))
+            .transpose()
+            .map_err(|e| anyhow!(e))?;
+        Ok((
+            mint,
+            MintOverrides {
+                amount_in: self.amount_in,
+                min_profit: self.min_profit,
+                tip_percentage: self.tip_percentage,
+                dexes,
+            },
+        ))
+    }
+}
+
+/// A JSON array of `{"mint": .., "amount_in": .., "min_profit": ..,
+/// "tip_percentage": .., "dexes": ..}`, everything but the mint optional.
+/// Entries keep their order, a later duplicate replaces the earlier one.
+/// Unknown dex labels are an error unless `allow_unknown_dexes`, as for
+/// `--dexes`.
+pub fn parse_watchlist(
+    content: &str,
+    allow_unknown_dexes: bool,
+) -> Result<Vec<(Pubkey, MintOverrides)>> {
+    let entries: Vec<WatchlistEntry> = serde_json::from_str(content)?;
+    let mut resolved: Vec<(Pubkey, MintOverrides)> = vec![];
+    for (index, entry) in entries.into_iter().enumerate() {
+        let (mint, overrides) = entry
+            .resolve(allow_unknown_dexes)
+            .map_err(|e| anyhow!("watchlist entry {}: {}", index, e))?;
+        match resolved.iter_mut().find(|(m, _)| *m == mint) {
+            Some((_, existing)) => *existing = overrides,
+            None => resolved.push((mint, overrides)),
+        }
+    }
+    Ok(resolved)
+}
+
+pub fn read_watchlist_file(
+    path: &Path,
+    allow_unknown_dexes: bool,
+) -> Result<Vec<(Pubkey, MintOverrides)>> {
+    parse_watchlist(&fs::read_to_string(path)?, allow_unknown_dexes)
+        .map_err(|e| anyhow!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dex::Dex;
+
+    use super::parse_watchlist;
+
+    const MINT_A: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const MINT_B: &str = "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB";
+
+    #[test]
+    fn test_parse_watchlist() {
+        let content = format!(
+            r#"[
+                {{"mint": "{MINT_A}", "amount_in": 0.5, "dexes": "Raydium,Whirlpool"}},
+                {{"mint": "{MINT_B}", "min_profit": 0.001, "tip_percentage": 0.3}},
+                {{"mint": "{MINT_A}", "amount_in": 0.2}}
+            ]"#
+        );
+        let entries = parse_watchlist(&content, false).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0.to_string(), MINT_A);
+        assert_eq!(entries[0].1.amount_in, Some(0.2));
+        assert!(entries[0].1.dexes.is_none());
+        assert_eq!(entries[1].1.min_profit, Some(0.001));
+        assert_eq!(entries[1].1.tip_percentage, Some(0.3));
+
+        let content = format!(r#"[{{"mint": "{MINT_A}", "dexes": "Raydium,Whirlpool"}}]"#);
+        let dexes = parse_watchlist(&content, false).unwrap()[0]
+            .1
+            .dexes
+            .clone()
+            .unwrap();
+        assert_eq!(dexes.dexes.bits(), (Dex::RAYDIUM | Dex::WHIRLPOOL).bits());
+        assert!(dexes.custom.is_empty());
+
+        let content = format!(r#"[{{"mint": "{MINT_A}", "dexes": "Raydium,SomeNewAmm"}}]"#);
+        assert!(parse_watchlist(&content, false).is_err());
+        let dexes = parse_watchlist(&content, true).unwrap()[0]
+            .1
+            .dexes
+            .clone()
+            .unwrap();
+        assert_eq!(dexes.custom, vec!["SomeNewAmm".to_string()]);
+
+        let err = parse_watchlist(
+            &format!(r#"[{{"mint": "{MINT_A}", "tip_percentage": 2}}]"#),
+            false,
+        )
+        .unwrap_err();
+        assert!(err.to_string().starts_with("watchlist entry 0"));
+        assert!(
+            parse_watchlist(&format!(r#"[{{"mint": "{MINT_A}", "tip": 0.3}}]"#), false).is_err()
+        );
+        assert!(parse_watchlist(r#"[{"mint": "nope"}]"#, false).is_err());
+    }
+}
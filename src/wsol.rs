@@ -0,0 +1,215 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, instruction::Instruction, program_pack::Pack,
+    pubkey::Pubkey, signature::Keypair, signer::Signer, system_instruction,
+    transaction::Transaction,
+};
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use spl_token::amount_to_ui_amount;
+use tracing::{debug, info, warn};
+
+use crate::{get_rpc_client, inflight, token::get_ata};
+
+#[derive(Debug, Clone)]
+pub struct AutoWrapConfig {
+    // lamports
+    pub target: u64,
+    // lamports, wrapping starts once the WSOL balance falls below this
+    pub min: u64,
+    // lamports of native SOL that are never wrapped
+    pub reserve: u64,
+    pub interval: Duration,
+    pub compute_unit_price: u64,
+}
+
+impl AutoWrapConfig {
+    /// Lamports to wrap so the WSOL balance goes back to `target`,
+    /// limited by the native balance above the reserve
+    pub fn wrap_amount(&self, wsol_balance: u64, sol_balance: u64) -> u64 {
+        if wsol_balance >= self.min {
+            return 0;
+        }
+        let wanted = self.target.saturating_sub(wsol_balance);
+        let available = sol_balance.saturating_sub(self.reserve);
+        wanted.min(available)
+    }
+}
+
+/// Create the WSOL ATA if needed, move `lamports` into it and sync
+pub fn wrap_instructions(owner: &Pubkey, lamports: u64) -> Result<Vec<Instruction>> {
+    let native_mint = spl_token::native_mint::id();
+    let ata = get_ata(owner, &native_mint);
+    Ok(vec![
+        create_associated_token_account_idempotent(owner, owner, &native_mint, &spl_token::id()),
+        system_instruction::transfer(owner, &ata, lamports),
+        spl_token::instruction::sync_native(&spl_token::id(), &ata)?,
+    ])
+}
+
+/// Close the WSOL ATA, its balance and rent go back to the owner as native SOL
+pub fn unwrap_instructions(owner: &Pubkey) -> Result<Vec<Instruction>> {
+    let ata = get_ata(owner, &spl_token::native_mint::id());
+    Ok(vec![spl_token::instruction::close_account(
+        &spl_token::id(),
+        &ata,
+        owner,
+        owner,
+        &[],
+    )?])
+}
+
+/// WSOL balance of the owner's ATA, 0 when the account doesn't exist yet
+pub fn get_wsol_balance(client: &RpcClient, owner: &Pubkey) -> Result<u64> {
+    let ata = get_ata(owner, &spl_token::native_mint::id());
+    let account = client
+        .get_account_with_commitment(&ata, client.commitment())?
+        .value;
+    match account {
+        Some(account) => Ok(spl_token::state::Account::unpack(&account.data)?.amount),
+        None => Ok(0),
+    }
+}
+
+/// Periodically top up the WSOL ATA. Runs under the maintenance lock so
+/// it never overlaps an arbitrage execution.
+pub async fn run_auto_wrap(payer: Arc<Keypair>, config: AutoWrapConfig) {
+    loop {
+        if let Err(e) = top_up(&payer, &config).await {
+            warn!("auto wrap: {}", e);
+        }
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AutoUnwrapConfig {
+    pub interval: Duration,
+    pub compute_unit_price: u64,
+}
+
+/// Periodically close the WSOL ATA so leftovers of SOL based arbs end up
+/// as native SOL. A round is skipped while an execution is in flight.
+pub async fn run_auto_unwrap(payer: Arc<Keypair>, config: AutoUnwrapConfig) {
+    loop {
+        tokio::time::sleep(config.interval).await;
+        if let Err(e) = unwrap_all(&payer, &config) {
+            warn!("auto unwrap: {}", e);
+        }
+    }
+}
+
+fn unwrap_all(payer: &Keypair, config: &AutoUnwrapConfig) -> Result<()> {
+    let Some(_maintenance) = inflight::try_maintenance() else {
+        debug!("auto unwrap: execution in flight, skipping");
+        return Ok(());
+    };
+    let client = get_rpc_client()?;
+    let owner = payer.pubkey();
+    let ata = get_ata(&owner, &spl_token::native_mint::id());
+    let Some(account) = client
+        .get_account_with_commitment(&ata, client.commitment())?
+        .value
+    else {
+        return Ok(());
+    };
+    let wsol_balance = spl_token::state::Account::unpack(&account.data)?.amount;
+
+    let mut ixs = vec![ComputeBudgetInstruction::set_compute_unit_price(
+        config.compute_unit_price,
+    )];
+    ixs.extend(unwrap_instructions(&owner)?);
+    let tx = Transaction::new_signed_with_payer(
+        &ixs,
+        Some(&owner),
+        &[payer],
+        client.get_latest_blockhash()?,
+    );
+    let signature = client.send_and_confirm_transaction(&tx)?;
+    info!(
+        "🔄 Unwrapped {} sol (rent {} sol), tx: {}",
+        amount_to_ui_amount(wsol_balance, 9),
+        amount_to_ui_amount(account.lamports.saturating_sub(wsol_balance), 9),
+        signature
+    );
+    Ok(())
+}
+
+async fn top_up(payer: &Keypair, config: &AutoWrapConfig) -> Result<()> {
+    let client = get_rpc_client()?;
+    let owner = payer.pubkey();
+    if get_wsol_balance(&client, &owner)? >= config.min {
+        return Ok(());
+    }
+
+    let _maintenance = inflight::maintenance().await;
+    // balances may have moved while waiting for executions to finish
+    let wsol_balance = get_wsol_balance(&client, &owner)?;
+    let sol_balance = client.get_balance(&owner)?;
+    let lamports = config.wrap_amount(wsol_balance, sol_balance);
+    debug!(
+        "auto wrap: wsol {} lamports, sol {} lamports, wrapping {} lamports",
+        wsol_balance, sol_balance, lamports
+    );
+    if lamports == 0 {
+        if wsol_balance < config.min {
+            warn!(
+                "auto wrap: WSOL balance {} sol is low but nothing above the reserve to wrap",
+                amount_to_ui_amount(wsol_balance, 9)
+            );
+        }
+        return Ok(());
+    }
+
+    let mut ixs = vec![ComputeBudgetInstruction::set_compute_unit_price(
+        config.compute_unit_price,
+    )];
+    ixs.extend(wrap_instructions(&owner, lamports)?);
+    let tx = Transaction::new_signed_with_payer(
+        &ixs,
+        Some(&owner),
+        &[payer],
+        client.get_latest_blockhash()?,
+    );
+    let signature = client.send_and_confirm_transaction(&tx)?;
+    info!(
+        "🔄 Wrapped {} sol, WSOL balance: {} sol, tx: {}",
+        amount_to_ui_amount(lamports, 9),
+        amount_to_ui_amount(wsol_balance + lamports, 9),
+        signature
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::AutoWrapConfig;
+
+    #[test]
+    fn test_wrap_amount() {
+        let config = AutoWrapConfig {
+            target: 2_000_000_000,
+            min: 500_000_000,
+            reserve: 10_000_000,
+            interval: Duration::from_secs(60),
+            compute_unit_price: 10_000,
+        };
+        // above the minimum, nothing to do
+        assert_eq!(config.wrap_amount(600_000_000, 5_000_000_000), 0);
+        // restore the target
+        assert_eq!(
+            config.wrap_amount(400_000_000, 5_000_000_000),
+            1_600_000_000
+        );
+        // never dip into the reserve
+        assert_eq!(
+            config.wrap_amount(400_000_000, 1_010_000_000),
+            1_000_000_000
+        );
+        assert_eq!(config.wrap_amount(0, 5_000_000), 0);
+    }
+}